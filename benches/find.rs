@@ -0,0 +1,65 @@
+// Compares the collecting buscador functions with their early-exit versions on
+// a large synthetic page. Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use scraper::{find_by_tag_name, find_first_by_tag_name, iter_select, select, ElementBuilder, Node};
+
+const ROUNDS: u32 = 20;
+
+// a catalogue page: a head with title and meta first, then lots of product cards
+fn large_page(products: usize) -> Node {
+    let head = ElementBuilder::new("head")
+        .child(ElementBuilder::new("title").text("Catalogue"))
+        .child(ElementBuilder::new("meta").attr("name", "description").attr("content", "Everything we sell"));
+    let cards = (0..products).map(|i| {
+        ElementBuilder::new("div")
+            .attr("class", "product")
+            .child(ElementBuilder::new("h2").text(&format!("Product {}", i)))
+            .child(ElementBuilder::new("p").attr("class", "price").text(&format!("${}.99", i % 100)))
+            .child(ElementBuilder::new("a").attr("href", &format!("/p/{}", i)).text("details"))
+            .build()
+    });
+    ElementBuilder::new("html")
+        .child(head)
+        .child(ElementBuilder::new("body").children(cards))
+        .build()
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn report(name: &str, all: Duration, first: Duration) {
+    println!(
+        "{:<34} collect all {:>10.3?}   first only {:>10.3?}   {:>8.1}x",
+        name,
+        all,
+        first,
+        all.as_secs_f64() / first.as_secs_f64().max(1e-9)
+    );
+}
+
+fn main() {
+    let page = large_page(50_000);
+
+    report(
+        "<title> by tag name",
+        time(|| { black_box(find_by_tag_name("title", &page).first().copied()); }),
+        time(|| { black_box(find_first_by_tag_name("title", &page)); }),
+    );
+    report(
+        "meta[name=description] by selector",
+        time(|| { black_box(select(&page, "meta[name=description]").unwrap().first().copied()); }),
+        time(|| { black_box(iter_select(&page, "meta[name=description]").unwrap().next()); }),
+    );
+    report(
+        "first 10 prices by selector",
+        time(|| { black_box(select(&page, ".product .price").unwrap().into_iter().take(10).count()); }),
+        time(|| { black_box(iter_select(&page, ".product .price").unwrap().take(10).count()); }),
+    );
+}
@@ -0,0 +1,179 @@
+// #[derive(Extract)] for the scraper crate, see src/extract.rs there for what
+// the attributes mean. The expansion calls the helpers in scraper::extract and
+// compiles every selector once, into a static next to the field that uses it.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Token, Type};
+
+#[proc_macro_derive(Extract, attributes(select, select_all))]
+pub fn derive_extract(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+// how the field's type wraps the extracted value
+enum Shape<'a> {
+    Required(&'a Type),
+    Optional(&'a Type),
+    Many(&'a Type),
+}
+
+struct FieldSpec {
+    selector: LitStr,
+    parse: Option<LitStr>,
+    all: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "Extract can't be derived for generic structs"));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(name, "Extract can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(name, "Extract needs a struct with named fields"));
+    };
+
+    let mut lets = Vec::new();
+    let mut idents = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let spec = field_spec(field)?;
+        lets.push(field_extraction(ident, &field.ty, &spec)?);
+        idents.push(ident);
+    }
+
+    Ok(quote! {
+        impl ::scraper::extract::Extract for #name {
+            fn extract(node: &::scraper::Node) -> ::std::result::Result<Self, ::scraper::extract::ExtractError> {
+                #(#lets)*
+                ::std::result::Result::Ok(#name { #(#idents),* })
+            }
+        }
+    })
+}
+
+fn field_spec(field: &syn::Field) -> syn::Result<FieldSpec> {
+    let mut found = None;
+    for attr in &field.attrs {
+        let all = if attr.path().is_ident("select") {
+            false
+        } else if attr.path().is_ident("select_all") {
+            true
+        } else {
+            continue;
+        };
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(attr, "only one #[select] or #[select_all] per field"));
+        }
+        let (selector, parse) = attr.parse_args_with(|input: ParseStream| {
+            let selector: LitStr = input.parse()?;
+            let mut parse = None;
+            if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+                let key: Ident = input.parse()?;
+                if key != "parse" {
+                    return Err(syn::Error::new_spanned(&key, "expected `parse = \"...\"`"));
+                }
+                input.parse::<Token![=]>()?;
+                let value: LitStr = input.parse()?;
+                if value.value() != "money" {
+                    return Err(syn::Error::new_spanned(&value, "unknown parse, the only one is \"money\""));
+                }
+                parse = Some(value);
+                input.parse::<Option<Token![,]>>()?;
+            }
+            Ok((selector, parse))
+        })?;
+        found = Some(FieldSpec { selector, parse, all });
+    }
+    found.ok_or_else(|| syn::Error::new_spanned(field, "every field needs #[select(\"...\")] or #[select_all(\"...\")]"))
+}
+
+// the T in Option<T> or Vec<T>
+fn wrapped<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    match arguments.args.first()? {
+        GenericArgument::Type(inner) if arguments.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+// whether the selector has a `::` outside strings, brackets and parentheses.
+// pseudo-elements may only come last, so that's a trailing ::text or ::attr();
+// the `::` in `a[href*="::"]` or `:not(..)` doesn't count. the compiled query
+// checks the same thing at runtime, see scraper::extract::compiled
+fn has_pseudo_element(selector: &str) -> bool {
+    let mut chars = selector.chars().peekable();
+    let mut quote = None;
+    let mut depth = 0usize;
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                chars.next();
+            }
+            (c, Some(open)) if c == open => quote = None,
+            (_, Some(_)) => {}
+            ('"' | '\'', None) => quote = Some(c),
+            ('[' | '(', None) => depth += 1,
+            (']' | ')', None) => depth = depth.saturating_sub(1),
+            (':', None) if depth == 0 && chars.peek() == Some(&':') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn field_extraction(ident: &Ident, ty: &Type, spec: &FieldSpec) -> syn::Result<TokenStream2> {
+    let shape = match (spec.all, wrapped(ty, "Vec"), wrapped(ty, "Option")) {
+        (true, Some(inner), _) => Shape::Many(inner),
+        (true, None, _) => return Err(syn::Error::new_spanned(ty, "#[select_all] needs a Vec<_> field")),
+        (false, _, Some(inner)) => Shape::Optional(inner),
+        (false, _, None) => Shape::Required(ty),
+    };
+    let selector = &spec.selector;
+    let field_name = ident.to_string();
+    // ::text and ::attr() give strings, anything else gives elements for a nested Extract
+    let values = has_pseudo_element(&selector.value());
+    if !values && spec.parse.is_some() {
+        return Err(syn::Error::new_spanned(selector, "parse needs a selector ending in ::text or ::attr(...)"));
+    }
+    let parse = match spec.parse {
+        Some(_) => quote!(::scraper::extract::Parse::Money),
+        None => quote!(::scraper::extract::Parse::Text),
+    };
+
+    let value = match (shape, values) {
+        (Shape::Many(inner), true) => quote!(::scraper::extract::all_values::<#inner>(node, query, #parse, #field_name, #selector)?),
+        (Shape::Many(inner), false) => quote!(::scraper::extract::all_nested::<#inner>(node, query, #field_name)?),
+        (Shape::Optional(inner), true) => quote!(::scraper::extract::first_value::<#inner>(node, query, #parse, #field_name, #selector)?),
+        (Shape::Optional(inner), false) => quote!(::scraper::extract::first_nested::<#inner>(node, query, #field_name)?),
+        (Shape::Required(inner), true) => quote! {
+            ::scraper::extract::required(::scraper::extract::first_value::<#inner>(node, query, #parse, #field_name, #selector)?, #field_name, #selector)?
+        },
+        (Shape::Required(inner), false) => quote! {
+            ::scraper::extract::required(::scraper::extract::first_nested::<#inner>(node, query, #field_name)?, #field_name, #selector)?
+        },
+    };
+
+    Ok(quote! {
+        let #ident = {
+            static QUERY: ::std::sync::LazyLock<::std::result::Result<::scraper::Query, ::scraper::QueryError>> =
+                ::std::sync::LazyLock::new(|| ::scraper::Query::compile(#selector));
+            let query = ::scraper::extract::compiled(&QUERY, #field_name, #selector, #values)?;
+            #value
+        };
+    })
+}
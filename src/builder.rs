@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use crate::{Attributes, Node};
+
+// Fluent construction of element nodes, mostly for fixtures and synthetic pages:
+//
+//     ElementBuilder::new("a").attr("href", "/next").text("Next").build()
+//
+// The html! macro below expands into these calls.
+
+#[derive(Debug, Default)]
+pub struct ElementBuilder {
+    tag_name: String,
+    attributes: Attributes,
+    children: Vec<Node>,
+}
+
+impl ElementBuilder {
+    pub fn new(tag_name: &str) -> Self {
+        ElementBuilder { tag_name: tag_name.to_string(), attributes: HashMap::new(), children: Vec::new() }
+    }
+
+    pub fn attr(mut self, name: &str, value: &str) -> Self {
+        self.attributes.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // takes a Node or another builder
+    pub fn child(mut self, child: impl Into<Node>) -> Self {
+        self.children.push(child.into());
+        self
+    }
+
+    pub fn children(mut self, children: impl IntoIterator<Item = Node>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.children.push(Node::Text(text.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Node {
+        Node::Element { tag_name: self.tag_name, attributes: self.attributes, children: self.children }
+    }
+}
+
+impl From<ElementBuilder> for Node {
+    fn from(builder: ElementBuilder) -> Node {
+        builder.build()
+    }
+}
+
+impl Node {
+    // shorthand for ElementBuilder::new
+    pub fn element(tag_name: &str) -> ElementBuilder {
+        ElementBuilder::new(tag_name)
+    }
+}
+
+// Declarative trees:
+//
+//     html! {
+//         div(class = "product", "data-sku" = sku) {
+//             h2 { "Widget" }
+//             p { "only " { price_node } " left" }
+//             br
+//         }
+//     }
+//
+// - `tag(name = value, ...) { children }`, where both the attribute list and the
+//   braces can be left out (`br`, `img(src = "x.png")`)
+// - attribute names are identifiers or string literals for names like `data-sku`,
+//   values are any expression implementing ToString
+// - string literals become text nodes
+// - `{ expr }` splices in anything that converts into a Node, e.g. a builder
+#[macro_export]
+macro_rules! html {
+    (@children [$($done:expr,)*]) => {
+        ::std::vec![$($done,)*]
+    };
+    (@children [$($done:expr,)*] $text:literal $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::Node::Text(($text).to_string()),] $($rest)*)
+    };
+    (@children [$($done:expr,)*] { $node:expr } $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::Node::from($node),] $($rest)*)
+    };
+    (@children [$($done:expr,)*] $tag:ident ( $($attrs:tt)* ) { $($inner:tt)* } $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::html!($tag ( $($attrs)* ) { $($inner)* }),] $($rest)*)
+    };
+    (@children [$($done:expr,)*] $tag:ident { $($inner:tt)* } $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::html!($tag { $($inner)* }),] $($rest)*)
+    };
+    (@children [$($done:expr,)*] $tag:ident ( $($attrs:tt)* ) $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::html!($tag ( $($attrs)* )),] $($rest)*)
+    };
+    (@children [$($done:expr,)*] $tag:ident $($rest:tt)*) => {
+        $crate::html!(@children [$($done,)* $crate::html!($tag),] $($rest)*)
+    };
+    (@name $name:ident) => { stringify!($name) };
+    (@name $name:literal) => { $name };
+
+    ($text:literal) => {
+        $crate::Node::Text(($text).to_string())
+    };
+    ($tag:ident ( $($name:tt = $value:expr),* $(,)? ) { $($inner:tt)* }) => {
+        $crate::ElementBuilder::new(stringify!($tag))
+            $(.attr($crate::html!(@name $name), &($value).to_string()))*
+            .children($crate::html!(@children [] $($inner)*))
+            .build()
+    };
+    ($tag:ident ( $($attrs:tt)* )) => {
+        $crate::html!($tag ( $($attrs)* ) {})
+    };
+    ($tag:ident { $($inner:tt)* }) => {
+        $crate::html!($tag () { $($inner)* })
+    };
+    ($tag:ident) => {
+        $crate::html!($tag () {})
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::Node;
+    use crate::builder::*;
+
+    #[test]
+    fn test_element_builder() {
+        let node = ElementBuilder::new("p")
+            .attr("class", "intro")
+            .text("Welcome to this ")
+            .child(Node::element("span").attr("id", "test_span").text("simple page"))
+            .build();
+
+        let Node::Element { tag_name, attributes, children } = node else { panic!("expected an element") };
+        assert_eq!(tag_name, "p");
+        assert_eq!(attributes.get("class"), Some(&"intro".to_string()));
+        assert_eq!(children.len(), 2);
+        assert!(matches!(&children[0], Node::Text(text) if text == "Welcome to this "));
+        assert!(matches!(&children[1], Node::Element { tag_name, attributes, .. }
+            if tag_name == "span" && attributes.get("id") == Some(&"test_span".to_string())));
+    }
+
+    #[test]
+    fn test_html_macro() {
+        let price = 12;
+        let node = html! {
+            div(class = "product", "data-sku" = "w-1",) {
+                h2 { "Widget" }
+                p { "only " { Node::element("b").text("3") } " left at " { Node::Text(price.to_string()) } }
+                br
+                img(src = "w.png")
+                ul { li { "a" } li() { "b" } }
+            }
+        };
+
+        let Node::Element { tag_name, attributes, children } = &node else { panic!("expected an element") };
+        assert_eq!(tag_name, "div");
+        let mut expected = HashMap::new();
+        expected.insert("class".to_string(), "product".to_string());
+        expected.insert("data-sku".to_string(), "w-1".to_string());
+        assert_eq!(attributes, &expected);
+
+        let tags: Vec<&str> = children.iter().map(|child| match child {
+            Node::Element { tag_name, .. } => tag_name.as_str(),
+            Node::Text(_) => "#text",
+        }).collect();
+        assert_eq!(tags, vec!["h2", "p", "br", "img", "ul"]);
+
+        let Node::Element { children: p_children, .. } = &children[1] else { panic!() };
+        assert_eq!(p_children.len(), 4);
+        assert!(matches!(&p_children[3], Node::Text(text) if text == "12"));
+        assert!(matches!(&children[3], Node::Element { attributes, children, .. }
+            if attributes.get("src") == Some(&"w.png".to_string()) && children.is_empty()));
+        assert!(matches!(html!("hi"), Node::Text(text) if text == "hi"));
+    }
+}
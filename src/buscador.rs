@@ -1,6 +1,10 @@
-use std::collections::{HashMap};
-use crate::tokenizer::Tokens;
+use regex::Regex;
 use crate::Node;
+use crate::predicate::Predicate;
+use crate::query::{Extracted, Query};
+use crate::selector::{parse_selector, Matches};
+use crate::traversal::pre_order;
+use crate::xpath::{XPath, XPathValue};
 
 // vamos a buscar en el árbol por una hoja specifica. 
 
@@ -24,7 +28,7 @@ where
 
 pub fn find_by_tag_name<'a> (tag_name: &'a str, root_node: &'a Node) -> Vec<&'a Node> {
     let mut matches : Vec<&Node> = Vec::new();
-    find_node(&root_node, &|node| {
+    find_node(root_node, &|node| {
         if let Node::Element { tag_name: node_tag_name, .. } = node {
             node_tag_name == tag_name
         } else {
@@ -38,7 +42,7 @@ pub fn find_by_tag_name<'a> (tag_name: &'a str, root_node: &'a Node) -> Vec<&'a
 
 pub fn find_by_attribute <'a>(attribute_name: &'a str, attribute_value: &'a str, root_node: &'a Node) -> Vec<&'a Node> {
     let mut matches : Vec<&Node> = Vec::new();
-    find_node(&root_node, &|node| {
+    find_node(root_node, &|node| {
         if let Node::Element { attributes, .. } = node {
             attributes.get(attribute_name) == Some(&attribute_value.to_string())
         } else {
@@ -50,7 +54,7 @@ pub fn find_by_attribute <'a>(attribute_name: &'a str, attribute_value: &'a str,
 
 pub fn find_by_text <'a> (text: &'a str, root_node: &'a Node) -> Vec<&'a Node> {
     let mut matches : Vec<&Node> = Vec::new();
-    find_node(&root_node, &|node| {
+    find_node(root_node, &|node| {
         if let Node::Text(node_text)= node {
             node_text == text
         } else {
@@ -60,171 +64,220 @@ pub fn find_by_text <'a> (text: &'a str, root_node: &'a Node) -> Vec<&'a Node> {
     matches
 }
 
-#[cfg(test)]
+// Lazy versions of the functions above. They walk the tree only as far as the
+// caller pulls, so find_first_by_tag_name("title", ..) stops at the first title
+// instead of visiting every node of the page.
+
+pub fn iter_nodes<F>(root_node: &Node, predicate: F) -> impl Iterator<Item = &Node>
+where
+    F: Fn(&Node) -> bool,
+{
+    pre_order(root_node).map(|(node, _)| node).filter(move |node| predicate(node))
+}
+
+pub fn iter_by_tag_name<'a> (tag_name: &'a str, root_node: &'a Node) -> impl Iterator<Item = &'a Node> {
+    iter_nodes(root_node, move |node| matches!(node, Node::Element { tag_name: node_tag_name, .. } if node_tag_name == tag_name))
+}
+
+pub fn iter_by_attribute<'a> (attribute_name: &'a str, attribute_value: &'a str, root_node: &'a Node) -> impl Iterator<Item = &'a Node> {
+    iter_nodes(root_node, move |node| {
+        matches!(node, Node::Element { attributes, .. } if attributes.get(attribute_name).is_some_and(|value| value == attribute_value))
+    })
+}
+
+pub fn iter_by_text<'a> (text: &'a str, root_node: &'a Node) -> impl Iterator<Item = &'a Node> {
+    iter_nodes(root_node, move |node| matches!(node, Node::Text(node_text) if node_text == text))
+}
+
+// an invalid selector is reported before any walking happens
+pub fn iter_select<'a> (root_node: &'a Node, selector: &str) -> Result<Matches<'static, 'a>, String> {
+    Ok(Query::compile(selector).map_err(|error| error.to_string())?.into_matches(root_node))
+}
+
+pub fn find_first_node<F>(root_node: &Node, predicate: F) -> Option<&Node>
+where
+    F: Fn(&Node) -> bool,
+{
+    iter_nodes(root_node, predicate).next()
+}
+
+pub fn find_first_by_tag_name<'a> (tag_name: &'a str, root_node: &'a Node) -> Option<&'a Node> {
+    iter_by_tag_name(tag_name, root_node).next()
+}
+
+pub fn find_first_by_attribute<'a> (attribute_name: &'a str, attribute_value: &'a str, root_node: &'a Node) -> Option<&'a Node> {
+    iter_by_attribute(attribute_name, attribute_value, root_node).next()
+}
+
+pub fn find_first_by_text<'a> (text: &'a str, root_node: &'a Node) -> Option<&'a Node> {
+    iter_by_text(text, root_node).next()
+}
+
+pub fn find_first_by_selector<'a> (root_node: &'a Node, selector: &str) -> Result<Option<&'a Node>, String> {
+    Ok(iter_select(root_node, selector)?.next())
+}
+
+// how find_by_text_match compares text
+#[derive(Debug, Clone)]
+pub enum TextMatch {
+    Equals(String),
+    Contains(String),
+    StartsWith(String),
+    // equal once both sides are lowercased
+    EqualsIgnoreCase(String),
+    // equal once both sides are trimmed and every run of whitespace is one space
+    EqualsIgnoreWhitespace(String),
+    Regex(Regex),
+}
+
+impl TextMatch {
+    pub fn regex(pattern: &str) -> Result<TextMatch, String> {
+        Regex::new(pattern).map(TextMatch::Regex).map_err(|error| error.to_string())
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            TextMatch::Equals(expected) => text == expected,
+            TextMatch::Contains(expected) => text.contains(expected.as_str()),
+            TextMatch::StartsWith(expected) => text.starts_with(expected.as_str()),
+            TextMatch::EqualsIgnoreCase(expected) => text.to_lowercase() == expected.to_lowercase(),
+            TextMatch::EqualsIgnoreWhitespace(expected) => {
+                text.split_whitespace().eq(expected.split_whitespace())
+            }
+            TextMatch::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+// what text find_by_text_match looks at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextScope {
+    // each text node on its own; the element holding a matching text node is returned
+    OwnText,
+    // an element's text with all its descendants joined up, so `<b>Out</b> of stock`
+    // reads "Out of stock". only the innermost matching elements are returned,
+    // not every ancestor around them. script and style text is ignored
+    Descendants,
+}
+
+// elements whose text matches, in document order, e.g.
+// find_by_text_match(&TextMatch::regex(r"\$\d+\.\d\d")?, TextScope::Descendants, &root)
+pub fn find_by_text_match<'a> (text_match: &TextMatch, scope: TextScope, root_node: &'a Node) -> Vec<&'a Node> {
+    let mut matches : Vec<&Node> = Vec::new();
+    match scope {
+        TextScope::OwnText => find_node(root_node, &|node| {
+            if let Node::Element { children, .. } = node {
+                children.iter().any(|child| matches!(child, Node::Text(text) if text_match.is_match(text)))
+            } else {
+                false
+            }
+        }, &mut matches),
+        TextScope::Descendants => {
+            find_innermost_text(root_node, text_match, &mut String::new(), &mut matches);
+        }
+    }
+    matches
+}
+
+// appends the node's text to `text`, which holds everything before it, so each
+// element's combined text is the slice it added and nothing gets copied per
+// level. an element goes into matches when its text matches and nothing inside
+// it did. script and style hold code, not text, so they're left out
+fn find_innermost_text<'a>(node: &'a Node, text_match: &TextMatch, text: &mut String, matches: &mut Vec<&'a Node>) {
+    match node {
+        Node::Text(value) => text.push_str(value),
+        Node::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case("script") || tag_name.eq_ignore_ascii_case("style") => {}
+        Node::Element { children, .. } => {
+            let start = text.len();
+            let found_before = matches.len();
+            for child in children {
+                find_innermost_text(child, text_match, text, matches);
+            }
+            if matches.len() == found_before && text_match.is_match(&text[start..]) {
+                matches.push(node);
+            }
+        }
+    }
+}
 
+// elements with `class` as one of their class tokens, so "container" finds
+// class="container wide" too (find_by_attribute compares the whole string)
+pub fn find_by_class<'a> (class: &'a str, root_node: &'a Node) -> Vec<&'a Node> {
+    let mut matches : Vec<&Node> = Vec::new();
+    find_node(root_node, &|node| node.class_list().contains(class), &mut matches);
+    matches
+}
+
+// combined conditions, e.g.
+// find_by_predicate(&tag("a").and(has_class("btn")).and(attr_starts_with("href", "/cart")), &root)
+pub fn find_by_predicate<'a> (predicate: &Predicate, root_node: &'a Node) -> Vec<&'a Node> {
+    let mut matches : Vec<&Node> = Vec::new();
+    find_node(root_node, &|node| predicate.matches(node), &mut matches);
+    matches
+}
+
+// css selectors, e.g. select(&root, "div.product > h2 a[href]")
+// matches come back in document order. an invalid selector gives an error
+// saying what is wrong and where.
+pub fn select<'a> (root_node: &'a Node, selector: &str) -> Result<Vec<&'a Node>, String> {
+    let selector_list = parse_selector(selector)?;
+    Ok(selector_list.select(root_node))
+}
+
+// css selectors ending in ::text or ::attr(name), e.g.
+// extract(&root, "a.next::attr(href)")?.get()
+pub fn extract(root_node: &Node, selector: &str) -> Result<Extracted, String> {
+    Ok(Query::compile(selector).map_err(|error| error.to_string())?.extract(root_node))
+}
+
+// XPath 1.0, e.g. xpath(&root, "//table[@id='prices']//tr[td[1]='Total']/td[2]/text()")
+// the expression runs against a document root above root_node, so absolute
+// paths start at root_node itself. the result is a node-set, string, number or
+// boolean depending on the expression.
+pub fn xpath<'a> (root_node: &'a Node, expression: &str) -> Result<XPathValue<'a>, String> {
+    XPath::compile(expression)?.evaluate(root_node)
+}
+
+#[cfg(test)]
 mod tests{
 
     use crate::Node;
     use crate::buscador::*;
+    use crate::html;
     use lazy_static::lazy_static;
     
     lazy_static!{
         pub static ref AST:Vec<Node> = vec![
-        Node::Element {
-            tag_name: "html".to_string(),
-            attributes: {
-                let mut attrs = HashMap::new();
-                attrs.insert("lang".to_string(), "en".to_string());
-                attrs
-            },
-            children: vec![
-                Node::Element {
-                    tag_name: "head".to_string(),
-                    attributes: HashMap::new(),
-                    children: vec![
-                        Node::Element {
-                            tag_name: "meta".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("charset".to_string(), "UTF-8".to_string());
-                                attrs
-                            },
-                            children: vec![],
-                        },
-                        Node::Element {
-                            tag_name: "meta".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("name".to_string(), "viewport".to_string());
-                                attrs.insert("content".to_string(), "width=device-width, initial-scale=1.0".to_string());
-                                attrs
-                            },
-                            children: vec![],
-                        },
-                        Node::Element {
-                            tag_name: "title".to_string(),
-                            attributes: HashMap::new(),
-                            children: vec![
-                                Node::Text("Tokenizer Test Page".to_string()),
-                            ],
-                        },
-                        Node::Element {
-                            tag_name: "style".to_string(),
-                            attributes: HashMap::new(),
-                            children: vec![
-                                Node::Text(r#"body { font-family: sans-serif; margin: 20px; background-color: #f4f4f4; color: #333; } .container { max-width: 800px; margin: 0 auto; background-color: #fff; padding: 20px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1); } h1 { color: #0056b3; } p { line-height: 1.6; } code { background-color: #e0e0e0; padding: 2px 4px; border-radius: 3px; }"#.to_string()),
-                            ],
-                        },
-                    ],
-                },
-                Node::Element {
-                    tag_name: "body".to_string(),
-                    attributes: HashMap::new(),
-                    children: vec![
-                        Node::Element {
-                            tag_name: "div".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("class".to_string(), "container".to_string());
-                                attrs
-                            },
-                            children: vec![
-                                Node::Element {
-                                    tag_name: "h1".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("This is some sample text".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("class".to_string(), "intro".to_string());
-                                        attrs
-                                    },
-                                    children: vec![
-                                        Node::Text("Welcome to this ".to_string()),
-                                        Node::Element {
-                                            tag_name: "span".to_string(),
-                                            attributes: {
-                                                let mut attrs = HashMap::new();
-                                                attrs.insert("id".to_string(), "test_span".to_string());
-                                                attrs
-                                            },
-                                            children: vec![
-                                                Node::Text("simple page".to_string()),
-                                            ],
-                                        },
-                                        Node::Text(" for tokenizer testing.".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("Here's a paragraph with a ".to_string()),
-                                        Node::Element {
-                                            tag_name: "a".to_string(),
-                                            attributes: {
-                                                let mut attrs = HashMap::new();
-                                                attrs.insert("target".to_string(), "_blank".to_string());
-                                                attrs.insert("href".to_string(), "https://example.com".to_string());
-                                                attrs.insert("data-info".to_string(), "link".to_string());
-                                                attrs
-                                            },
-                                            children: vec![
-                                                Node::Text("link".to_string()),
-                                            ],
-                                        },
-                                        Node::Text(".".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "img".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("src".to_string(), "https://placehold.co/150x50/cccccc/333333?text=Image".to_string());
-                                        attrs.insert("alt".to_string(), "Placeholder Image".to_string());
-                                        attrs
-                                    },
-                                    children: vec![],
-                                },
-                                Node::Element {
-                                    tag_name: "br".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![],
-                                },
-                                Node::Element {
-                                    tag_name: "div".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("id".to_string(), "dynamicContent".to_string());
-                                        attrs.insert("style".to_string(), "background-color: lightblue;".to_string());
-                                        attrs
-                                    },
-                                    children: vec![
-                                        Node::Element {
-                                            tag_name: "p".to_string(),
-                                            attributes: HashMap::new(),
-                                            children: vec![
-                                                Node::Text("Another paragraph inside a div.".to_string()),
-                                            ],
-                                        },
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("This is the final content.".to_string()),
-                                    ],
-                                },
-                            ],
-                        },
-                    ],
-                },
-            ],
+        html! {
+            html(lang = "en") {
+                head {
+                    meta(charset = "UTF-8")
+                    meta(name = "viewport", content = "width=device-width, initial-scale=1.0")
+                    title { "Tokenizer Test Page" }
+                    style { r#"body { font-family: sans-serif; margin: 20px; background-color: #f4f4f4; color: #333; } .container { max-width: 800px; margin: 0 auto; background-color: #fff; padding: 20px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1); } h1 { color: #0056b3; } p { line-height: 1.6; } code { background-color: #e0e0e0; padding: 2px 4px; border-radius: 3px; }"# }
+                }
+                body {
+                    div(class = "container") {
+                        h1 { "This is some sample text" }
+                        p(class = "intro") {
+                            "Welcome to this "
+                            span(id = "test_span") { "simple page" }
+                            " for tokenizer testing."
+                        }
+                        p {
+                            "Here's a paragraph with a "
+                            a(target = "_blank", href = "https://example.com", "data-info" = "link") { "link" }
+                            "."
+                        }
+                        img(src = "https://placehold.co/150x50/cccccc/333333?text=Image", alt = "Placeholder Image")
+                        br
+                        div(id = "dynamicContent", style = "background-color: lightblue;") {
+                            p { "Another paragraph inside a div." }
+                        }
+                        p { "This is the final content." }
+                    }
+                }
+            }
         },
     ];
     }
@@ -396,7 +449,7 @@ mod tests{
         let found_node = find_by_text(text_to_find, root_node);
 
         // Check that a node was found.
-        assert!(found_node.len() > 0);
+        assert!(!found_node.is_empty());
 
         // Get the found node and verify its content.
         for each_node in found_node {
@@ -454,5 +507,255 @@ mod tests{
             }
         }
     }
-} 
-  
\ No newline at end of file
+
+    fn tags_of(nodes: &[&Node]) -> Vec<String> {
+        nodes.iter().map(|node| match node {
+            Node::Element { tag_name, attributes, .. } => match attributes.get("id") {
+                Some(id) => format!("{}#{}", tag_name, id),
+                None => tag_name.clone(),
+            },
+            Node::Text(text) => text.clone(),
+        }).collect()
+    }
+
+    fn selected(selector: &str) -> Vec<String> {
+        tags_of(&select(&AST[0], selector).unwrap())
+    }
+
+    #[test]
+    fn test_select_simple_selectors() {
+        assert_eq!(selected("p").len(), 4);
+        assert_eq!(selected("P").len(), 4);
+        assert_eq!(selected("#test_span"), vec!["span#test_span"]);
+        assert_eq!(selected(".container"), vec!["div"]);
+        assert_eq!(selected("p.intro"), vec!["p"]);
+        assert_eq!(selected("*").len(), 18);
+        assert!(selected("p.container").is_empty());
+    }
+
+    #[test]
+    fn test_select_attribute_operators() {
+        assert_eq!(selected("[data-info]"), vec!["a"]);
+        assert_eq!(selected("meta[name=viewport]"), vec!["meta"]);
+        assert_eq!(selected("meta[content~='initial-scale=1.0']"), vec!["meta"]);
+        assert_eq!(selected("html[lang|=en]"), vec!["html"]);
+        assert_eq!(selected("a[href^='https://']"), vec!["a"]);
+        assert_eq!(selected("img[src$=Image]"), vec!["img"]);
+        assert_eq!(selected("[style*=lightblue]"), vec!["div#dynamicContent"]);
+        assert!(selected("meta[charset=utf-8]").is_empty());
+        assert_eq!(selected("meta[charset=utf-8 i]"), vec!["meta"]);
+        assert!(selected("[href^='']").is_empty());
+    }
+
+    #[test]
+    fn test_select_combinators() {
+        assert_eq!(selected("div.container > p").len(), 3);
+        assert_eq!(selected("div p").len(), 4);
+        assert_eq!(selected("body > p"), Vec::<String>::new());
+        assert_eq!(selected("h1 + p"), vec!["p"]);
+        assert_eq!(selected("img ~ p"), vec!["p"]);
+        assert_eq!(selected("p.intro ~ p").len(), 2);
+        assert_eq!(selected("head meta + meta"), vec!["meta"]);
+        assert_eq!(selected("html > body div > div > p"), vec!["p"]);
+    }
+
+    #[test]
+    fn test_select_lists_and_logical_pseudo_classes() {
+        assert_eq!(selected("h1, title, span"), vec!["title", "h1", "span#test_span"]);
+        assert_eq!(selected("p:not(.intro)").len(), 3);
+        assert_eq!(selected(":is(h1, span)"), vec!["h1", "span#test_span"]);
+        assert_eq!(selected("div :where(img, br)"), vec!["img", "br"]);
+        assert_eq!(selected("p:has(a)").len(), 1);
+        assert_eq!(selected("p:has(> span#test_span)"), vec!["p"]);
+        assert_eq!(selected("div:has(> p:not(.intro))").len(), 2);
+        assert_eq!(selected("div:has(p.intro)"), vec!["div"]);
+        assert_eq!(selected("h1:has(+ p.intro)"), vec!["h1"]);
+        assert_eq!(selected("img:has(~ div)"), vec!["img"]);
+        assert!(selected("div:has(h1 + span)").is_empty());
+        assert_eq!(selected(":scope > body").len(), 1);
+    }
+
+    #[test]
+    fn test_select_structural_pseudo_classes() {
+        // children of div.container: h1 p.intro p img br div#dynamicContent p
+        assert_eq!(selected("div.container > :first-child"), vec!["h1"]);
+        assert_eq!(selected("div.container > :last-child"), vec!["p"]);
+        assert_eq!(selected("div.container > :nth-child(3)"), vec!["p"]);
+        assert_eq!(selected("div.container > :nth-child(odd)"), vec!["h1", "p", "br", "p"]);
+        assert_eq!(selected("div.container > :nth-child(2n)").len(), 3);
+        assert_eq!(selected("div.container > :nth-child(-n+2)"), vec!["h1", "p"]);
+        assert_eq!(selected("div.container > :nth-last-child(2)"), vec!["div#dynamicContent"]);
+        assert_eq!(selected("div.container > :nth-child(2 of p)"), vec!["p"]);
+        assert_eq!(selected("div.container > :nth-child(2 of :not(.intro))"), vec!["p"]);
+        assert_eq!(selected("div.container > :nth-last-child(1 of p:not(.intro))"), vec!["p"]);
+
+        assert_eq!(selected("p:first-of-type"), vec!["p", "p"]);
+        assert_eq!(selected("div.container > p:last-of-type"), vec!["p"]);
+        assert_eq!(selected("div.container > p:nth-of-type(2)").len(), 1);
+        assert_eq!(selected("div.container > p:nth-last-of-type(3)").len(), 1);
+        assert_eq!(selected("head > meta:only-of-type").len(), 0);
+        assert_eq!(selected("head > title:only-of-type"), vec!["title"]);
+        assert_eq!(selected(":only-child"), vec!["html", "div", "span#test_span", "a", "p"]);
+
+        assert_eq!(selected(":empty"), vec!["meta", "meta", "img", "br"]);
+        assert_eq!(selected(":root"), vec!["html"]);
+        assert_eq!(selected(":root > :last-child"), vec!["body"]);
+    }
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(extract(&AST[0], "p.intro span::text").unwrap().get(), Some("simple page"));
+        assert_eq!(extract(&AST[0], "a::attr(href)").unwrap().get_all(), ["https://example.com"]);
+        assert_eq!(extract(&AST[0], "meta::attr(name)").unwrap().get_all(), ["viewport"]);
+        assert!(extract(&AST[0], "a::attr(").is_err());
+    }
+
+    #[test]
+    fn test_find_by_class_and_predicate() {
+        use crate::predicate::*;
+
+        let page = html! {
+            div(class = "container wide") {
+                a(class = "btn", href = "/cart/add") { "Add" }
+                a(class = "btn ghost", href = "/wishlist") { "Save" }
+                a(href = "/cart") { "Cart" }
+            }
+        };
+        assert_eq!(find_by_class("container", &page).len(), 1);
+        assert!(find_by_attribute("class", "container", &page).is_empty());
+        assert_eq!(find_by_class("btn", &page).len(), 2);
+
+        let cart_buttons = find_by_predicate(&tag("a").and(has_class("btn")).and(attr_starts_with("href", "/cart")), &page);
+        assert_eq!(cart_buttons.len(), 1);
+        assert!(matches!(cart_buttons[0], Node::Element { attributes, .. } if attributes["href"] == "/cart/add"));
+        assert_eq!(find_by_predicate(&tag("a").and(!has_class("ghost")), &page).len(), 2);
+        assert_eq!(find_by_predicate(&has_class("ghost").or(attr_equals("href", "/cart")), &page).len(), 2);
+    }
+
+    #[test]
+    fn test_find_first_and_iterators() {
+        let first_p = find_first_by_tag_name("p", &AST[0]).unwrap();
+        assert!(std::ptr::eq(first_p, find_by_tag_name("p", &AST[0])[0]));
+        assert!(find_first_by_tag_name("table", &AST[0]).is_none());
+        assert_eq!(tags_of(&find_first_by_attribute("id", "dynamicContent", &AST[0]).into_iter().collect::<Vec<_>>()), vec!["div#dynamicContent"]);
+        assert_eq!(find_first_by_text("link", &AST[0]).map(|node| matches!(node, Node::Text(_))), Some(true));
+        assert_eq!(tags_of(&find_first_node(&AST[0], |node| matches!(node, Node::Element { children, .. } if children.is_empty())).into_iter().collect::<Vec<_>>()), vec!["meta"]);
+
+        assert_eq!(iter_by_tag_name("p", &AST[0]).count(), 4);
+        assert_eq!(iter_by_attribute("name", "viewport", &AST[0]).count(), 1);
+        assert_eq!(iter_by_text("link", &AST[0]).count(), 1);
+
+        let lazy: Vec<&Node> = iter_select(&AST[0], "div p, h1").unwrap().collect();
+        assert_eq!(tags_of(&lazy), selected("div p, h1"));
+        assert_eq!(tags_of(&iter_select(&AST[0], "*").unwrap().skip(2).take(2).collect::<Vec<_>>()), vec!["meta", "meta"]);
+        assert_eq!(tags_of(&find_first_by_selector(&AST[0], "p:not(.intro)").unwrap().into_iter().collect::<Vec<_>>()), vec!["p"]);
+        assert!(iter_select(&AST[0], "p[").is_err());
+    }
+
+    #[test]
+    fn test_find_by_text_match() {
+        let own = |text_match: TextMatch| tags_of(&find_by_text_match(&text_match, TextScope::OwnText, &AST[0]));
+        assert_eq!(own(TextMatch::Contains("paragraph".to_string())), vec!["p", "p"]);
+        assert_eq!(own(TextMatch::StartsWith("This is".to_string())), vec!["h1", "p"]);
+        assert_eq!(own(TextMatch::EqualsIgnoreCase("SIMPLE PAGE".to_string())), vec!["span#test_span"]);
+        assert_eq!(own(TextMatch::EqualsIgnoreWhitespace("  Tokenizer\n Test   Page ".to_string())), vec!["title"]);
+        assert!(own(TextMatch::Equals("simple".to_string())).is_empty());
+
+        // "Welcome to this simple page for tokenizer testing." only exists as the
+        // combined text of p.intro
+        let page_text = TextMatch::regex(r"this simple page for \w+").unwrap();
+        assert!(own(page_text.clone()).is_empty());
+        let found = find_by_text_match(&page_text, TextScope::Descendants, &AST[0]);
+        assert_eq!(tags_of(&found), vec!["p"]);
+        assert!(std::ptr::eq(found[0], find_by_attribute("class", "intro", &AST[0])[0]));
+
+        let inside = find_by_text_match(&TextMatch::Contains("inside a div".to_string()), TextScope::Descendants, &AST[0]);
+        assert_eq!(tags_of(&inside), vec!["p"]);
+
+        let with_script = html! { div { p { "price: " b { "12" } } script { "var price = 12;" } } };
+        let found = find_by_text_match(&TextMatch::Contains("price".to_string()), TextScope::Descendants, &with_script);
+        assert_eq!(tags_of(&found), vec!["p"]);
+        assert!(TextMatch::regex("(").is_err());
+    }
+
+    #[test]
+    fn test_select_invalid() {
+        assert!(select(&AST[0], "div >").is_err());
+        assert!(select(&AST[0], "[a=").is_err());
+    }
+
+    fn xpath_strings(expression: &str) -> Vec<String> {
+        xpath(&AST[0], expression).unwrap().strings()
+    }
+
+    #[test]
+    fn test_xpath_paths_and_predicates() {
+        assert_eq!(xpath_strings("//p[@class='intro']/span/text()"), vec!["simple page"]);
+        assert_eq!(xpath(&AST[0], "//p").unwrap().nodes().len(), 4);
+        assert_eq!(tags_of(&xpath(&AST[0], "/html/body/div/*[1]").unwrap().nodes()), vec!["h1"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//div[@class='container']/p[last()]").unwrap().nodes()), vec!["p"]);
+        assert_eq!(xpath_strings("//div[@class='container']/p[last()]"), vec!["This is the final content."]);
+        assert_eq!(xpath_strings("(//p)[2]/a/@href"), vec!["https://example.com"]);
+        assert_eq!(xpath_strings("//meta/@*").len(), 3);
+        assert_eq!(tags_of(&xpath(&AST[0], "//span | //h1").unwrap().nodes()), vec!["h1", "span#test_span"]);
+        // //x[n] counts per parent, (//x)[n] across the whole document
+        assert!(xpath_strings("//*[@id][2]").is_empty());
+        assert_eq!(xpath_strings("(//*[@id])[2]/@id"), vec!["dynamicContent"]);
+    }
+
+    #[test]
+    fn test_xpath_axes() {
+        assert_eq!(tags_of(&xpath(&AST[0], "//span/ancestor::*").unwrap().nodes()), vec!["html", "body", "div", "p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//span/ancestor::*[1]").unwrap().nodes()), vec!["p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//img/following-sibling::*").unwrap().nodes()), vec!["br", "div#dynamicContent", "p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//img/preceding-sibling::*[1]").unwrap().nodes()), vec!["p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//br/following::p").unwrap().nodes()), vec!["p", "p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//h1/preceding::*").unwrap().nodes()), vec!["head", "meta", "meta", "title", "style"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//span/..").unwrap().nodes()), vec!["p"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//a/@href/..").unwrap().nodes()), vec!["a"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "//title/self::title").unwrap().nodes()), vec!["title"]);
+        assert_eq!(xpath(&AST[0], "count(/descendant-or-self::node())").unwrap(), XPathValue::Number(30.0));
+    }
+
+    #[test]
+    fn test_xpath_functions() {
+        assert_eq!(xpath(&AST[0], "count(//p)").unwrap(), XPathValue::Number(4.0));
+        assert_eq!(xpath(&AST[0], "string(//h1)").unwrap(), XPathValue::String("This is some sample text".to_string()));
+        assert_eq!(xpath(&AST[0], "normalize-space(//p[@class='intro'])").unwrap(), XPathValue::String("Welcome to this simple page for tokenizer testing.".to_string()));
+        assert_eq!(xpath(&AST[0], "substring-after(//a/@href, '://')").unwrap(), XPathValue::String("example.com".to_string()));
+        assert_eq!(xpath(&AST[0], "substring('12345', 1.5, 2.6)").unwrap(), XPathValue::String("234".to_string()));
+        assert_eq!(xpath(&AST[0], "translate('bar', 'abc', 'AB')").unwrap(), XPathValue::String("BAr".to_string()));
+        assert_eq!(xpath(&AST[0], "concat(name(//*[@id='test_span']), '-', string-length('abc'))").unwrap(), XPathValue::String("span-3".to_string()));
+        assert_eq!(xpath(&AST[0], "boolean(//table)").unwrap(), XPathValue::Boolean(false));
+        assert_eq!(xpath(&AST[0], "not(//p[contains(., 'final')])").unwrap(), XPathValue::Boolean(false));
+        assert_eq!(xpath_strings("//p[starts-with(normalize-space(), 'Another')]/text()"), vec!["Another paragraph inside a div."]);
+        assert_eq!(tags_of(&xpath(&AST[0], "id('test_span dynamicContent')").unwrap().nodes()), vec!["span#test_span", "div#dynamicContent"]);
+        assert_eq!(tags_of(&xpath(&AST[0], "(//*[lang('EN')])[1]").unwrap().nodes()), vec!["html"]);
+        assert_eq!(xpath(&AST[0], "7 mod 3 + 10 div 4 * 2 - -1").unwrap(), XPathValue::Number(7.0));
+        assert_eq!(xpath(&AST[0], "round(2.5) = 3 and floor(-1.5) = -2 and ceiling(1.2) = 2").unwrap(), XPathValue::Boolean(true));
+        assert!(xpath(&AST[0], "number('abc')").unwrap().as_number().is_nan());
+    }
+
+    #[test]
+    fn test_xpath_prices_table() {
+        let page = html! {
+            table(id = "prices") {
+                tr { td { "Widget" } td { "4.50" } }
+                tr { td { "Gadget" } td { "5.50" } }
+                tr { td { "Total" } td { "10.00" } }
+            }
+        };
+        assert_eq!(xpath(&page, "//table[@id='prices']//tr[td[1]='Total']/td[2]/text()").unwrap().strings(), vec!["10.00"]);
+        assert_eq!(xpath(&page, "sum(//tr[td[1]!='Total']/td[2])").unwrap(), XPathValue::Number(10.0));
+        assert_eq!(xpath(&page, "//tr[td[2] > 5]/td[1]").unwrap().strings(), vec!["Gadget", "Total"]);
+        assert_eq!(xpath(&page, "//tr[position() < last()]/td[1]").unwrap().strings(), vec!["Widget", "Gadget"]);
+        assert_eq!(xpath(&page, "//td = 'Gadget'").unwrap(), XPathValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_xpath_invalid() {
+        assert!(xpath(&AST[0], "//p[").is_err());
+        assert!(xpath(&AST[0], "nope()").is_err());
+        assert!(xpath(&AST[0], "count(1)").is_err());
+    }
+}
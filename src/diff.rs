@@ -0,0 +1,713 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::hash::{Hash, Hasher};
+use crate::Node;
+use crate::json::json_string;
+use crate::mutation::NodePath;
+use crate::selector::{parse_selector, SelectorList};
+
+// Structural diff between two trees, e.g. two fetches of the same page.
+//
+// Nodes are matched by ordered tree edit distance (Zhang–Shasha): the matching
+// is one that needs the fewest node removals, insertions and updates (another
+// attribute set or text), so the edit script read off it is minimal for those
+// operations. Elements with different tags, or an element and a text, are never
+// matched. A subtree found once and unchanged on both sides is compared as one
+// node weighing as much as its nodes, so a block that moved stays in one piece
+// and the unchanged bulk of a page costs little. Two steps then make the
+// script read like a change report:
+//   1. a matched node that changed and ended up under another parent is split
+//      back into a removal and an insertion, only unchanged subtrees move
+//   2. a removed subtree identical to an inserted one is reported as a single
+//      move instead of a delete + insert
+//
+// For trees of n and m nodes this takes O(n·m) memory and O(n·m·k²) time, k
+// being the depth or the number of leaves of the shallower tree, whichever is
+// smaller.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    // a subtree only present in the new tree, `path` is in the new tree
+    Inserted { path: NodePath, node: String },
+    // a subtree only present in the old tree, `path` is in the old tree
+    Removed { path: NodePath, node: String },
+    // the same subtree at a different place (other parent or other order)
+    Moved { from: NodePath, to: NodePath, node: String },
+    // `path` is in the new tree, None means the attribute is missing on that side
+    AttributeChanged { path: NodePath, name: String, old: Option<String>, new: Option<String> },
+    TextChanged { path: NodePath, old: String, new: String },
+}
+
+type SubtreeFilter = Box<dyn Fn(&Node) -> bool>;
+
+#[derive(Default)]
+pub struct DiffOptions {
+    ignored_attributes: Vec<String>,
+    ignored_subtrees: Vec<SubtreeFilter>,
+    ignored_selectors: Vec<SelectorList>,
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        DiffOptions::default()
+    }
+
+    // attributes that change on every fetch, e.g. nonces or tracking ids
+    pub fn ignore_attribute(mut self, name: &str) -> Self {
+        self.ignored_attributes.push(name.to_string());
+        self
+    }
+
+    // nodes for which the predicate holds are left out together with their
+    // children, on both sides
+    pub fn ignore_subtree<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Node) -> bool + 'static,
+    {
+        self.ignored_subtrees.push(Box::new(predicate));
+        self
+    }
+
+    // same as ignore_subtree with a css selector instead of a predicate, e.g.
+    // "div.ad, body > [id^=google_ads]"
+    pub fn ignore_selector(mut self, selector: &str) -> Result<Self, String> {
+        self.ignored_selectors.push(parse_selector(selector)?);
+        Ok(self)
+    }
+
+    fn is_ignored(&self, node: &Node, selected: &HashSet<*const Node>) -> bool {
+        selected.contains(&(node as *const Node)) || self.ignored_subtrees.iter().any(|predicate| predicate(node))
+    }
+
+    fn keeps_attribute(&self, name: &str) -> bool {
+        !self.ignored_attributes.iter().any(|ignored| ignored == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diff {
+    pub edits: Vec<Edit>,
+}
+
+pub fn diff(old: &Node, new: &Node) -> Diff {
+    diff_with_options(old, new, &DiffOptions::default())
+}
+
+pub fn diff_with_options(old: &Node, new: &Node, options: &DiffOptions) -> Diff {
+    let old_tree = FlatTree::new(old, options);
+    let new_tree = FlatTree::new(new, options);
+    let mut matching = Matching::new(old_tree.nodes.len(), new_tree.nodes.len());
+
+    if !old_tree.nodes.is_empty() && !new_tree.nodes.is_empty() {
+        EditDistance::new(&old_tree, &new_tree, options).matching(&mut matching);
+    }
+    split_changed_moves(&old_tree, &new_tree, &mut matching);
+    let moved = pair_moves(&old_tree, &new_tree, &mut matching);
+
+    Diff { edits: edit_script(&old_tree, &new_tree, &matching, &moved, options) }
+}
+
+// one entry per node, in document order, so a node's descendants are the
+// entries right after it up to `end`
+struct FlatNode<'a> {
+    node: &'a Node,
+    path: NodePath,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    end: usize,
+    hash: u64,
+}
+
+struct FlatTree<'a> {
+    nodes: Vec<FlatNode<'a>>,
+}
+
+impl<'a> FlatTree<'a> {
+    fn new(root: &'a Node, options: &DiffOptions) -> Self {
+        // selectors need the whole tree to match, so they are run once up front
+        let selected: HashSet<*const Node> = options
+            .ignored_selectors
+            .iter()
+            .flat_map(|selector| selector.select(root))
+            .map(|node| node as *const Node)
+            .collect();
+        let mut tree = FlatTree { nodes: Vec::new() };
+        if !options.is_ignored(root, &selected) {
+            tree.add(root, Vec::new(), None, options, &selected);
+        }
+        tree
+    }
+
+    fn add(&mut self, node: &'a Node, path: NodePath, parent: Option<usize>, options: &DiffOptions, selected: &HashSet<*const Node>) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(FlatNode { node, path: path.clone(), parent, children: Vec::new(), end: index + 1, hash: 0 });
+
+        let mut hasher = DefaultHasher::new();
+        match node {
+            Node::Element { tag_name, attributes, children } => {
+                tag_name.hash(&mut hasher);
+                let mut kept: Vec<(&String, &String)> = attributes.iter().filter(|(name, _)| options.keeps_attribute(name)).collect();
+                kept.sort();
+                kept.hash(&mut hasher);
+                for (i, child) in children.iter().enumerate() {
+                    if options.is_ignored(child, selected) {
+                        continue;
+                    }
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    let child_index = self.add(child, child_path, Some(index), options, selected);
+                    self.nodes[index].children.push(child_index);
+                    self.nodes[child_index].hash.hash(&mut hasher);
+                }
+            }
+            Node::Text(text) => {
+                "#text".hash(&mut hasher);
+                text.hash(&mut hasher);
+            }
+        }
+        let end = self.nodes.len();
+        let entry = &mut self.nodes[index];
+        entry.hash = hasher.finish();
+        entry.end = end;
+        index
+    }
+
+    fn descendants(&self, index: usize) -> std::ops::Range<usize> {
+        index + 1..self.nodes[index].end
+    }
+}
+
+struct Matching {
+    old_to_new: Vec<Option<usize>>,
+    new_to_old: Vec<Option<usize>>,
+}
+
+impl Matching {
+    fn new(old_len: usize, new_len: usize) -> Self {
+        Matching { old_to_new: vec![None; old_len], new_to_old: vec![None; new_len] }
+    }
+
+    fn link(&mut self, old: usize, new: usize) {
+        self.old_to_new[old] = Some(new);
+        self.new_to_old[new] = Some(old);
+    }
+
+    fn unlink(&mut self, old: usize, new: usize) {
+        self.old_to_new[old] = None;
+        self.new_to_old[new] = None;
+    }
+}
+
+// one side of the edit distance: the nodes in post-order, each with the
+// post-order index of its leftmost leaf
+struct PostOrder {
+    // pre-order index in the FlatTree of each post-order position
+    nodes: Vec<usize>,
+    leftmost: Vec<usize>,
+    // the root and every node with a left sibling, in post-order
+    keyroots: Vec<usize>,
+    // whole subtrees standing in as one node
+    whole: Vec<bool>,
+    // what removing or inserting the node costs: 1, or the size of a whole subtree
+    weights: Vec<u32>,
+}
+
+impl PostOrder {
+    // `whole` tells which subtrees, by pre-order index, are taken as one node
+    fn new(tree: &FlatTree, whole: &dyn Fn(usize) -> bool) -> Self {
+        let capacity = tree.nodes.len();
+        let mut order = PostOrder {
+            nodes: Vec::with_capacity(capacity),
+            leftmost: Vec::with_capacity(capacity),
+            keyroots: Vec::new(),
+            whole: Vec::with_capacity(capacity),
+            weights: Vec::with_capacity(capacity),
+        };
+        order.visit(tree, 0, whole);
+        let mut seen = HashSet::new();
+        for index in (0..order.nodes.len()).rev() {
+            if seen.insert(order.leftmost[index]) {
+                order.keyroots.push(index);
+            }
+        }
+        order.keyroots.reverse();
+        order
+    }
+
+    fn visit(&mut self, tree: &FlatTree, index: usize, whole: &dyn Fn(usize) -> bool) -> usize {
+        let is_whole = whole(index);
+        let mut leftmost = None;
+        if !is_whole {
+            for &child in &tree.nodes[index].children {
+                let child = self.visit(tree, child, whole);
+                leftmost.get_or_insert(self.leftmost[child]);
+            }
+        }
+        let position = self.nodes.len();
+        self.nodes.push(index);
+        self.leftmost.push(leftmost.unwrap_or(position));
+        self.whole.push(is_whole);
+        self.weights.push(if is_whole { (tree.nodes[index].end - index) as u32 } else { 1 });
+        position
+    }
+}
+
+struct EditDistance<'t, 'a> {
+    old_tree: &'t FlatTree<'a>,
+    new_tree: &'t FlatTree<'a>,
+    options: &'t DiffOptions,
+    old: PostOrder,
+    new: PostOrder,
+    // cost of turning the old subtree into the new one, by post-order positions
+    trees: Vec<u32>,
+    // distances between the forests of the current pair of subtrees
+    forests: Vec<u32>,
+}
+
+impl<'t, 'a> EditDistance<'t, 'a> {
+    fn new(old_tree: &'t FlatTree<'a>, new_tree: &'t FlatTree<'a>, options: &'t DiffOptions) -> Self {
+        // subtrees whose hash turns up exactly once on each side
+        let mut counts: HashMap<u64, (usize, usize)> = HashMap::new();
+        for entry in &old_tree.nodes {
+            counts.entry(entry.hash).or_default().0 += 1;
+        }
+        for entry in &new_tree.nodes {
+            counts.entry(entry.hash).or_default().1 += 1;
+        }
+        let unique = |hash: u64| counts.get(&hash) == Some(&(1, 1));
+        let old = PostOrder::new(old_tree, &|index| unique(old_tree.nodes[index].hash));
+        let new = PostOrder::new(new_tree, &|index| unique(new_tree.nodes[index].hash));
+        let trees = vec![0; old.nodes.len() * new.nodes.len()];
+        let mut distance = EditDistance { old_tree, new_tree, options, old, new, trees, forests: Vec::new() };
+        for i in 0..distance.old.keyroots.len() {
+            for j in 0..distance.new.keyroots.len() {
+                distance.forest(distance.old.keyroots[i], distance.new.keyroots[j]);
+            }
+        }
+        distance
+    }
+
+    fn update_cost(&self, old: usize, new: usize) -> u32 {
+        // more than removing one and inserting the other, so the pair is never matched
+        let incompatible = self.old.weights[old] + self.new.weights[new] + 1;
+        if self.old.whole[old] || self.new.whole[new] {
+            let same = self.old.whole[old] && self.new.whole[new] && self.old_tree.nodes[self.old.nodes[old]].hash == self.new_tree.nodes[self.new.nodes[new]].hash;
+            return if same { 0 } else { incompatible };
+        }
+        match (self.old_tree.nodes[self.old.nodes[old]].node, self.new_tree.nodes[self.new.nodes[new]].node) {
+            (Node::Text(old_text), Node::Text(new_text)) => u32::from(old_text != new_text),
+            (Node::Element { tag_name: old_tag, attributes: old_attrs, .. }, Node::Element { tag_name: new_tag, attributes: new_attrs, .. }) if old_tag == new_tag => {
+                let kept = |name: &String| self.options.keeps_attribute(name);
+                let same = old_attrs.iter().filter(|(name, _)| kept(name)).all(|(name, value)| new_attrs.get(name) == Some(value))
+                    && new_attrs.keys().filter(|name| kept(name)).all(|name| old_attrs.contains_key(name));
+                u32::from(!same)
+            }
+            _ => incompatible,
+        }
+    }
+
+    // fills `forests` for the subtrees rooted at old `i` and new `j`, row by
+    // row with one more column than the new subtree has nodes, and records the
+    // distance of every pair of whole subtrees met on the way
+    fn forest(&mut self, i: usize, j: usize) -> usize {
+        let (old_first, new_first) = (self.old.leftmost[i], self.new.leftmost[j]);
+        let (rows, columns) = (i - old_first + 2, j - new_first + 2);
+        let new_len = self.new.nodes.len();
+        self.forests.clear();
+        self.forests.resize(rows * columns, 0);
+        for x in 1..rows {
+            self.forests[x * columns] = self.forests[(x - 1) * columns] + self.old.weights[old_first + x - 1];
+        }
+        for y in 1..columns {
+            self.forests[y] = self.forests[y - 1] + self.new.weights[new_first + y - 1];
+        }
+        for x in 1..rows {
+            let old = old_first + x - 1;
+            for y in 1..columns {
+                let new = new_first + y - 1;
+                let removed = self.forests[(x - 1) * columns + y] + self.old.weights[old];
+                let inserted = self.forests[x * columns + y - 1] + self.new.weights[new];
+                let distance = if self.old.leftmost[old] == old_first && self.new.leftmost[new] == new_first {
+                    let distance = removed.min(inserted).min(self.forests[(x - 1) * columns + y - 1] + self.update_cost(old, new));
+                    self.trees[old * new_len + new] = distance;
+                    distance
+                } else {
+                    let before = (self.old.leftmost[old] - old_first) * columns + self.new.leftmost[new] - new_first;
+                    removed.min(inserted).min(self.forests[before] + self.trees[old * new_len + new])
+                };
+                self.forests[x * columns + y] = distance;
+            }
+        }
+        columns
+    }
+
+    // walks the distances back from the two roots and links the pairs of nodes
+    // the cheapest script keeps
+    fn matching(&mut self, matching: &mut Matching) {
+        let new_len = self.new.nodes.len();
+        let mut pending = vec![(self.old.nodes.len() - 1, new_len - 1)];
+        while let Some((i, j)) = pending.pop() {
+            let (old_first, new_first) = (self.old.leftmost[i], self.new.leftmost[j]);
+            let columns = self.forest(i, j);
+            let (mut x, mut y) = (i - old_first + 1, j - new_first + 1);
+            while x > 0 && y > 0 {
+                let (old, new) = (old_first + x - 1, new_first + y - 1);
+                let here = self.forests[x * columns + y];
+                if self.old.leftmost[old] == old_first && self.new.leftmost[new] == new_first {
+                    if here == self.forests[(x - 1) * columns + y - 1] + self.update_cost(old, new) {
+                        let (old_index, new_index) = (self.old.nodes[old], self.new.nodes[new]);
+                        if self.old.whole[old] {
+                            for (o, n) in self.old_tree.descendants(old_index).zip(self.new_tree.descendants(new_index)) {
+                                matching.link(o, n);
+                            }
+                        }
+                        matching.link(old_index, new_index);
+                        x -= 1;
+                        y -= 1;
+                        continue;
+                    }
+                } else {
+                    let (before_x, before_y) = (self.old.leftmost[old] - old_first, self.new.leftmost[new] - new_first);
+                    if here == self.forests[before_x * columns + before_y] + self.trees[old * new_len + new] {
+                        pending.push((old, new));
+                        x = before_x;
+                        y = before_y;
+                        continue;
+                    }
+                }
+                if here == self.forests[(x - 1) * columns + y] + self.old.weights[old] {
+                    x -= 1;
+                } else {
+                    y -= 1;
+                }
+            }
+        }
+    }
+}
+
+// a matched pair under parents that aren't matched to each other only counts
+// as a move when the subtree is unchanged, otherwise it's a removal and an
+// insertion. Parents come first, so a split cascades into changed children
+fn split_changed_moves(old_tree: &FlatTree, new_tree: &FlatTree, matching: &mut Matching) {
+    for old in 0..old_tree.nodes.len() {
+        let Some(new) = matching.old_to_new[old] else { continue };
+        let same_parent = match (old_tree.nodes[old].parent, new_tree.nodes[new].parent) {
+            (Some(old_parent), Some(new_parent)) => matching.old_to_new[old_parent] == Some(new_parent),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same_parent && old_tree.nodes[old].hash != new_tree.nodes[new].hash {
+            matching.unlink(old, new);
+        }
+    }
+}
+
+// pairs each removed subtree with an identical inserted one, in document
+// order, and returns which new nodes are the roots of such moves
+fn pair_moves(old_tree: &FlatTree, new_tree: &FlatTree, matching: &mut Matching) -> Vec<bool> {
+    let mut moved = vec![false; new_tree.nodes.len()];
+    let mut inserted: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (new, entry) in new_tree.nodes.iter().enumerate().rev() {
+        if matching.new_to_old[new].is_none() && entry.parent.is_some_and(|parent| matching.new_to_old[parent].is_some()) {
+            inserted.entry(entry.hash).or_default().push(new);
+        }
+    }
+    for (old, entry) in old_tree.nodes.iter().enumerate() {
+        if matching.old_to_new[old].is_some() || entry.parent.is_none_or(|parent| matching.old_to_new[parent].is_none()) {
+            continue;
+        }
+        // the candidates are stacked last first
+        let Some(new) = inserted.get_mut(&entry.hash).and_then(Vec::pop) else { continue };
+        for (o, n) in old_tree.descendants(old).zip(new_tree.descendants(new)) {
+            matching.link(o, n);
+        }
+        matching.link(old, new);
+        moved[new] = true;
+    }
+    moved
+}
+
+fn edit_script(old_tree: &FlatTree, new_tree: &FlatTree, matching: &Matching, moved: &[bool], options: &DiffOptions) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    // removed: unmatched old nodes whose parent is still there
+    for (old, entry) in old_tree.nodes.iter().enumerate() {
+        let parent_kept = entry.parent.is_none_or(|parent| matching.old_to_new[parent].is_some());
+        if matching.old_to_new[old].is_none() && parent_kept {
+            edits.push(Edit::Removed { path: entry.path.clone(), node: describe(entry.node) });
+        }
+    }
+
+    for (new, entry) in new_tree.nodes.iter().enumerate() {
+        let Some(old) = matching.new_to_old[new] else {
+            let parent_kept = entry.parent.is_none_or(|parent| matching.new_to_old[parent].is_some());
+            if parent_kept {
+                edits.push(Edit::Inserted { path: entry.path.clone(), node: describe(entry.node) });
+            }
+            continue;
+        };
+
+        // moved among its siblings or to another parent. a move inside a moved
+        // subtree comes for free
+        let old_parent = old_tree.nodes[old].parent;
+        let new_parent = entry.parent.and_then(|parent| matching.new_to_old[parent]);
+        if moved[new] || old_parent.is_some() && old_parent != new_parent {
+            edits.push(Edit::Moved { from: old_tree.nodes[old].path.clone(), to: entry.path.clone(), node: describe(entry.node) });
+        }
+
+        match (old_tree.nodes[old].node, entry.node) {
+            (Node::Element { attributes: old_attrs, .. }, Node::Element { attributes: new_attrs, .. }) => {
+                let mut names: Vec<&String> = old_attrs.keys().chain(new_attrs.keys()).filter(|name| options.keeps_attribute(name)).collect();
+                names.sort();
+                names.dedup();
+                for name in names {
+                    if old_attrs.get(name) != new_attrs.get(name) {
+                        edits.push(Edit::AttributeChanged {
+                            path: entry.path.clone(),
+                            name: name.clone(),
+                            old: old_attrs.get(name).cloned(),
+                            new: new_attrs.get(name).cloned(),
+                        });
+                    }
+                }
+            }
+            (Node::Text(old_text), Node::Text(new_text)) if old_text != new_text => {
+                edits.push(Edit::TextChanged { path: entry.path.clone(), old: old_text.clone(), new: new_text.clone() });
+            }
+            _ => {}
+        }
+    }
+    edits
+}
+
+// short label for a node in reports: `<div class="ad">` or `"some text"`
+fn describe(node: &Node) -> String {
+    match node {
+        Node::Element { tag_name, attributes, .. } => {
+            let mut out = format!("<{}", tag_name);
+            let mut names: Vec<&String> = attributes.keys().collect();
+            names.sort();
+            for name in names {
+                if name == "id" || name == "class" {
+                    let _ = write!(out, " {}=\"{}\"", name, attributes[name]);
+                }
+            }
+            out.push('>');
+            out
+        }
+        Node::Text(text) => {
+            let short: String = text.chars().take(40).collect();
+            if short.len() < text.len() {
+                format!("{:?}...", short)
+            } else {
+                format!("{:?}", short)
+            }
+        }
+    }
+}
+
+fn format_path(path: &[usize]) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.iter().map(|i| format!("/{}", i)).collect()
+}
+
+fn json_path(path: &[usize]) -> String {
+    format!("[{}]", path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(","))
+}
+
+fn json_optional(value: &Option<String>) -> String {
+    value.as_deref().map(json_string).unwrap_or_else(|| "null".to_string())
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    // one line per edit:
+    //   - /1/0 <script>
+    //   + /1/3 <div class="ad">
+    //   ~ /1/0 -> /1/2 <p>
+    //   @ /1/2 class: "old" -> "new"
+    //   " /1/2/0: "old" -> "new"
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for edit in &self.edits {
+            let _ = match edit {
+                Edit::Removed { path, node } => writeln!(out, "- {} {}", format_path(path), node),
+                Edit::Inserted { path, node } => writeln!(out, "+ {} {}", format_path(path), node),
+                Edit::Moved { from, to, node } => writeln!(out, "~ {} -> {} {}", format_path(from), format_path(to), node),
+                Edit::AttributeChanged { path, name, old, new } => writeln!(
+                    out,
+                    "@ {} {}: {} -> {}",
+                    format_path(path),
+                    name,
+                    old.as_deref().map(|v| format!("{:?}", v)).unwrap_or_else(|| "(none)".to_string()),
+                    new.as_deref().map(|v| format!("{:?}", v)).unwrap_or_else(|| "(none)".to_string()),
+                ),
+                Edit::TextChanged { path, old, new } => writeln!(out, "\" {}: {:?} -> {:?}", format_path(path), old, new),
+            };
+        }
+        out
+    }
+
+    // {"edits":[{"op":"removed","path":[1,0],"node":"<script>"}, ...]}
+    pub fn to_json(&self) -> String {
+        let edits: Vec<String> = self
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                Edit::Removed { path, node } => format!("{{\"op\":\"removed\",\"path\":{},\"node\":{}}}", json_path(path), json_string(node)),
+                Edit::Inserted { path, node } => format!("{{\"op\":\"inserted\",\"path\":{},\"node\":{}}}", json_path(path), json_string(node)),
+                Edit::Moved { from, to, node } => format!(
+                    "{{\"op\":\"moved\",\"from\":{},\"to\":{},\"node\":{}}}",
+                    json_path(from),
+                    json_path(to),
+                    json_string(node)
+                ),
+                Edit::AttributeChanged { path, name, old, new } => format!(
+                    "{{\"op\":\"attribute\",\"path\":{},\"name\":{},\"old\":{},\"new\":{}}}",
+                    json_path(path),
+                    json_string(name),
+                    json_optional(old),
+                    json_optional(new)
+                ),
+                Edit::TextChanged { path, old, new } => format!(
+                    "{{\"op\":\"text\",\"path\":{},\"old\":{},\"new\":{}}}",
+                    json_path(path),
+                    json_string(old),
+                    json_string(new)
+                ),
+            })
+            .collect();
+        format!("{{\"edits\":[{}]}}", edits.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Node;
+    use crate::diff::*;
+    use crate::html;
+
+    fn page() -> Node {
+        html! {
+            body {
+                div(id = "nav") { a(href = "/") { "Home" } }
+                div(class = "product") {
+                    h2 { "Widget" }
+                    p(class = "price") { "$10.00" }
+                }
+                div(class = "ad", "data-nonce" = "111") { "Buy now" }
+            }
+        }
+    }
+
+    #[test]
+    fn test_identical_trees() {
+        assert!(diff(&page(), &page()).is_empty());
+    }
+
+    #[test]
+    fn test_text_and_attribute_changes() {
+        let new = html! {
+            body {
+                div(id = "nav") { a(href = "/home") { "Home" } }
+                div(class = "product") {
+                    h2 { "Widget" }
+                    p(class = "price") { "$12.00" }
+                }
+                div(class = "ad", "data-nonce" = "111") { "Buy now" }
+            }
+        };
+        let result = diff(&page(), &new);
+        assert_eq!(result.edits, vec![
+            Edit::AttributeChanged { path: vec![0, 0], name: "href".to_string(), old: Some("/".to_string()), new: Some("/home".to_string()) },
+            Edit::TextChanged { path: vec![1, 1, 0], old: "$10.00".to_string(), new: "$12.00".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_insert_remove_and_move() {
+        let new = html! {
+            body {
+                div(class = "product") {
+                    h2 { "Widget" }
+                    p(class = "price") { "$10.00" }
+                }
+                div(id = "nav") { a(href = "/") { "Home" } }
+                footer { "bye" }
+            }
+        };
+        let result = diff(&page(), &new);
+        assert!(result.edits.contains(&Edit::Removed { path: vec![2], node: "<div class=\"ad\">".to_string() }));
+        assert!(result.edits.contains(&Edit::Inserted { path: vec![2], node: "<footer>".to_string() }));
+        // one of the two blocks is reported as moved, not deleted and re-added
+        let moves = result.edits.iter().filter(|edit| matches!(edit, Edit::Moved { .. })).count();
+        assert_eq!(moves, 1);
+        assert_eq!(result.edits.len(), 3);
+    }
+
+    #[test]
+    fn test_move_to_other_parent() {
+        let old = html! { body { div { p { "a" } span { "b" } } section {} } };
+        let new = html! { body { div { p { "a" } } section { span { "b" } } } };
+        let result = diff(&old, &new);
+        assert_eq!(result.edits, vec![Edit::Moved { from: vec![0, 1], to: vec![1, 0], node: "<span>".to_string() }]);
+    }
+
+    #[test]
+    fn test_repeated_items_give_the_smallest_script() {
+        // no subtree is unique here, so it all comes down to the edit distance
+        let old = html! { ul { li { "a" } li { "a" } li { "b" } } };
+        let new = html! { ul { li { "a" } li { "b" } li { "b" } } };
+        assert_eq!(diff(&old, &new).edits, vec![Edit::TextChanged { path: vec![1, 0], old: "a".to_string(), new: "b".to_string() }]);
+
+        let new = html! { ul { li { "a" } li { "a" } li(class = "new") { "a" } li { "b" } } };
+        assert_eq!(diff(&old, &new).edits, vec![Edit::Inserted { path: vec![2], node: "<li class=\"new\">".to_string() }]);
+    }
+
+    #[test]
+    fn test_ignored_attributes_and_subtrees() {
+        let new = html! {
+            body {
+                div(id = "nav") { a(href = "/") { "Home" } }
+                div(class = "product") {
+                    h2 { "Widget" }
+                    p(class = "price") { "$10.00" }
+                }
+                div(class = "ad", "data-nonce" = "222") { "Buy now" }
+                div(class = "ad") { "Another ad" }
+            }
+        };
+        let options = DiffOptions::new()
+            .ignore_attribute("data-nonce")
+            .ignore_subtree(|node| matches!(node, Node::Element { attributes, .. } if attributes.get("class").is_some_and(|c| c == "ad")));
+        assert!(diff_with_options(&page(), &new, &options).is_empty());
+
+        let options = DiffOptions::new().ignore_attribute("data-nonce");
+        assert_eq!(diff_with_options(&page(), &new, &options).edits.len(), 1);
+
+        let options = DiffOptions::new().ignore_selector("div.ad, iframe[data-ad]").unwrap();
+        assert!(diff_with_options(&page(), &new, &options).is_empty());
+        let options = DiffOptions::new().ignore_selector("body > div.ad").unwrap();
+        assert!(diff_with_options(&page(), &new, &options).is_empty());
+        assert!(DiffOptions::new().ignore_selector("div >").is_err());
+        assert!(DiffOptions::new().ignore_selector("div.ad,").is_err());
+    }
+
+    #[test]
+    fn test_reports() {
+        let old = html! { div(class = "a") { "x" } };
+        let new = html! { div(class = "b") { "y \"quoted\"" } };
+        let result = diff(&old, &new);
+        assert_eq!(result.to_text(), "@ / class: \"a\" -> \"b\"\n\" /0: \"x\" -> \"y \\\"quoted\\\"\"\n");
+        assert_eq!(
+            result.to_json(),
+            r#"{"edits":[{"op":"attribute","path":[],"name":"class","old":"a","new":"b"},{"op":"text","path":[0],"old":"x","new":"y \"quoted\""}]}"#
+        );
+    }
+}
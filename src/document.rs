@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use reqwest::Url;
+use crate::Node;
+use crate::entities::decode_entities;
+use crate::mutation::{node_at, NodePath};
+use crate::parser::graph_creator;
+use crate::query::Query;
+use crate::selection::Selection;
+use crate::selector::IndexKey;
+use crate::tokenizer::tokenize;
+use crate::traversal::pre_order;
+
+// A parsed page plus, optionally, an index of its ids, class tokens and tag names.
+//
+// Without an index every lookup walks the tree. Building one costs a single walk
+// and after that get_element_by_id and friends only follow a stored path:
+//
+//     let mut document = Document::parse(&html);
+//     document.build_index();
+//     let price = document.get_element_by_id("price");
+//
+// The index stores NodePaths rather than references so the Document can own the
+// tree. Anything that can change the tree (root_mut) throws the index away.
+//
+// A Document can also remember where it was fetched from, which is what relative
+// links get resolved against (see base_url and links.rs).
+
+#[derive(Debug)]
+pub struct Document {
+    root: Node,
+    index: Option<DocumentIndex>,
+    url: Option<Url>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentIndex {
+    // ids should be unique but often aren't, so every element is kept
+    ids: HashMap<String, Vec<NodePath>>,
+    classes: HashMap<String, Vec<NodePath>>,
+    // tag names are lowercased
+    tags: HashMap<String, Vec<NodePath>>,
+}
+
+impl DocumentIndex {
+    pub fn build(root_node: &Node) -> Self {
+        let mut index = DocumentIndex::default();
+        index.add(root_node, &mut Vec::new());
+        index
+    }
+
+    // paths are pushed in document order, so every list stays sorted
+    fn add(&mut self, node: &Node, path: &mut NodePath) {
+        let Node::Element { tag_name, attributes, children } = node else {
+            return;
+        };
+        self.tags.entry(tag_name.to_ascii_lowercase()).or_default().push(path.clone());
+        if let Some(id) = attributes.get("id") {
+            self.ids.entry(id.clone()).or_default().push(path.clone());
+        }
+        if let Some(class) = attributes.get("class") {
+            let mut tokens: Vec<&str> = class.split_whitespace().collect();
+            tokens.sort();
+            tokens.dedup();
+            for token in tokens {
+                self.classes.entry(token.to_string()).or_default().push(path.clone());
+            }
+        }
+        for (i, child) in children.iter().enumerate() {
+            path.push(i);
+            self.add(child, path);
+            path.pop();
+        }
+    }
+
+    // the first element with the id, like the DOM
+    pub fn id(&self, id: &str) -> Option<&NodePath> {
+        self.ids.get(id).and_then(|paths| paths.first())
+    }
+
+    pub fn class(&self, class: &str) -> &[NodePath] {
+        self.classes.get(class).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn tag(&self, tag_name: &str) -> &[NodePath] {
+        self.tags.get(&tag_name.to_ascii_lowercase()).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn lookup(&self, key: &IndexKey) -> &[NodePath] {
+        match key {
+            IndexKey::Id(id) => self.ids.get(*id).map(Vec::as_slice).unwrap_or_default(),
+            IndexKey::Class(class) => self.class(class),
+            IndexKey::Tag(tag_name) => self.tag(tag_name),
+        }
+    }
+}
+
+impl Document {
+    pub fn new(root: Node) -> Self {
+        Document { root, index: None, url: None }
+    }
+
+    pub fn parse(html: &str) -> Self {
+        Document::new(graph_creator(&mut tokenize(html)))
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    // mutable access to the tree. the index can't be trusted afterwards so it is
+    // dropped; call build_index again when done editing
+    pub fn root_mut(&mut self) -> &mut Node {
+        self.index = None;
+        &mut self.root
+    }
+
+    pub fn into_root(self) -> Node {
+        self.root
+    }
+
+    pub fn with_url(mut self, url: Url) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    // where the page came from, if known
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    // what relative URLs in the page are relative to: the first <base href>,
+    // entities decoded and itself resolved against the page URL, or else the
+    // page URL
+    pub fn base_url(&self) -> Option<Url> {
+        let base_href = pre_order(&self.root).map(|(node, _)| node).find_map(|node| match node {
+            Node::Element { tag_name, attributes, .. } if tag_name.eq_ignore_ascii_case("base") => attributes.get("href").map(|href| decode_entities(href)),
+            _ => None,
+        });
+        match (base_href.as_deref(), &self.url) {
+            (Some(href), Some(url)) => url.join(href.trim()).ok().or_else(|| Some(url.clone())),
+            (Some(href), None) => Url::parse(href.trim()).ok(),
+            (None, url) => url.clone(),
+        }
+    }
+
+    pub fn build_index(&mut self) {
+        self.index = Some(DocumentIndex::build(&self.root));
+    }
+
+    // builder-style build_index
+    pub fn indexed(mut self) -> Self {
+        self.build_index();
+        self
+    }
+
+    pub fn index(&self) -> Option<&DocumentIndex> {
+        self.index.as_ref()
+    }
+
+    pub fn get_element_by_id(&self, id: &str) -> Option<&Node> {
+        match &self.index {
+            Some(index) => index.id(id).and_then(|path| node_at(&self.root, path)),
+            None => pre_order(&self.root).map(|(node, _)| node).find(|node| {
+                matches!(node, Node::Element { attributes, .. } if attributes.get("id").is_some_and(|value| value == id))
+            }),
+        }
+    }
+
+    // elements with `class` as one of their class tokens, in document order
+    pub fn get_elements_by_class_name(&self, class: &str) -> Vec<&Node> {
+        match &self.index {
+            Some(index) => self.nodes_at(index.class(class)),
+            None => pre_order(&self.root).map(|(node, _)| node).filter(|node| {
+                matches!(node, Node::Element { attributes, .. }
+                    if attributes.get("class").is_some_and(|value| value.split_whitespace().any(|token| token == class)))
+            }).collect(),
+        }
+    }
+
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<&Node> {
+        match &self.index {
+            Some(index) => self.nodes_at(index.tag(tag_name)),
+            None => pre_order(&self.root).map(|(node, _)| node).filter(|node| {
+                matches!(node, Node::Element { tag_name: name, .. } if name.eq_ignore_ascii_case(tag_name))
+            }).collect(),
+        }
+    }
+
+    // run a compiled selector. with an index, only the elements the index finds
+    // for each selector's rightmost id, class or tag get matched, instead of
+    // every element in the tree
+    pub fn query(&self, query: &Query) -> Vec<&Node> {
+        self.nodes_at(&self.query_paths(query))
+    }
+
+    // a chainable Selection, see selection.rs. an invalid selector gives an
+    // empty selection with the error recorded
+    pub fn select(&self, selector: &str) -> Selection<'_> {
+        match Query::compile(selector) {
+            Ok(query) => Selection::from_paths(&self.root, self.query_paths(&query)),
+            Err(error) => Selection::failed(&self.root, error),
+        }
+    }
+
+    fn query_paths(&self, query: &Query) -> Vec<NodePath> {
+        let selectors = query.selectors();
+        let (Some(index), Some(keys)) = (&self.index, selectors.index_keys()) else {
+            return selectors.select_paths(&self.root, &[], true);
+        };
+        let mut candidates: Vec<&NodePath> = keys.iter().flat_map(|key| index.lookup(key)).collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates.into_iter().filter(|path| selectors.matches_path(&self.root, path, &[])).cloned().collect()
+    }
+
+    fn nodes_at(&self, paths: &[NodePath]) -> Vec<&Node> {
+        paths.iter().filter_map(|path| node_at(&self.root, path)).collect()
+    }
+}
+
+impl From<Node> for Document {
+    fn from(root: Node) -> Self {
+        Document::new(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+    use crate::html;
+
+    fn document() -> Document {
+        Document::new(html! {
+            body {
+                div(id = "main", class = "box wide") {
+                    p(class = "note") { "first" }
+                    P(class = "note box") { "second" }
+                    span(id = "main") { "duplicate id" }
+                }
+                p { "plain" }
+            }
+        })
+    }
+
+    fn texts(nodes: &[&Node]) -> Vec<String> {
+        nodes.iter().map(|node| match node {
+            Node::Element { tag_name, children, .. } => match children.first() {
+                Some(Node::Text(text)) => text.clone(),
+                _ => tag_name.clone(),
+            },
+            Node::Text(text) => text.clone(),
+        }).collect()
+    }
+
+    #[test]
+    fn test_index_contents() {
+        let index = DocumentIndex::build(document().root());
+        assert_eq!(index.id("main"), Some(&vec![0]));
+        assert_eq!(index.id("missing"), None);
+        assert_eq!(index.class("box"), &[vec![0], vec![0, 1]]);
+        assert_eq!(index.tag("p"), &[vec![0, 0], vec![0, 1], vec![1]]);
+        assert!(index.class("Box").is_empty());
+    }
+
+    #[test]
+    fn test_lookups_with_and_without_index() {
+        let plain = document();
+        let indexed = document().indexed();
+        assert!(plain.index().is_none());
+        for document in [&plain, &indexed] {
+            assert_eq!(texts(&document.get_element_by_id("main").into_iter().collect::<Vec<_>>()), vec!["div"]);
+            assert_eq!(texts(&document.get_elements_by_class_name("note")), vec!["first", "second"]);
+            assert_eq!(texts(&document.get_elements_by_tag_name("P")), vec!["first", "second", "plain"]);
+        }
+    }
+
+    #[test]
+    fn test_query_uses_index_and_agrees_with_scan() {
+        let plain = document();
+        let indexed = document().indexed();
+        for selector in ["p", ".note", "#main > .note", "div p.box", "span#main", "p, .wide", "body > *", "[class~=note]", "p:not(.note)"] {
+            let query = Query::compile(selector).unwrap();
+            assert_eq!(texts(&indexed.query(&query)), texts(&plain.query(&query)), "{}", selector);
+        }
+        assert_eq!(texts(&indexed.query(&Query::compile("#main .note").unwrap())), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_base_url() {
+        let url = Url::parse("https://example.com/shop/list?page=2").unwrap();
+        assert_eq!(document().base_url(), None);
+        assert_eq!(document().with_url(url.clone()).base_url(), Some(url.clone()));
+
+        let with_base = |href: &str| Document::new(html! { html { head { base(href = href) } } });
+        assert_eq!(with_base("/catalog/").with_url(url.clone()).base_url().unwrap().as_str(), "https://example.com/catalog/");
+        assert_eq!(with_base("https://cdn.example.org/").base_url().unwrap().as_str(), "https://cdn.example.org/");
+        assert_eq!(with_base("/relative/").base_url(), None);
+        assert_eq!(with_base("/search?q=a&amp;lang=en").with_url(url.clone()).base_url().unwrap().as_str(), "https://example.com/search?q=a&lang=en");
+    }
+
+    #[test]
+    fn test_root_mut_drops_index() {
+        let mut document = document().indexed();
+        document.root_mut();
+        assert!(document.index().is_none());
+        document.build_index();
+        assert!(document.index().is_some());
+    }
+}
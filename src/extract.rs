@@ -0,0 +1,255 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::Node;
+use crate::query::{Query, QueryError};
+
+// Typed extraction, normally derived:
+//
+//     #[derive(Extract)]
+//     struct Product {
+//         #[select("h1::text")]
+//         title: String,
+//         #[select(".price::text", parse = "money")]
+//         price: Option<f64>,
+//         #[select_all("li.review")]
+//         reviews: Vec<Review>,
+//     }
+//
+//     let product = Product::extract(&node)?;
+//
+// A selector ending in ::text or ::attr(name) fills the field from the string
+// it picks out, parsed with FromStr after trimming. Without a pseudo-element the
+// field is another Extract type, extracted from the matched element. Option
+// fields may be missing, Vec fields take every match, anything else has to be
+// there. parse = "money" drops currency signs and thousands separators first.
+//
+// The functions below are what the derive expands to; they're public for the
+// generated code, not meant to be called directly.
+
+pub trait Extract: Sized {
+    fn extract(node: &Node) -> Result<Self, ExtractError>;
+}
+
+// which field failed and why. nested fields read like `reviews[1].author`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractError {
+    pub field: String,
+    pub selector: String,
+    pub message: String,
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (selector `{}`): {}", self.field, self.selector, self.message)
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl ExtractError {
+    fn new(field: &str, selector: &str, message: String) -> Self {
+        ExtractError { field: field.to_string(), selector: selector.to_string(), message }
+    }
+
+    // put the outer field in front of an error coming from a nested struct
+    fn inside(mut self, field: &str, index: Option<usize>) -> Self {
+        let outer = match index {
+            Some(index) => format!("{}[{}]", field, index),
+            None => field.to_string(),
+        };
+        self.field = format!("{}.{}", outer, self.field);
+        self
+    }
+}
+
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parse {
+    Text,
+    Money,
+}
+
+// "$1,234.50" -> "1234.50", "1.234,50 €" -> "1234.50"
+//
+// With both . and , the last one is the decimal separator. A separator that
+// shows up more than once is a thousands separator. A lone . or , is read the
+// same way either way: with exactly three digits after it it's a thousands
+// separator ("1.234" and "1,234" are both 1234), otherwise it's the decimal
+// separator ("4.5" and "4,50" are 4.5).
+fn clean_money(value: &str) -> String {
+    let kept: String = value.chars().filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-')).collect();
+    let lone = |separator: char| {
+        let at = kept.find(separator)?;
+        let digits_after = kept[at + 1..].chars().filter(char::is_ascii_digit).count();
+        (kept.matches(separator).count() == 1 && digits_after != 3).then_some(at)
+    };
+    let decimal = match (kept.rfind('.'), kept.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(_), None) => lone('.'),
+        (None, Some(_)) => lone(','),
+        (None, None) => None,
+    };
+    kept.char_indices()
+        .filter_map(|(i, c)| match c {
+            '.' | ',' if Some(i) == decimal => Some('.'),
+            '.' | ',' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+#[doc(hidden)]
+pub fn compiled<'q>(query: &'q Result<Query, QueryError>, field: &str, selector: &str, values: bool) -> Result<&'q Query, ExtractError> {
+    let query = query.as_ref().map_err(|error| ExtractError::new(field, selector, format!("invalid selector: {}", error)))?;
+    // the derive decides from the selector text; the compiled query has the final say
+    if query.extracts_values() != values {
+        let expected = if values { "every selector to end in ::text or ::attr(...)" } else { "no ::text or ::attr(...)" };
+        return Err(ExtractError::new(field, selector, format!("expected {}", expected)));
+    }
+    Ok(query)
+}
+
+#[doc(hidden)]
+pub fn required<T>(value: Option<T>, field: &str, selector: &str) -> Result<T, ExtractError> {
+    value.ok_or_else(|| ExtractError::new(field, selector, "nothing matched".to_string()))
+}
+
+fn parse_value<T>(raw: &str, parse: Parse, field: &str, selector: &str) -> Result<T, ExtractError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let cleaned = match parse {
+        Parse::Text => raw.trim().to_string(),
+        Parse::Money => clean_money(raw),
+    };
+    cleaned.parse().map_err(|error| ExtractError::new(field, selector, format!("can't parse {:?}: {}", raw.trim(), error)))
+}
+
+#[doc(hidden)]
+pub fn first_value<T>(node: &Node, query: &Query, parse: Parse, field: &str, selector: &str) -> Result<Option<T>, ExtractError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    query.extract(node).get().map(|raw| parse_value(raw, parse, field, selector)).transpose()
+}
+
+#[doc(hidden)]
+pub fn all_values<T>(node: &Node, query: &Query, parse: Parse, field: &str, selector: &str) -> Result<Vec<T>, ExtractError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    query.extract(node).get_all().iter().map(|raw| parse_value(raw, parse, field, selector)).collect()
+}
+
+#[doc(hidden)]
+pub fn first_nested<T: Extract>(node: &Node, query: &Query, field: &str) -> Result<Option<T>, ExtractError> {
+    query.select_first(node).map(|found| T::extract(found).map_err(|error| error.inside(field, None))).transpose()
+}
+
+#[doc(hidden)]
+pub fn all_nested<T: Extract>(node: &Node, query: &Query, field: &str) -> Result<Vec<T>, ExtractError> {
+    query
+        .select(node)
+        .into_iter()
+        .enumerate()
+        .map(|(i, found)| T::extract(found).map_err(|error| error.inside(field, Some(i))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract::*;
+    use crate::html;
+
+    #[derive(Debug, PartialEq, crate::Extract)]
+    struct Review {
+        #[select(".author::text")]
+        author: String,
+        #[select(".stars::attr(data-stars)")]
+        stars: u8,
+    }
+
+    #[derive(Debug, PartialEq, crate::Extract)]
+    struct Product {
+        #[select("h1::text")]
+        title: String,
+        #[select(".price::text", parse = "money")]
+        price: Option<f64>,
+        #[select(".sku::text")]
+        sku: Option<String>,
+        #[select_all(".tags li::text")]
+        tags: Vec<String>,
+        #[select_all("li.review")]
+        reviews: Vec<Review>,
+        #[select("li.review")]
+        top_review: Option<Review>,
+    }
+
+    // the :: inside the attribute selector isn't a pseudo-element
+    #[derive(Debug, PartialEq, crate::Extract)]
+    struct Noted {
+        #[select("li[data-note*=\"::\"]")]
+        review: Option<Review>,
+    }
+
+    fn review(author: &str, stars: &str) -> Node {
+        html! { li(class = "review") { span(class = "author") { { Node::Text(author.to_string()) } } span(class = "stars", "data-stars" = stars) } }
+    }
+
+    #[test]
+    fn test_derive_extract() {
+        let page = html! {
+            div {
+                h1 { " Widget " }
+                span(class = "price") { "$1,234.50" }
+                ul(class = "tags") { li { "new" } li { "sale" } }
+                ul { { review("ana", "5") } { review("bo", "3") } }
+            }
+        };
+        let product = Product::extract(&page).unwrap();
+        assert_eq!(product.title, "Widget");
+        assert_eq!(product.price, Some(1234.5));
+        assert_eq!(product.sku, None);
+        assert_eq!(product.tags, vec!["new", "sale"]);
+        assert_eq!(product.reviews, vec![
+            Review { author: "ana".to_string(), stars: 5 },
+            Review { author: "bo".to_string(), stars: 3 },
+        ]);
+        assert_eq!(product.top_review.map(|review| review.author), Some("ana".to_string()));
+    }
+
+    #[test]
+    fn test_double_colon_inside_attribute_selector() {
+        let page = html! { ul { li(class = "review", "data-note" = "see::this") { span(class = "author") { "ana" } span(class = "stars", "data-stars" = "4") } } };
+        assert_eq!(Noted::extract(&page).unwrap(), Noted { review: Some(Review { author: "ana".to_string(), stars: 4 }) });
+    }
+
+    #[test]
+    fn test_field_errors() {
+        let missing_title = html! { div { span(class = "price") { "3" } } };
+        let error = Product::extract(&missing_title).unwrap_err();
+        assert_eq!(error, ExtractError { field: "title".to_string(), selector: "h1::text".to_string(), message: "nothing matched".to_string() });
+
+        let bad_stars = html! { div { h1 { "x" } ul { { review("ana", "5") } { review("bo", "lots") } } } };
+        let error = Product::extract(&bad_stars).unwrap_err();
+        assert_eq!(error.field, "reviews[1].stars");
+        assert_eq!(error.selector, ".stars::attr(data-stars)");
+        assert_eq!(error.to_string(), "reviews[1].stars (selector `.stars::attr(data-stars)`): can't parse \"lots\": invalid digit found in string");
+    }
+
+    #[test]
+    fn test_clean_money() {
+        assert_eq!(clean_money("$1,234.50"), "1234.50");
+        assert_eq!(clean_money("1.234,50 €"), "1234.50");
+        assert_eq!(clean_money("4,50 €"), "4.50");
+        assert_eq!(clean_money("1,234"), "1234");
+        assert_eq!(clean_money("1.234"), "1234");
+        assert_eq!(clean_money("4.5"), "4.5");
+        assert_eq!(clean_money("12.99"), "12.99");
+        assert_eq!(clean_money("1,234,567"), "1234567");
+        assert_eq!(clean_money("-£12"), "-12");
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use reqwest::{Client, Method, RequestBuilder, Url};
+use crate::Node;
+use crate::document::Document;
+use crate::traversal::pre_order;
+use crate::entities::decode_entities;
+use crate::links::resolve;
+use crate::util::{attribute, collapsed_text, decoded_attribute, is_tag, tag_of};
+
+// <form> elements read the way a browser would submit them.
+//
+// The fields are the form's successful controls, in tree order, following the
+// HTML spec's entry list rules:
+// - controls without a name, disabled controls, and controls inside a disabled
+//   <fieldset> (other than in its first <legend>) are left out
+// - checkboxes and radios count only when checked, with "on" as their default value
+// - a <select> gives its selected, enabled options; a single-choice select with
+//   nothing selected gives its first enabled option, like a browser shows it
+// - a hidden input named _charset_ gets "UTF-8"
+// - submit buttons are only sent when pressed, see Form::press
+// - file inputs are sent as empty file parts in multipart bodies, since no file
+//   has been chosen
+//
+// Controls outside the form that point at it with form="id" belong to it too,
+// and controls pointing at another form don't.
+//
+// Names, values, option text and the action are read with their entities
+// decoded, so value="a &amp; b" is sent as "a & b".
+//
+// Change fields with set/remove, then to_request gives a reqwest request with
+// the encoded fields: in the query string for GET, or as the body for POST.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enctype {
+    UrlEncoded,
+    Multipart,
+    TextPlain,
+}
+
+impl Enctype {
+    pub fn mime(&self) -> &'static str {
+        match self {
+            Enctype::UrlEncoded => "application/x-www-form-urlencoded",
+            Enctype::Multipart => "multipart/form-data",
+            Enctype::TextPlain => "text/plain",
+        }
+    }
+}
+
+// a submit button: <input type=submit|image> or <button type=submit>
+#[derive(Debug, Clone, PartialEq)]
+pub struct Button {
+    pub name: String,
+    pub value: String,
+    // image buttons send the click coordinates, name.x and name.y, instead of a value
+    pub image: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    // the action resolved against the base URL; a missing or empty action
+    // submits to the base URL itself. None when there is nothing to resolve against
+    pub action: Option<Url>,
+    // GET or POST
+    pub method: Method,
+    pub enctype: Enctype,
+    // (name, value) pairs; names may repeat
+    pub fields: Vec<(String, String)>,
+    // names of the file inputs
+    pub file_fields: Vec<String>,
+    // the named submit buttons, in tree order
+    pub buttons: Vec<Button>,
+}
+
+pub fn forms(document: &Document) -> Vec<Form> {
+    forms_from(document.root(), document.base_url().as_ref())
+}
+
+// every form under `root_node`, with actions resolved against `base`
+pub fn forms_from(root_node: &Node, base: Option<&Url>) -> Vec<Form> {
+    let mut ids: HashMap<&str, &Node> = HashMap::new();
+    for (node, _) in pre_order(root_node) {
+        if let Node::Element { tag_name, attributes, .. } = node && tag_name.eq_ignore_ascii_case("form") && let Some(id) = attributes.get("id") {
+            ids.entry(id.as_str()).or_insert(node);
+        }
+    }
+    let mut controls = Vec::new();
+    collect_controls(root_node, None, false, &ids, &mut controls);
+
+    pre_order(root_node)
+        .map(|(node, _)| node)
+        .filter(|node| is_tag(node, "form"))
+        .map(|form| {
+            let owned = controls.iter().filter(|control| control.owner.is_some_and(|owner| std::ptr::eq(owner, form)));
+            build_form(form, owned.map(|control| control.node), base)
+        })
+        .collect()
+}
+
+struct Control<'a> {
+    node: &'a Node,
+    owner: Option<&'a Node>,
+}
+
+// enabled form controls in tree order, each with the form it belongs to
+fn collect_controls<'a>(node: &'a Node, form: Option<&'a Node>, disabled: bool, ids: &HashMap<&str, &'a Node>, controls: &mut Vec<Control<'a>>) {
+    let Node::Element { tag_name, children, .. } = node else { return };
+    let tag_name = tag_name.to_ascii_lowercase();
+    // nested forms aren't allowed, the inner one is ignored like the parser does
+    let form = if tag_name == "form" && form.is_none() { Some(node) } else { form };
+    if matches!(tag_name.as_str(), "input" | "select" | "textarea" | "button") {
+        if !disabled && attribute(node, "disabled").is_none() {
+            let owner = match attribute(node, "form") {
+                Some(id) => ids.get(id).copied(),
+                None => form,
+            };
+            controls.push(Control { node, owner });
+        }
+        // controls don't contain other controls
+        return;
+    }
+    let fieldset_disabled = tag_name == "fieldset" && attribute(node, "disabled").is_some();
+    let first_legend = children.iter().find(|child| matches!(child, Node::Element { .. })).filter(|child| is_tag(child, "legend"));
+    for child in children {
+        let in_legend = first_legend.is_some_and(|legend| std::ptr::eq(legend, child));
+        collect_controls(child, form, disabled || (fieldset_disabled && !in_legend), ids, controls);
+    }
+}
+
+fn build_form<'a>(form: &Node, controls: impl Iterator<Item = &'a Node>, base: Option<&Url>) -> Form {
+    let method = match attribute(form, "method").map(|method| method.trim().to_ascii_lowercase()).as_deref() {
+        Some("post") => Method::POST,
+        _ => Method::GET,
+    };
+    let enctype = match attribute(form, "enctype").map(|enctype| enctype.trim().to_ascii_lowercase()).as_deref() {
+        Some("multipart/form-data") => Enctype::Multipart,
+        Some("text/plain") => Enctype::TextPlain,
+        _ => Enctype::UrlEncoded,
+    };
+    let action = match attribute(form, "action").filter(|action| !action.trim().is_empty()) {
+        Some(action) => resolve(base, action),
+        None => base.cloned(),
+    };
+    let mut built = Form {
+        id: decoded_attribute(form, "id").map(String::from),
+        name: decoded_attribute(form, "name").map(String::from),
+        action,
+        method,
+        enctype,
+        fields: Vec::new(),
+        file_fields: Vec::new(),
+        buttons: Vec::new(),
+    };
+    for control in controls {
+        let Some(name) = decoded_attribute(control, "name").filter(|name| !name.is_empty()) else { continue };
+        let name = name.into_owned();
+        let value = decoded_attribute(control, "value").unwrap_or_default().into_owned();
+        match tag_of(control).as_str() {
+            "select" => built.fields.extend(selected_options(control).into_iter().map(|value| (name.clone(), value))),
+            "textarea" => {
+                let text = control.text_content();
+                let text = decode_entities(text.strip_prefix('\n').unwrap_or(&text)).into_owned();
+                built.fields.push((name, text));
+            }
+            "button" => {
+                if attribute(control, "type").is_none_or(|kind| kind.trim().eq_ignore_ascii_case("submit")) {
+                    built.buttons.push(Button { name, value, image: false });
+                }
+            }
+            _ => match attribute(control, "type").map(|kind| kind.trim().to_ascii_lowercase()).as_deref().unwrap_or("text") {
+                "submit" => built.buttons.push(Button { name, value, image: false }),
+                "image" => built.buttons.push(Button { name, value, image: true }),
+                "reset" | "button" => {}
+                "checkbox" | "radio" => {
+                    if attribute(control, "checked").is_some() {
+                        built.fields.push((name, decoded_attribute(control, "value").unwrap_or("on".into()).into_owned()));
+                    }
+                }
+                "file" => built.file_fields.push(name),
+                "hidden" if name.eq_ignore_ascii_case("_charset_") => built.fields.push((name, "UTF-8".to_string())),
+                _ => built.fields.push((name, value)),
+            },
+        }
+    }
+    built
+}
+
+fn selected_options(select: &Node) -> Vec<String> {
+    let options: Vec<&Node> = pre_order(select).map(|(node, _)| node).filter(|node| is_tag(node, "option")).collect();
+    let enabled = |option: &&&Node| attribute(option, "disabled").is_none();
+    let value = |option: &&Node| decoded_attribute(option, "value").map(String::from).unwrap_or_else(|| collapsed_text(option));
+    let selected: Vec<String> = options.iter().filter(enabled).filter(|option| attribute(option, "selected").is_some()).map(value).collect();
+    let multiple = attribute(select, "multiple").is_some();
+    let size = attribute(select, "size").and_then(|size| size.trim().parse::<usize>().ok()).unwrap_or(1);
+    if selected.is_empty() && !multiple && size <= 1 {
+        return options.iter().find(enabled).map(value).into_iter().collect();
+    }
+    if !multiple {
+        // only the last selected option stays selected
+        return selected.into_iter().last().into_iter().collect();
+    }
+    selected
+}
+
+// application/x-www-form-urlencoded, spaces as +
+fn urlencode(fields: &[(String, String)]) -> String {
+    let mut url = Url::parse("http://localhost/").expect("static URL");
+    url.query_pairs_mut().extend_pairs(fields);
+    url.query().unwrap_or_default().to_string()
+}
+
+// names in Content-Disposition get ", CR and LF escaped, as browsers do
+fn multipart_name(name: &str) -> String {
+    name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+impl Form {
+    // the first value of a field
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.fields.iter().filter(|(field, _)| field == name).map(|(_, value)| value.as_str()).collect()
+    }
+
+    // sets a field to one value, replacing every value it had, or adds it at the end
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        match self.fields.iter().position(|(field, _)| field == name) {
+            Some(first) => {
+                self.fields[first].1 = value.to_string();
+                let mut index = 0;
+                self.fields.retain(|(field, _)| {
+                    index += 1;
+                    index - 1 == first || field != name
+                });
+            }
+            None => self.fields.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    // adds another value, for checkbox groups and multiple selects
+    pub fn append(&mut self, name: &str, value: &str) -> &mut Self {
+        self.fields.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.fields.retain(|(field, _)| field != name);
+        self
+    }
+
+    // submits with the named button, adding its value (or for image buttons the
+    // click coordinates 0,0) to the fields
+    pub fn press(&mut self, button: &str) -> Result<&mut Self, String> {
+        let Some(found) = self.buttons.iter().find(|found| found.name == button).cloned() else {
+            return Err(format!("no submit button named '{}'", button));
+        };
+        if found.image {
+            self.fields.push((format!("{}.x", found.name), "0".to_string()));
+            self.fields.push((format!("{}.y", found.name), "0".to_string()));
+        } else {
+            self.fields.push((found.name, found.value));
+        }
+        Ok(self)
+    }
+
+    // the request a browser would send, on a new default client like
+    // get_html_graph uses
+    pub fn to_request(&self) -> Result<RequestBuilder, String> {
+        self.to_request_with(&Client::new())
+    }
+
+    // the same on a given client, e.g. one keeping cookies between steps
+    pub fn to_request_with(&self, client: &Client) -> Result<RequestBuilder, String> {
+        let mut url = self.action.clone().ok_or_else(|| "form has no absolute action URL; give the document a URL".to_string())?;
+        if self.method == Method::GET {
+            url.set_query(Some(&urlencode(&self.fields)));
+            return Ok(client.get(url));
+        }
+        let (content_type, body) = match self.enctype {
+            Enctype::UrlEncoded => (self.enctype.mime().to_string(), urlencode(&self.fields)),
+            Enctype::TextPlain => (self.enctype.mime().to_string(), self.fields.iter().map(|(name, value)| format!("{}={}\r\n", name, value)).collect()),
+            Enctype::Multipart => {
+                let boundary = self.boundary();
+                (format!("{}; boundary={}", self.enctype.mime(), boundary), self.multipart_body(&boundary))
+            }
+        };
+        Ok(client.post(url).header(reqwest::header::CONTENT_TYPE, content_type).body(body))
+    }
+
+    // a boundary that appears in none of the values
+    fn boundary(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.fields.hash(&mut hasher);
+        let mut seed = hasher.finish();
+        loop {
+            let boundary = format!("----FormBoundary{:016x}", seed);
+            if !self.fields.iter().any(|(name, value)| name.contains(&boundary) || value.contains(&boundary)) {
+                return boundary;
+            }
+            seed = seed.wrapping_add(1);
+        }
+    }
+
+    fn multipart_body(&self, boundary: &str) -> String {
+        let mut body = String::new();
+        for (name, value) in &self.fields {
+            body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, multipart_name(name), value));
+        }
+        for name in &self.file_fields {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"\"\r\nContent-Type: application/octet-stream\r\n\r\n\r\n",
+                boundary,
+                multipart_name(name)
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::forms::*;
+
+    fn body_of(request: RequestBuilder) -> (reqwest::Request, String) {
+        let request = request.build().unwrap();
+        let body = request.body().and_then(|body| body.as_bytes()).map(|bytes| String::from_utf8(bytes.to_vec()).unwrap()).unwrap_or_default();
+        (request, body)
+    }
+
+    fn page() -> Document {
+        Document::parse(r#"<html><body>
+            <form id="search" action="/search">
+                <input name="q" value="red shoes">
+                <input type="checkbox" name="new" checked>
+                <input type="checkbox" name="sale" value="yes">
+                <input type="radio" name="sort" value="price">
+                <input type="radio" name="sort" value="rating" checked>
+                <select name="size"><option value="">any</option><option>42</option></select>
+                <select name="colors" multiple>
+                    <optgroup label="warm"><option selected>red</option><option value="o" selected disabled>orange</option></optgroup>
+                    <option value="b" selected>blue</option>
+                </select>
+                <input type="hidden" name="_charset_">
+                <input name="off" disabled value="x">
+                <fieldset disabled><legend><input name="in_legend" value="1"></legend><input name="in_fieldset" value="2"></fieldset>
+                <input type="submit" name="go" value="Search">
+                <input type="image" name="map" src="go.png">
+                <button name="other" value="not mine" form="login">Other</button>
+                <input value="nameless">
+            </form>
+            <form id="login" method="POST" enctype="multipart/form-data">
+                <input name="user" value="ana">
+                <textarea name="note">
+line one
+line two</textarea>
+                <input type="file" name="avatar">
+                <button type="reset" name="clear">clear</button>
+            </form>
+            <input name="remember" value="1" form="login">
+        </body></html>"#)
+        .with_url(Url::parse("https://example.com/shop/").unwrap())
+    }
+
+    #[test]
+    fn test_successful_controls() {
+        let forms = forms(&page());
+        assert_eq!(forms.len(), 2);
+        let search = &forms[0];
+        assert_eq!(search.action.as_ref().map(Url::as_str), Some("https://example.com/search"));
+        assert_eq!(search.method, Method::GET);
+        assert_eq!(search.fields, vec![
+            ("q".to_string(), "red shoes".to_string()),
+            ("new".to_string(), "on".to_string()),
+            ("sort".to_string(), "rating".to_string()),
+            ("size".to_string(), "".to_string()),
+            ("colors".to_string(), "red".to_string()),
+            ("colors".to_string(), "b".to_string()),
+            ("_charset_".to_string(), "UTF-8".to_string()),
+            ("in_legend".to_string(), "1".to_string()),
+        ]);
+        let buttons: Vec<&str> = search.buttons.iter().map(|button| button.name.as_str()).collect();
+        assert_eq!(buttons, vec!["go", "map"]);
+
+        let login = &forms[1];
+        assert_eq!(login.action.as_ref().map(Url::as_str), Some("https://example.com/shop/"));
+        assert_eq!(login.get("note"), Some("line one\nline two"));
+        assert_eq!(login.get_all("remember"), vec!["1"]);
+        assert_eq!(login.file_fields, vec!["avatar"]);
+        assert_eq!(login.buttons.iter().map(|button| button.name.as_str()).collect::<Vec<_>>(), vec!["other"]);
+    }
+
+    #[test]
+    fn test_get_request() {
+        let mut search = forms(&page()).remove(0);
+        search.set("q", "blue & green").remove("colors").append("colors", "c").press("map").unwrap();
+        assert!(search.press("missing").is_err());
+        let (request, body) = body_of(search.to_request().unwrap());
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.url().as_str(),
+            "https://example.com/search?q=blue+%26+green&new=on&sort=rating&size=&_charset_=UTF-8&in_legend=1&colors=c&map.x=0&map.y=0"
+        );
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_post_bodies() {
+        let mut login = forms(&page()).remove(1);
+        login.set("note", "hi").press("other").unwrap();
+        let (request, body) = body_of(login.to_request().unwrap());
+        assert_eq!(request.method(), Method::POST);
+        let content_type = request.headers()[reqwest::header::CONTENT_TYPE].to_str().unwrap().to_string();
+        let boundary = content_type.strip_prefix("multipart/form-data; boundary=").unwrap();
+        assert_eq!(body, format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"user\"\r\n\r\nana\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhi\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"remember\"\r\n\r\n1\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"other\"\r\n\r\nnot mine\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"\"\r\nContent-Type: application/octet-stream\r\n\r\n\r\n\
+             --{b}--\r\n",
+            b = boundary
+        ));
+
+        login.enctype = Enctype::UrlEncoded;
+        let (request, body) = body_of(login.to_request().unwrap());
+        assert_eq!(request.headers()[reqwest::header::CONTENT_TYPE], "application/x-www-form-urlencoded");
+        assert_eq!(body, "user=ana&note=hi&remember=1&other=not+mine");
+
+        login.action = None;
+        assert!(login.to_request().is_err());
+    }
+
+    #[test]
+    fn test_entities_are_decoded() {
+        let page = Document::parse(r#"<html><body><form action="/search?lang=en&amp;safe=1">
+            <input name="q" value="fish &amp; chips">
+            <input type="checkbox" name="tag" value="a&lt;b" checked>
+            <select name="brand"><option selected>Marks &amp; Spencer</option></select>
+            <select name="shop"><option value="H&amp;M">H&amp;M</option></select>
+            <textarea name="note">R&amp;D</textarea>
+        </form></body></html>"#)
+        .with_url(Url::parse("https://example.com/").unwrap());
+        let form = forms(&page).remove(0);
+        assert_eq!(form.action.as_ref().map(Url::as_str), Some("https://example.com/search?lang=en&safe=1"));
+        assert_eq!(form.get("q"), Some("fish & chips"));
+        assert_eq!(form.get("tag"), Some("a<b"));
+        assert_eq!(form.get("brand"), Some("Marks & Spencer"));
+        assert_eq!(form.get("shop"), Some("H&M"));
+        assert_eq!(form.get("note"), Some("R&D"));
+    }
+}
@@ -0,0 +1,430 @@
+use std::fmt::Write as _;
+use crate::Node;
+use crate::document::Document;
+use crate::entities::decode_entities;
+use crate::query::Query;
+use crate::util::{attribute, collapsed_text, tag_of};
+
+// Wrapper induction: learning selectors from a few example pages and the values
+// wanted from each of them.
+//
+//     let wrapper = induce_wrapper(&[
+//         Example::new(&first).value("title", "Oak bench").value("price", "249.00"),
+//         Example::new(&second).value("title", "Pine stool").value("price", "39.50"),
+//     ])?;
+//     let fields = wrapper.extract(&third);
+//
+// A value is found where it is the whole text of an element or the whole value
+// of one of its attributes, whitespace collapsed and entities decoded. For every
+// place it turns up, candidate selectors are generalized from the element and
+// its ancestors: ids, classes and attributes like itemprop, property or name,
+// alone or under an ancestor carrying one, and as a last resort the tag path
+// with :nth-of-type. Ids and classes with long runs of digits are left out, they
+// tend to change from page to page.
+//
+// A candidate is kept when its first match gives the right value on every
+// example. The best one wins on
+//
+//   kind of selector: id 0.95, itemprop/property/name 0.9, class 0.85,
+//   under an id 0.85, under a class or attribute 0.8, bare tag 0.7, tag path 0.5
+//   × 0.8 when it matches more than one element on some page
+//   × 1 - 0.5^(examples + 1), so one example caps it at 0.75
+//
+// and the wrapper is as confident as its weakest field.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueSource {
+    // the element's text, whitespace collapsed
+    Text,
+    Attribute(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRule {
+    pub field: String,
+    pub query: Query,
+    pub source: ValueSource,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wrapper {
+    // one rule per field, in the order the fields were first given
+    pub rules: Vec<FieldRule>,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Example<'a> {
+    pub document: &'a Document,
+    // field name and the value wanted from this page
+    pub values: Vec<(String, String)>,
+}
+
+impl<'a> Example<'a> {
+    pub fn new(document: &'a Document) -> Self {
+        Example { document, values: Vec::new() }
+    }
+
+    pub fn value(mut self, field: &str, value: &str) -> Self {
+        self.values.push((field.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Wrapper {
+    // field name and value for every rule that finds something on the page
+    pub fn extract(&self, document: &Document) -> Vec<(String, String)> {
+        self.extract_from(document.root())
+    }
+
+    pub fn extract_from(&self, root_node: &Node) -> Vec<(String, String)> {
+        self.rules.iter().filter_map(|rule| Some((rule.field.clone(), rule.apply(root_node)?))).collect()
+    }
+
+    pub fn rule(&self, field: &str) -> Option<&FieldRule> {
+        self.rules.iter().find(|rule| rule.field == field)
+    }
+}
+
+impl FieldRule {
+    pub fn apply(&self, root_node: &Node) -> Option<String> {
+        value_of(self.query.select_first(root_node)?, &self.source)
+    }
+}
+
+// attributes whose values name what an element holds
+const NAMING_ATTRIBUTES: [&str; 6] = ["itemprop", "property", "name", "role", "data-testid", "data-field"];
+const SKIPPED_TAGS: [&str; 4] = ["script", "style", "noscript", "template"];
+// how many ancestors a selector may be anchored on
+const ANCESTOR_LEVELS: usize = 4;
+
+fn normalize(text: &str) -> String {
+    decode_entities(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn value_of(node: &Node, source: &ValueSource) -> Option<String> {
+    let value = match source {
+        ValueSource::Text => collapsed_text(node),
+        ValueSource::Attribute(name) => normalize(attribute(node, name)?),
+    };
+    if value.is_empty() { None } else { Some(value) }
+}
+
+// ids and classes that look generated, like "post-48213"
+fn is_volatile(name: &str) -> bool {
+    let mut run = 0;
+    for c in name.chars() {
+        run = if c.is_ascii_digit() { run + 1 } else { 0 };
+        if run >= 3 {
+            return true;
+        }
+    }
+    false
+}
+
+// a CSS identifier, escaped the way CSSOM serializes one: a digit at the start,
+// or after a leading -, and control characters become hex escapes like "\31 ",
+// other ASCII punctuation gets a backslash
+fn ident(name: &str) -> String {
+    let mut out = String::new();
+    let first = name.chars().next();
+    for (i, c) in name.chars().enumerate() {
+        let leading_digit = c.is_ascii_digit() && (i == 0 || (i == 1 && first == Some('-')));
+        if c == '\0' {
+            out.push('\u{FFFD}');
+        } else if leading_digit || c.is_ascii_control() {
+            let _ = write!(out, "\\{:x} ", c as u32);
+        } else if i == 0 && c == '-' && name.len() == 1 {
+            out.push_str("\\-");
+        } else if (c as u32) < 0x80 && !(c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            out.push('\\');
+            out.push(c);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn classes(node: &Node) -> Vec<&str> {
+    attribute(node, "class").map(|class| class.split_whitespace().filter(|class| !is_volatile(class)).collect()).unwrap_or_default()
+}
+
+fn id_of(node: &Node) -> Option<&str> {
+    attribute(node, "id").map(str::trim).filter(|id| !id.is_empty() && !id.contains(char::is_whitespace) && !is_volatile(id))
+}
+
+// where a value turned up: the elements from the root down to it, each with
+// its :nth-of-type position
+struct Occurrence<'a> {
+    chain: Vec<(&'a Node, usize)>,
+    source: ValueSource,
+}
+
+// appends decoded text to `buffer`, each whitespace run becoming one space, so
+// that normalize(a + b) is the trimmed result of appending a then b
+fn push_normalized(text: &str, buffer: &mut String) {
+    for c in decode_entities(text).chars() {
+        if !c.is_whitespace() {
+            buffer.push(c);
+        } else if !buffer.ends_with(' ') {
+            buffer.push(' ');
+        }
+    }
+}
+
+// `text` is the normalized text of everything walked so far; an element's own
+// is what its children add to it, so no subtree is read twice
+fn find_occurrences<'a>(node: &'a Node, position: usize, value: &str, chain: &mut Vec<(&'a Node, usize)>, text: &mut String, found: &mut Vec<Occurrence<'a>>) {
+    let Node::Element { attributes, children, .. } = node else {
+        if let Node::Text(content) = node {
+            push_normalized(content, text);
+        }
+        return;
+    };
+    if SKIPPED_TAGS.contains(&tag_of(node).as_str()) {
+        // not a candidate, but its text is part of its ancestors'
+        push_normalized(&node.text_content(), text);
+        return;
+    }
+    chain.push((node, position));
+    let (found_at, text_at) = (found.len(), text.len());
+    let mut names: Vec<&String> = attributes.keys().filter(|name| name.as_str() != "class" && name.as_str() != "style").collect();
+    names.sort();
+    for name in names {
+        if normalize(&attributes[name]) == value {
+            found.push(Occurrence { chain: chain.clone(), source: ValueSource::Attribute(name.clone()) });
+        }
+    }
+    let mut seen: Vec<(String, usize)> = Vec::new();
+    for child in children {
+        let tag = tag_of(child);
+        if tag.is_empty() {
+            find_occurrences(child, 0, value, chain, text, found);
+            continue;
+        }
+        let nth = match seen.iter_mut().find(|(seen_tag, _)| *seen_tag == tag) {
+            Some((_, count)) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                seen.push((tag, 1));
+                1
+            }
+        };
+        find_occurrences(child, nth, value, chain, text, found);
+    }
+    // the text comes before the attributes, as the element's own value
+    if text[text_at..].trim_matches(' ') == value {
+        found.insert(found_at, Occurrence { chain: chain.clone(), source: ValueSource::Text });
+    }
+    chain.pop();
+}
+
+// the compounds describing one element, with how much each can be trusted
+fn compounds(node: &Node) -> Vec<(String, f64)> {
+    let tag = tag_of(node);
+    let mut found = Vec::new();
+    if let Some(id) = id_of(node) {
+        found.push((format!("{}#{}", tag, ident(id)), 0.95));
+    }
+    for name in NAMING_ATTRIBUTES {
+        if let Some(value) = attribute(node, name).map(str::trim).filter(|value| !value.is_empty()) {
+            found.push((format!("{}[{}={}]", tag, name, quoted(value)), 0.9));
+        }
+    }
+    for class in classes(node) {
+        found.push((format!("{}.{}", tag, ident(class)), 0.85));
+    }
+    found
+}
+
+fn candidates(occurrence: &Occurrence) -> Vec<(String, f64)> {
+    let (target, _) = occurrence.chain[occurrence.chain.len() - 1];
+    let tag = tag_of(target);
+    let own = compounds(target);
+    let mut found = own.clone();
+    found.push((tag.clone(), 0.7));
+
+    // anchored on an ancestor: "div.buy-box span" or "div.buy-box span.amount"
+    let ancestors = &occurrence.chain[..occurrence.chain.len() - 1];
+    for &(ancestor, _) in ancestors.iter().rev().take(ANCESTOR_LEVELS) {
+        for (anchor, weight) in compounds(ancestor) {
+            let weight = if weight >= 0.95 { 0.85 } else { 0.8 };
+            found.push((format!("{} {}", anchor, tag), weight));
+            for (compound, _) in &own {
+                found.push((format!("{} {}", anchor, compound), weight));
+            }
+        }
+    }
+
+    let path: Vec<String> = occurrence.chain.iter().map(|&(node, nth)| format!("{}:nth-of-type({})", tag_of(node), nth)).collect();
+    found.push((path.join(" > "), 0.5));
+    found
+}
+
+// the best rule for one field, or why there is none
+fn induce_field(field: &str, examples: &[(&Node, String)]) -> Result<FieldRule, String> {
+    let mut proposals: Vec<(String, ValueSource, f64)> = Vec::new();
+    for (number, (root, value)) in examples.iter().enumerate() {
+        let mut found = Vec::new();
+        find_occurrences(root, 1, value, &mut Vec::new(), &mut String::new(), &mut found);
+        if found.is_empty() {
+            return Err(format!("field `{}`: value {:?} not found in example {}", field, value, number + 1));
+        }
+        for occurrence in &found {
+            for (selector, weight) in candidates(occurrence) {
+                match proposals.iter_mut().find(|(known, source, _)| *known == selector && *source == occurrence.source) {
+                    Some(proposal) => proposal.2 = proposal.2.max(weight),
+                    None => proposals.push((selector, occurrence.source.clone(), weight)),
+                }
+            }
+        }
+    }
+
+    let coverage = 1.0 - 0.5f64.powi(examples.len() as i32 + 1);
+    let mut best: Option<FieldRule> = None;
+    for (selector, source, weight) in proposals {
+        // the selectors are built here, one that doesn't compile is a bug in building them
+        let query = Query::compile(&selector).map_err(|error| format!("field `{}`: generated selector `{}` is invalid: {}", field, selector, error))?;
+        let mut unique = true;
+        let generalizes = examples.iter().all(|(root, value)| {
+            let matches = query.select(root);
+            unique &= matches.len() == 1;
+            matches.first().and_then(|node| value_of(node, &source)).as_ref() == Some(value)
+        });
+        if !generalizes {
+            continue;
+        }
+        let confidence = weight * if unique { 1.0 } else { 0.8 } * coverage;
+        // ties go to the shorter selector
+        let better = best.as_ref().is_none_or(|best| confidence > best.confidence || confidence == best.confidence && selector.len() < best.query.as_str().len());
+        if better {
+            best = Some(FieldRule { field: field.to_string(), query, source, confidence });
+        }
+    }
+    best.ok_or_else(|| format!("field `{}`: no selector gives the example value on every page", field))
+}
+
+pub fn induce_wrapper(examples: &[Example]) -> Result<Wrapper, String> {
+    if examples.is_empty() {
+        return Err("no examples".to_string());
+    }
+    let mut fields: Vec<&str> = Vec::new();
+    for example in examples {
+        for (field, _) in &example.values {
+            if !fields.contains(&field.as_str()) {
+                fields.push(field);
+            }
+        }
+    }
+    if fields.is_empty() {
+        return Err("the examples have no values".to_string());
+    }
+
+    let mut rules = Vec::new();
+    for field in fields {
+        let mut labelled = Vec::new();
+        for (number, example) in examples.iter().enumerate() {
+            let Some((_, value)) = example.values.iter().find(|(name, _)| name == field) else {
+                return Err(format!("field `{}`: no value given in example {}", field, number + 1));
+            };
+            let value = normalize(value);
+            if value.is_empty() {
+                return Err(format!("field `{}`: empty value in example {}", field, number + 1));
+            }
+            labelled.push((example.document.root(), value));
+        }
+        rules.push(induce_field(field, &labelled)?);
+    }
+    let confidence = rules.iter().map(|rule| rule.confidence).fold(1.0, f64::min);
+    Ok(Wrapper { rules, confidence })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::induction::*;
+
+    fn fixture(name: &str) -> Document {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/induction").join(name);
+        Document::parse(&fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn test_induce_from_product_pages() {
+        let (first, second, unseen) = (fixture("product-1.html"), fixture("product-2.html"), fixture("product-3.html"));
+        let wrapper = induce_wrapper(&[
+            Example::new(&first).value("title", "Oak Workbench").value("price", "249.00").value("image", "/img/oak-workbench.jpg"),
+            Example::new(&second).value("title", "Pine Step Stool").value("price", "39.50").value("image", "/img/pine-stool.jpg"),
+        ])
+        .unwrap();
+
+        let describe: Vec<String> = wrapper.rules.iter().map(|rule| format!("{} {} {:?} {:.2}", rule.field, rule.query, rule.source, rule.confidence)).collect();
+        assert_eq!(describe, [
+            "title h1[itemprop=\"name\"] Text 0.79",
+            "price span[itemprop=\"price\"] Attribute(\"content\") 0.79",
+            "image img.hero Attribute(\"src\") 0.74",
+        ]);
+        assert_eq!(format!("{:.2}", wrapper.confidence), "0.74");
+
+        let fields = wrapper.extract(&unseen);
+        assert_eq!(fields, [
+            ("title".to_string(), "Walnut Tool Chest".to_string()),
+            ("price".to_string(), "1199.00".to_string()),
+            ("image".to_string(), "/img/walnut-chest.jpg".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_induction_errors() {
+        let first = fixture("product-1.html");
+        assert_eq!(induce_wrapper(&[]).unwrap_err(), "no examples");
+        assert_eq!(
+            induce_wrapper(&[Example::new(&first).value("title", "Birch Table")]).unwrap_err(),
+            "field `title`: value \"Birch Table\" not found in example 1"
+        );
+        let second = fixture("product-2.html");
+        assert_eq!(
+            induce_wrapper(&[Example::new(&first).value("title", "Oak Workbench"), Example::new(&second)]).unwrap_err(),
+            "field `title`: no value given in example 2"
+        );
+    }
+
+    #[test]
+    fn test_single_example_structural_fallback() {
+        let document = Document::parse("<html><body><div><p>intro</p><p>Total: <b>12</b></p></div><div><p>42 units</p></div></body></html>");
+        let wrapper = induce_wrapper(&[Example::new(&document).value("units", "42 units")]).unwrap();
+        let rule = wrapper.rule("units").unwrap();
+        assert_eq!(rule.query.as_str(), "html:nth-of-type(1) > body:nth-of-type(1) > div:nth-of-type(2)");
+        assert_eq!(rule.confidence, 0.5 * 0.75);
+        assert_eq!(rule.apply(document.root()).as_deref(), Some("42 units"));
+    }
+
+    #[test]
+    fn test_value_across_inline_elements() {
+        let document = Document::parse("<html><body><p class=\"dish\">Fish &amp;\n <b>Chips</b></p><p>Fish</p></body></html>");
+        let wrapper = induce_wrapper(&[Example::new(&document).value("dish", "Fish & Chips")]).unwrap();
+        assert_eq!(wrapper.rule("dish").unwrap().query.as_str(), "p.dish");
+    }
+
+    #[test]
+    fn test_names_starting_with_digits() {
+        assert_eq!(ident("1st"), "\\31 st");
+        assert_eq!(ident("-2x"), "-\\32 x");
+        assert_eq!(ident("a:b"), "a\\:b");
+        assert_eq!(ident("-"), "\\-");
+        let document = Document::parse("<html><body><p id=\"9lives\">Cat</p><p class=\"2col\">Dog</p><p class=\"2col\">Eel</p></body></html>");
+        let wrapper = induce_wrapper(&[Example::new(&document).value("cat", "Cat").value("dog", "Dog")]).unwrap();
+        assert_eq!(wrapper.rule("cat").unwrap().query.as_str(), "p#\\39 lives");
+        assert_eq!(wrapper.rule("dog").unwrap().query.as_str(), "p.\\32 col");
+        assert_eq!(wrapper.extract(&document), [("cat".to_string(), "Cat".to_string()), ("dog".to_string(), "Dog".to_string())]);
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+// A small JSON reader for what pages embed in <script type="application/ld+json">.
+//
+// That JSON is often not quite valid, so parse_json_lenient accepts:
+// - trailing commas in arrays and objects
+// - HTML comment markers (<!-- -->) and CDATA markers around the value
+// - // and /* */ comments
+// - raw control characters such as newlines inside strings
+//
+// Objects keep their keys in source order.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    // the value under `key` for objects, the first one if the key is repeated
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+// escaped and quoted
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// compact, valid JSON
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            JsonValue::Number(value) if value.is_finite() => write!(f, "{}", value),
+            JsonValue::Number(_) => f.write_str("null"),
+            JsonValue::String(value) => f.write_str(&json_string(value)),
+            JsonValue::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{}", json_string(key), value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+// arrays and objects nested deeper than this are rejected instead of
+// recursing until the stack runs out
+const MAX_DEPTH: usize = 512;
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+    // arrays and objects currently open
+    depth: usize,
+}
+
+impl JsonParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.pos)
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    // whitespace, comments and the HTML/CDATA wrappers
+    fn skip_ignored(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            let skip = ["<!--", "-->", "<![CDATA[", "]]>"].into_iter().find(|marker| self.starts_with(marker));
+            if let Some(marker) = skip {
+                self.pos += marker.chars().count();
+            } else if self.starts_with("//") {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else if self.starts_with("/*") {
+                self.pos += 2;
+                while self.peek().is_some() && !self.starts_with("*/") {
+                    self.pos += 1;
+                }
+                self.pos = (self.pos + 2).min(self.chars.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        self.skip_ignored();
+        match self.peek() {
+            Some('{' | '[') if self.depth == MAX_DEPTH => Err(self.error("nesting too deep")),
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => Ok(JsonValue::String(self.string()?)),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(JsonValue::Bool(true))
+            }
+            Some(_) if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(JsonValue::Bool(false))
+            }
+            Some(_) if self.starts_with("null") => {
+                self.pos += 4;
+                Ok(JsonValue::Null)
+            }
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<JsonValue, String>) -> Result<JsonValue, String> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_ignored();
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                Some('"') => {
+                    let key = self.string()?;
+                    self.skip_ignored();
+                    if self.peek() != Some(':') {
+                        return Err(self.error("expected ':'"));
+                    }
+                    self.pos += 1;
+                    entries.push((key, self.value()?));
+                    self.skip_ignored();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {}
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+                _ => return Err(self.error("expected a key or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_ignored();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(JsonValue::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_ignored();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => out.push(self.unicode_escape()?),
+                        // \" \\ \/ and anything unknown stand for themselves
+                        c => out.push(c),
+                    }
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits: String = self.chars.get(self.pos..self.pos + 4)?.iter().collect();
+        let value = u32::from_str_radix(&digits, 16).ok()?;
+        self.pos += 4;
+        Some(value)
+    }
+
+    // \uXXXX, surrogate pairs included
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4().ok_or_else(|| self.error("bad \\u escape"))?;
+        if (0xD800..0xDC00).contains(&high) && self.starts_with("\\u") {
+            let start = self.pos;
+            self.pos += 2;
+            match self.hex4() {
+                Some(low) if (0xDC00..0xE000).contains(&low) => {
+                    return Ok(char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap_or('\u{fffd}'));
+                }
+                _ => self.pos = start,
+            }
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{fffd}'))
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(JsonValue::Number).map_err(|_| format!("invalid number '{}' at position {}", text, start))
+    }
+}
+
+pub fn parse_json_lenient(input: &str) -> Result<JsonValue, String> {
+    let mut parser = JsonParser { chars: input.chars().collect(), pos: 0, depth: 0 };
+    let value = parser.value()?;
+    parser.skip_ignored();
+    // a stray `;` after the value shows up now and then
+    if parser.peek() == Some(';') {
+        parser.pos += 1;
+        parser.skip_ignored();
+    }
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(parser.error(&format!("unexpected '{}' after the value", c))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::*;
+
+    #[test]
+    fn test_parse_strict_json() {
+        let value = parse_json_lenient(r#"{"a": [1, -2.5e1, true, null], "b": "x\"é😀"}"#).unwrap();
+        assert_eq!(value.get("a").unwrap().as_array().unwrap()[1].as_f64(), Some(-25.0));
+        assert_eq!(value.get("b").unwrap().as_str(), Some("x\"é😀"));
+        assert_eq!(value.to_string(), r#"{"a":[1,-25,true,null],"b":"x\"é😀"}"#);
+    }
+
+    #[test]
+    fn test_parse_lenient_json() {
+        let input = "<!--\n/*<![CDATA[*/ {\n  // product\n  \"name\": \"Line one\nline two\",\n  \"offers\": [{\"price\": 9.5,},],\n} /*]]>*/;\n-->";
+        let value = parse_json_lenient(input).unwrap();
+        assert_eq!(value.get("name").unwrap().as_str(), Some("Line one\nline two"));
+        assert_eq!(value.get("offers").unwrap().as_array().unwrap()[0].get("price").unwrap().as_f64(), Some(9.5));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_json_lenient("{\"a\" 1}").unwrap_err(), "expected ':' at position 5");
+        assert!(parse_json_lenient("[1, 2").is_err());
+        assert!(parse_json_lenient("{} {}").is_err());
+        assert!(parse_json_lenient("").is_err());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let deep = format!("{}{}", "[".repeat(512), "]".repeat(512));
+        assert!(parse_json_lenient(&deep).is_ok());
+        let too_deep = format!("{}1{}", "[".repeat(513), "]".repeat(513));
+        assert_eq!(parse_json_lenient(&too_deep).unwrap_err(), "nesting too deep at position 512");
+        assert_eq!(parse_json_lenient(&"[{\"a\":".repeat(200_000)).unwrap_err(), "nesting too deep at position 1536");
+    }
+}
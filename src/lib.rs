@@ -1,11 +1,62 @@
 //lib.rs for scraper
+// lets the code generated by #[derive(Extract)] say ::scraper inside this crate too
+extern crate self as scraper;
+
 pub mod buscador;
 pub mod tokenizer;
 pub mod parser;
+pub mod mutation;
+pub mod traversal;
+pub mod builder;
+pub mod diff;
+pub mod printer;
+pub mod selector;
+pub mod xpath;
+pub mod query;
+pub mod document;
+pub mod selection;
+pub mod predicate;
+pub mod extract;
+pub mod table;
+pub mod entities;
+mod util;
+mod render;
+pub mod links;
+pub mod json;
+pub mod metadata;
+pub mod forms;
+pub mod readability;
+pub mod markdown;
+pub mod plaintext;
+pub mod pagination;
+pub mod induction;
 
 pub use buscador::*;
 pub use tokenizer::*;
 pub use parser::*;
+pub use mutation::*;
+pub use traversal::*;
+pub use builder::*;
+pub use diff::*;
+pub use selector::*;
+pub use xpath::*;
+pub use query::*;
+pub use document::*;
+pub use selection::*;
+pub use predicate::Predicate;
+pub use extract::{Extract, ExtractError};
+pub use scraper_derive::Extract;
+pub use table::*;
+pub use entities::decode_entities;
+pub use links::{links, links_from, Link, LinkKind};
+pub use json::*;
+pub use metadata::{metadata, metadata_from, Metadata};
+pub use forms::{forms, forms_from, Form};
+pub use readability::{article, article_from, Article};
+pub use markdown::{to_markdown, MarkdownOptions};
+pub use plaintext::{to_text, TextOptions};
+pub use pagination::{pagination, pagination_from, Pagination};
+pub use induction::{induce_wrapper, Example, Wrapper};
 use std::collections::{HashMap};
 pub use reqwest::Error;
 
@@ -25,20 +76,27 @@ pub enum Node {
     Text(String),
 }
 
-pub async fn get_html_graph(url: &str) -> (Result<Node, String>) {
-    
+pub async fn get_html_graph(url: &str) -> Result<Node, String> {
+    get_document(url).await.map(Document::into_root)
+}
+
+// like get_html_graph, but keeps the URL the page was finally served from (after
+// redirects) so relative links can be resolved, see links.rs
+pub async fn get_document(url: &str) -> Result<Document, String> {
+
     let response = reqwest::get(url).await.map_err(|e|e.to_string())?;
     if response.status().is_success() {
+        let final_url = response.url().clone();
         // Read the response body as a string.
         let body = response.text().await.map_err(|e|e.to_string())?;
 
         let mut tokens = tokenize(&body);
         let graph = graph_creator(&mut tokens);
-        Ok(graph)
+        Ok(Document::new(graph).with_url(final_url))
 
     } else {
         println!("Request failed with status: {}", response.status());
-        return Err("Request failed with status: {}".to_string()  +  &response.status().to_string() );
+        Err("Request failed with status: {}".to_string()  +  &response.status().to_string() )
     }
 }
 
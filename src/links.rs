@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use reqwest::Url;
+use crate::Node;
+use crate::document::Document;
+use crate::traversal::pre_order;
+use crate::entities::decode_entities;
+use crate::util::collapsed_text;
+
+// Every URL a page points at, made absolute.
+//
+// Looks at a[href], area[href], link[href], img[src], iframe[src], script[src],
+// the src of audio, video and source, and the srcset of img and source. Relative
+// URLs are resolved against Document::base_url, so <base href> is honoured; when
+// there is no base at all, relative URLs are skipped since they can't be made
+// absolute. javascript: and data: URLs are skipped too. Entities in the
+// attribute values, like the &amp; of a query string, are decoded first, and so
+// are the ones in the link text.
+//
+// Duplicates (same URL and kind, fragment ignored) are dropped, keeping the
+// first one in document order.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    // a and area without a more specific rel
+    Navigation,
+    Canonical,
+    Next,
+    Prev,
+    // rel=alternate, with the hreflang in Link::hreflang when there is one
+    Alternate,
+    Stylesheet,
+    Icon,
+    Image,
+    Script,
+    Frame,
+    Media,
+    // any other <link>
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub url: Url,
+    pub kind: LinkKind,
+    // the element and attribute it came from, like ("img", "srcset")
+    pub element: String,
+    pub attribute: String,
+    // rel tokens, lowercased
+    pub rel: Vec<String>,
+    pub nofollow: bool,
+    pub hreflang: Option<String>,
+    // the anchor text for a and area, whitespace collapsed
+    pub text: Option<String>,
+    // the width or density descriptor of a srcset candidate, like "2x" or "480w"
+    pub descriptor: Option<String>,
+}
+
+// the URL/descriptor pairs of a srcset attribute, following the HTML spec's
+// splitting rules: URLs may contain commas, candidates are split on the commas
+// after a descriptor or at the end of a URL
+pub fn parse_srcset(srcset: &str) -> Vec<(String, Option<String>)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (url, after) = rest.split_at(url_end);
+        if let Some(url) = url.strip_suffix(',') {
+            candidates.push((url.trim_end_matches(',').to_string(), None));
+            rest = after;
+            continue;
+        }
+        let descriptor_end = after.find(',').unwrap_or(after.len());
+        let descriptor = after[..descriptor_end].trim();
+        candidates.push((url.to_string(), if descriptor.is_empty() { None } else { Some(descriptor.to_string()) }));
+        rest = &after[descriptor_end..];
+    }
+}
+
+fn kind_for(tag_name: &str, rel: &[String]) -> Option<LinkKind> {
+    let has = |token: &str| rel.iter().any(|rel| rel == token);
+    let by_rel = if has("canonical") {
+        Some(LinkKind::Canonical)
+    } else if has("next") {
+        Some(LinkKind::Next)
+    } else if has("prev") || has("previous") {
+        Some(LinkKind::Prev)
+    } else if has("alternate") && !has("stylesheet") {
+        Some(LinkKind::Alternate)
+    } else if has("stylesheet") {
+        Some(LinkKind::Stylesheet)
+    } else if has("icon") || has("apple-touch-icon") {
+        Some(LinkKind::Icon)
+    } else {
+        None
+    };
+    Some(match tag_name {
+        "a" | "area" => by_rel.unwrap_or(LinkKind::Navigation),
+        "link" => by_rel.unwrap_or(LinkKind::Other),
+        "img" => LinkKind::Image,
+        "iframe" | "frame" => LinkKind::Frame,
+        "script" => LinkKind::Script,
+        "audio" | "video" | "source" | "track" => LinkKind::Media,
+        _ => return None,
+    })
+}
+
+pub(crate) fn resolve(base: Option<&Url>, raw: &str) -> Option<Url> {
+    // attribute values reach us with their entities still in, like ?a=1&amp;b=2
+    let raw = decode_entities(raw);
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let url = match base {
+        Some(base) => base.join(raw).ok()?,
+        None => Url::parse(raw).ok()?,
+    };
+    match url.scheme() {
+        "javascript" | "data" => None,
+        _ => Some(url),
+    }
+}
+
+pub fn links(document: &Document) -> Vec<Link> {
+    links_from(document.root(), document.base_url().as_ref())
+}
+
+// links under any node, resolved against `base`
+pub fn links_from(root_node: &Node, base: Option<&Url>) -> Vec<Link> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+    for (node, _) in pre_order(root_node) {
+        let Node::Element { tag_name, attributes, .. } = node else { continue };
+        let tag_name = tag_name.to_ascii_lowercase();
+        let rel: Vec<String> = attributes.get("rel").map(|rel| rel.split_whitespace().map(str::to_ascii_lowercase).collect()).unwrap_or_default();
+        let Some(element_kind) = kind_for(&tag_name, &rel) else { continue };
+        let text = matches!(tag_name.as_str(), "a" | "area").then(|| collapsed_text(node));
+
+        let mut candidates: Vec<(&str, String, Option<String>)> = Vec::new();
+        for attribute in ["href", "src"] {
+            if let Some(value) = attributes.get(attribute) {
+                candidates.push((attribute, value.clone(), None));
+            }
+        }
+        if matches!(tag_name.as_str(), "img" | "source") && let Some(srcset) = attributes.get("srcset") {
+            candidates.extend(parse_srcset(srcset).into_iter().map(|(url, descriptor)| ("srcset", url, descriptor)));
+        }
+
+        for (attribute, raw, descriptor) in candidates {
+            // <source srcset> inside <picture> is an image, inside audio/video it's media
+            let kind = if tag_name == "source" && attribute == "srcset" { LinkKind::Image } else { element_kind };
+            let Some(url) = resolve(base, &raw) else { continue };
+            let mut key = url.clone();
+            key.set_fragment(None);
+            if !seen.insert((key, kind)) {
+                continue;
+            }
+            found.push(Link {
+                url,
+                kind,
+                element: tag_name.clone(),
+                attribute: attribute.to_string(),
+                rel: rel.clone(),
+                nofollow: rel.iter().any(|rel| rel == "nofollow"),
+                hreflang: attributes.get("hreflang").map(|hreflang| hreflang.trim().to_string()),
+                text: text.clone(),
+                descriptor,
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::links::*;
+    use crate::html;
+
+    fn page() -> Document {
+        Document::new(html! {
+            html {
+                head {
+                    base(href = "/catalog/")
+                    link(rel = "canonical", href = "https://example.com/catalog/widgets")
+                    link(rel = "alternate", hreflang = "de", href = "/de/katalog/")
+                    link(rel = "next", href = "?page=3")
+                    link(rel = "stylesheet", href = "site.css")
+                }
+                body {
+                    a(href = "widget-1") { "Widget " b { "one" } }
+                    a(href = "widget-1#reviews") { "reviews" }
+                    a(href = "https://ads.example.net/x", rel = "nofollow sponsored") { "ad" }
+                    a(href = "javascript:void(0)") { "menu" }
+                    a { "no href" }
+                    img(src = "w.png", srcset = "w-480.png 480w, w,800.png 800w , w-2x.png 2x")
+                    picture { source(srcset = "w.webp") }
+                }
+            }
+        })
+        .with_url(Url::parse("https://example.com/shop/list?page=2").unwrap())
+    }
+
+    #[test]
+    fn test_links_resolved_and_classified() {
+        let links = links(&page());
+        let summary: Vec<(LinkKind, &str)> = links.iter().map(|link| (link.kind, link.url.as_str())).collect();
+        assert_eq!(summary, vec![
+            (LinkKind::Canonical, "https://example.com/catalog/widgets"),
+            (LinkKind::Alternate, "https://example.com/de/katalog/"),
+            (LinkKind::Next, "https://example.com/catalog/?page=3"),
+            (LinkKind::Stylesheet, "https://example.com/catalog/site.css"),
+            (LinkKind::Navigation, "https://example.com/catalog/widget-1"),
+            (LinkKind::Navigation, "https://ads.example.net/x"),
+            (LinkKind::Image, "https://example.com/catalog/w.png"),
+            (LinkKind::Image, "https://example.com/catalog/w-480.png"),
+            (LinkKind::Image, "https://example.com/catalog/w,800.png"),
+            (LinkKind::Image, "https://example.com/catalog/w-2x.png"),
+            (LinkKind::Image, "https://example.com/catalog/w.webp"),
+        ]);
+        assert_eq!(links[1].hreflang.as_deref(), Some("de"));
+        assert_eq!(links[4].text.as_deref(), Some("Widget one"));
+        assert!(links[5].nofollow);
+        assert!(!links[4].nofollow);
+        assert_eq!(links[9].descriptor.as_deref(), Some("2x"));
+        assert_eq!((links[9].element.as_str(), links[9].attribute.as_str()), ("img", "srcset"));
+    }
+
+    #[test]
+    fn test_without_base_relative_links_are_skipped() {
+        let node = html! { div { a(href = "/relative") a(href = "https://example.com/abs") } };
+        let found = links_from(&node, None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].url.as_str(), "https://example.com/abs");
+    }
+
+    #[test]
+    fn test_entities_in_urls_are_decoded() {
+        let node = html! { div { a(href = "/list?q=rust&amp;page=2") { "Tom &amp; Jerry, " i { "page 2" } } } };
+        let found = links_from(&node, Some(&Url::parse("https://example.com/").unwrap()));
+        assert_eq!(found[0].url.as_str(), "https://example.com/list?q=rust&page=2");
+        assert_eq!(found[0].text.as_deref(), Some("Tom & Jerry, page 2"));
+    }
+
+    #[test]
+    fn test_parse_srcset() {
+        assert_eq!(parse_srcset("a.png 1x,b.png 2x"), vec![
+            ("a.png".to_string(), Some("1x".to_string())),
+            ("b.png".to_string(), Some("2x".to_string())),
+        ]);
+        assert_eq!(parse_srcset(" a.png, b.png 100w,, "), vec![
+            ("a.png".to_string(), None),
+            ("b.png".to_string(), Some("100w".to_string())),
+        ]);
+        assert!(parse_srcset("").is_empty());
+    }
+}
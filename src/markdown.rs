@@ -0,0 +1,386 @@
+use reqwest::Url;
+use crate::Node;
+use crate::entities::decode_entities;
+use crate::util::{attribute, children_of, is_block, resolve_or_raw, tag_of};
+use crate::render::{Segment, is_skipped, segments};
+use crate::table::layout;
+
+// HTML to Markdown (CommonMark, plus GFM tables and strikethrough).
+//
+// Block elements become blocks separated by a blank line: headings in ATX style,
+// paragraphs, blockquotes, fenced code blocks, lists and tables. Inline elements
+// become emphasis, strong, code spans, links and images, with whitespace
+// collapsed the way a browser would. Anything Markdown can't say (scripts,
+// forms, the <head>) is left out, and unknown elements just contribute their
+// content.
+//
+// Text is escaped so it stays text: characters with a meaning in Markdown get a
+// backslash, and so do line starts that would turn into headings, lists or quotes.
+// <pre> keeps its whitespace as is, in a fence long enough not to clash with
+// backticks inside it.
+
+#[derive(Debug, Clone)]
+pub struct MarkdownOptions {
+    // relative link and image URLs are resolved against this
+    pub base_url: Option<Url>,
+    // '-', '*' or '+'
+    pub bullet: char,
+    // '*' or '_'
+    pub emphasis: char,
+    pub strong: &'static str,
+    // three or more backticks or tildes
+    pub fence: &'static str,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions { base_url: None, bullet: '-', emphasis: '_', strong: "**", fence: "```" }
+    }
+}
+
+// backslashes for the characters that mean something inline
+fn escape_inline(text: &str, out: &mut String) {
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~' | '|') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+// a paragraph starting with "# ", "- ", "1. ", "> " and the like would turn into
+// something else, so its first character gets escaped
+fn escape_line_start(line: &str) -> String {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let after_digits = &line[digits..];
+    if digits > 0 && (after_digits.starts_with(". ") || after_digits.starts_with(") ") || after_digits == "." || after_digits == ")") {
+        return format!("{}\\{}", &line[..digits], after_digits);
+    }
+    let starts_block = line.starts_with('#')
+        || line.starts_with('=')
+        || ["- ", "+ ", "* "].iter().any(|marker| line.starts_with(marker))
+        || line == "-"
+        || line == "+"
+        || line.starts_with("---");
+    if starts_block { format!("\\{}", line) } else { line.to_string() }
+}
+
+// a backtick run one longer than any inside the code
+fn code_span(code: &str) -> String {
+    let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let ticks = "`".repeat(longest + 1);
+    let padded = code.starts_with('`') || code.ends_with('`');
+    if padded { format!("{} {} {}", ticks, code, ticks) } else { format!("{}{}{}", ticks, code, ticks) }
+}
+
+struct Converter<'o> {
+    options: &'o MarkdownOptions,
+}
+
+pub fn to_markdown(node: &Node, options: &MarkdownOptions) -> String {
+    let converter = Converter { options };
+    let blocks = converter.blocks(std::slice::from_ref(node));
+    let mut out = blocks.join("\n\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+impl Converter<'_> {
+    // entities are decoded first, so href="?a=1&amp;b=2" links to ?a=1&b=2
+    fn url(&self, raw: &str) -> String {
+        let url = resolve_or_raw(self.options.base_url.as_ref(), raw).replace(' ', "%20");
+        if url.contains(['(', ')']) { format!("<{}>", url.replace('<', "%3C").replace('>', "%3E")) } else { url }
+    }
+
+    fn title_suffix(&self, node: &Node) -> String {
+        match attribute(node, "title").map(str::trim).filter(|title| !title.is_empty()) {
+            Some(title) => format!(" \"{}\"", decode_entities(title).replace('"', "\\\"")),
+            None => String::new(),
+        }
+    }
+
+    // block-level content: runs of inline nodes become paragraphs
+    fn blocks(&self, nodes: &[Node]) -> Vec<String> {
+        let mut blocks = Vec::new();
+        for segment in segments(nodes) {
+            match segment {
+                Segment::Inline(run) => self.paragraph(&run, &mut blocks),
+                Segment::Block(node) => blocks.extend(self.block(node)),
+            }
+        }
+        blocks
+    }
+
+    fn paragraph(&self, nodes: &[&Node], blocks: &mut Vec<String>) {
+        let text = self.inline_text(nodes);
+        if !text.is_empty() {
+            blocks.push(escape_line_start(&text));
+        }
+    }
+
+    fn block(&self, node: &Node) -> Vec<String> {
+        let tag = tag_of(node);
+        let children = children_of(node);
+        match tag.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = tag[1..].parse::<usize>().unwrap_or(1);
+                let text = self.inline_text(&children.iter().collect::<Vec<_>>()).replace("\\\n", " ");
+                if text.is_empty() { vec![] } else { vec![format!("{} {}", "#".repeat(level), text)] }
+            }
+            "p" => {
+                let mut blocks = Vec::new();
+                self.paragraph(&children.iter().collect::<Vec<_>>(), &mut blocks);
+                blocks
+            }
+            "hr" => vec!["---".to_string()],
+            "pre" => vec![self.code_block(node)],
+            "blockquote" => {
+                let inner = self.blocks(children).join("\n\n");
+                if inner.is_empty() {
+                    return vec![];
+                }
+                vec![inner.lines().map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) }).collect::<Vec<_>>().join("\n")]
+            }
+            "ul" | "ol" => {
+                let list = self.list(node, tag == "ol");
+                if list.is_empty() { vec![] } else { vec![list] }
+            }
+            "table" => self.table(node).into_iter().collect(),
+            _ => self.blocks(children),
+        }
+    }
+
+    fn code_block(&self, pre: &Node) -> String {
+        let code = children_of(pre).iter().find(|child| tag_of(child) == "code");
+        let language = [Some(pre), code]
+            .into_iter()
+            .flatten()
+            .filter_map(|node| attribute(node, "class"))
+            .flat_map(str::split_whitespace)
+            .find_map(|class| class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")))
+            .unwrap_or_default();
+        let text = decode_entities(&pre.text_content()).into_owned();
+        let text = text.strip_prefix('\n').unwrap_or(&text);
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let fence_char = self.options.fence.chars().next().unwrap_or('`');
+        let longest = text.split(|c| c != fence_char).map(str::len).max().unwrap_or(0);
+        let fence = if longest >= self.options.fence.len() { fence_char.to_string().repeat(longest + 1) } else { self.options.fence.to_string() };
+        format!("{}{}\n{}\n{}", fence, language, text, fence)
+    }
+
+    fn list(&self, list: &Node, ordered: bool) -> String {
+        let start = attribute(list, "start").and_then(|start| start.trim().parse::<usize>().ok()).unwrap_or(1);
+        let mut items = Vec::new();
+        let mut any_loose = false;
+        for (number, item) in (start..).zip(children_of(list).iter().filter(|child| tag_of(child) == "li")) {
+            let marker = if ordered { format!("{}. ", number) } else { format!("{} ", self.options.bullet) };
+            // items holding paragraphs are loose, with blank lines between their blocks
+            let loose = children_of(item).iter().any(|child| tag_of(child) == "p");
+            any_loose |= loose;
+            let content = self.blocks(children_of(item)).join(if loose { "\n\n" } else { "\n" });
+            let indent = " ".repeat(marker.len());
+            let mut lines = content.lines();
+            let mut out = format!("{}{}", marker, lines.next().unwrap_or_default()).trim_end().to_string();
+            for line in lines {
+                out.push('\n');
+                if !line.is_empty() {
+                    out.push_str(&indent);
+                    out.push_str(line);
+                }
+            }
+            items.push(out);
+        }
+        items.join(if any_loose { "\n\n" } else { "\n" })
+    }
+
+    // a GFM table; the first row is the header when there's no real one. GFM
+    // has no spans, so a cell's text goes in the slot it starts at and the
+    // other slots it covers are left empty
+    fn table(&self, table: &Node) -> Option<String> {
+        let layout = layout(table);
+        let texts: Vec<String> = layout.cells.iter().map(|cell| self.inline_text(&children_of(cell.node).iter().collect::<Vec<_>>()).replace("\\\n", " ")).collect();
+        let mut rows: Vec<Vec<&str>> = layout
+            .grid
+            .iter()
+            .enumerate()
+            .map(|(r, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(column, slot)| match slot {
+                        Some(index) if layout.cells[*index].row == r && layout.cells[*index].column == column => texts[*index].as_str(),
+                        _ => "",
+                    })
+                    .collect()
+            })
+            .collect();
+        let width = rows.first().map(Vec::len).filter(|&width| width > 0)?;
+        // only the first header row fits in the header, the rest go to the body
+        let header = rows.remove(0);
+        let line = |cells: &[&str]| format!("| {} |", cells.join(" | "));
+        let mut out = vec![line(&header), format!("|{}", " --- |".repeat(width))];
+        out.extend(rows.iter().map(|cells| line(cells)));
+        Some(out.join("\n"))
+    }
+
+    // inline content with whitespace collapsed and the ends trimmed
+    fn inline_text(&self, nodes: &[&Node]) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            self.inline(node, &mut out);
+        }
+        // no spaces around hard breaks
+        let out = out.split("\\\n").map(str::trim).filter(|part| !part.is_empty()).collect::<Vec<_>>().join("\\\n");
+        out.trim().to_string()
+    }
+
+    fn push_text(&self, text: &str, out: &mut String) {
+        let decoded = decode_entities(text);
+        let mut collapsed = String::new();
+        let mut last_space = out.is_empty() || out.ends_with([' ', '\n']);
+        for c in decoded.chars() {
+            if c.is_whitespace() {
+                if !last_space {
+                    collapsed.push(' ');
+                }
+                last_space = true;
+            } else {
+                collapsed.push(c);
+                last_space = false;
+            }
+        }
+        escape_inline(&collapsed, out);
+    }
+
+    // wrap the inline rendering of `node` in a delimiter, keeping spaces outside it
+    fn wrapped(&self, node: &Node, delimiter: &str, out: &mut String) {
+        let mut inner = String::new();
+        for child in children_of(node) {
+            self.inline(child, &mut inner);
+        }
+        let trimmed = inner.trim();
+        if trimmed.is_empty() {
+            out.push_str(&inner);
+            return;
+        }
+        if inner.starts_with(' ') && !out.ends_with(' ') {
+            out.push(' ');
+        }
+        out.push_str(delimiter);
+        out.push_str(trimmed);
+        out.push_str(delimiter);
+        if inner.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+
+    fn inline(&self, node: &Node, out: &mut String) {
+        if let Node::Text(text) = node {
+            self.push_text(text, out);
+            return;
+        }
+        if is_skipped(node) {
+            return;
+        }
+        match tag_of(node).as_str() {
+            "em" | "i" | "cite" | "dfn" | "var" => self.wrapped(node, &self.options.emphasis.to_string(), out),
+            "strong" | "b" => self.wrapped(node, self.options.strong, out),
+            "del" | "s" | "strike" => self.wrapped(node, "~~", out),
+            "code" | "kbd" | "samp" | "tt" => {
+                let code = decode_entities(&node.text_content()).split_whitespace().collect::<Vec<_>>().join(" ");
+                if !code.is_empty() {
+                    out.push_str(&code_span(&code));
+                }
+            }
+            "br" => out.push_str("\\\n"),
+            "img" => {
+                let Some(src) = attribute(node, "src").filter(|src| !src.trim().is_empty()) else { return };
+                let mut alt = String::new();
+                escape_inline(&decode_entities(attribute(node, "alt").unwrap_or_default()), &mut alt);
+                out.push_str(&format!("![{}]({}{})", alt.trim(), self.url(src), self.title_suffix(node)));
+            }
+            "a" => {
+                let mut text = String::new();
+                for child in children_of(node) {
+                    self.inline(child, &mut text);
+                }
+                let href = attribute(node, "href").map(str::trim).filter(|href| !href.is_empty() && !href.to_ascii_lowercase().starts_with("javascript:"));
+                match href {
+                    Some(href) if !text.trim().is_empty() => {
+                        if text.starts_with(' ') && !out.ends_with(' ') && !out.is_empty() {
+                            out.push(' ');
+                        }
+                        out.push_str(&format!("[{}]({}{})", text.trim(), self.url(href), self.title_suffix(node)));
+                        if text.ends_with(' ') {
+                            out.push(' ');
+                        }
+                    }
+                    _ => out.push_str(&text),
+                }
+            }
+            _ => {
+                // block elements met inside inline content, like a <div> in a <span>,
+                // are kept apart from their neighbours by a space
+                let spaced = is_block(node);
+                if spaced && !out.is_empty() && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+                for child in children_of(node) {
+                    self.inline(child, out);
+                }
+                if spaced && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::markdown::*;
+    use crate::html;
+    use crate::tokenizer::tokenize;
+    use crate::parser::graph_creator;
+
+    // tests/fixtures/markdown holds HTML inputs, each next to its expected .md
+    #[test]
+    fn test_golden_files() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/markdown");
+        let mut inputs: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).filter(|path| path.extension().is_some_and(|extension| extension == "html")).collect();
+        inputs.sort();
+        assert!(!inputs.is_empty(), "no golden files");
+        let options = MarkdownOptions { base_url: Some(Url::parse("https://example.com/docs/").unwrap()), ..MarkdownOptions::default() };
+        for input in inputs {
+            let node = graph_creator(&mut tokenize(&fs::read_to_string(&input).unwrap()));
+            let expected = fs::read_to_string(input.with_extension("md")).unwrap();
+            assert_eq!(to_markdown(&node, &options), expected, "golden file {}", input.display());
+        }
+    }
+
+    #[test]
+    fn test_escaping_and_options() {
+        let node = html! {
+            div {
+                p { "1. not a list, *not* emphasis and a_b [link]" }
+                p { "# not a heading" }
+                p { code { "a `tick`" } " " em { "em" } " " strong { "strong" } }
+            }
+        };
+        let options = MarkdownOptions { emphasis: '*', strong: "__", ..MarkdownOptions::default() };
+        assert_eq!(
+            to_markdown(&node, &options),
+            "1\\. not a list, \\*not\\* emphasis and a\\_b \\[link\\]\n\n\\# not a heading\n\n`` a `tick` `` *em* __strong__\n"
+        );
+    }
+
+    #[test]
+    fn test_entities_in_urls_are_decoded() {
+        let node = html! { p { a(href = "list?q=rust&amp;page=2") { "next" } img(src = "a.png?w=1&amp;h=2", alt = "x") } };
+        let options = MarkdownOptions { base_url: Some(Url::parse("https://example.com/").unwrap()), ..MarkdownOptions::default() };
+        assert_eq!(to_markdown(&node, &options), "[next](https://example.com/list?q=rust&page=2)![x](https://example.com/a.png?w=1&h=2)\n");
+    }
+}
@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+use reqwest::Url;
+use crate::Node;
+use crate::document::Document;
+use crate::json::{parse_json_lenient, JsonValue};
+use crate::traversal::pre_order;
+use crate::entities::decode_entities;
+use crate::util::{attribute, children_of, collapsed_text, decoded_attribute, is_tag, resolve_or_raw, tag_of};
+
+// The structured data a page carries about itself, in one model:
+//
+// - JSON-LD: every <script type="application/ld+json"> block, parsed leniently
+//   (see json.rs). Blocks that still don't parse are reported in `errors`.
+// - Microdata: the top-level itemscope items, following the HTML spec's rules for
+//   property values and itemref. An item referencing itself, directly or through
+//   itemref, gets the property left out rather than looping.
+// - RDFa Lite: vocab, typeof, property, resource and prefix, flattened into
+//   triples. Subjects without a resource are blank nodes (_:b0, _:b1, ...).
+// - OpenGraph (og:, plus the article:, book:, profile:, product:, music:, video:
+//   and fb: namespaces) and Twitter Card <meta> tags, in document order.
+//
+// URLs in microdata values and RDFa resources are resolved against the base when
+// there is one, see metadata_from. Values come out with their entities decoded,
+// so content="Tom &amp; Jerry" reads "Tom & Jerry".
+
+const OPENGRAPH_PREFIXES: [&str; 8] = ["og:", "article:", "book:", "profile:", "product:", "music:", "video:", "fb:"];
+
+// RDFa's initial context, the prefixes usable without declaring them
+const RDFA_PREFIXES: [(&str, &str); 8] = [
+    ("og", "http://ogp.me/ns#"),
+    ("schema", "http://schema.org/"),
+    ("dc", "http://purl.org/dc/terms/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("xhv", "http://www.w3.org/1999/xhtml/vocab#"),
+];
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub json_ld: Vec<JsonValue>,
+    pub microdata: Vec<MicrodataItem>,
+    pub rdfa: Vec<Triple>,
+    // (property, content) pairs; properties like og:image may repeat
+    pub opengraph: Vec<(String, String)>,
+    pub twitter: Vec<(String, String)>,
+    // JSON-LD blocks that couldn't be parsed, like "JSON-LD block 2: expected ':' at position 14"
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MicrodataItem {
+    // the itemtype URLs
+    pub types: Vec<String>,
+    // the itemid
+    pub id: Option<String>,
+    // (name, value) in tree order; an element with several itemprop names adds one entry per name
+    pub properties: Vec<(String, MicrodataValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MicrodataValue {
+    Text(String),
+    Item(MicrodataItem),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    pub subject: String,
+    pub predicate: String,
+    pub object: RdfObject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdfObject {
+    Iri(String),
+    Literal(String),
+}
+
+impl Metadata {
+    // the first content of an OpenGraph property, e.g. opengraph("og:title")
+    pub fn opengraph(&self, property: &str) -> Option<&str> {
+        first_of(&self.opengraph, property)
+    }
+
+    pub fn twitter(&self, name: &str) -> Option<&str> {
+        first_of(&self.twitter, name)
+    }
+
+    // JSON-LD objects whose @type is (or includes) `type_name`, looking inside
+    // top-level arrays and @graph
+    pub fn json_ld_of_type(&self, type_name: &str) -> Vec<&JsonValue> {
+        let mut found = Vec::new();
+        let mut pending: Vec<&JsonValue> = self.json_ld.iter().rev().collect();
+        while let Some(value) = pending.pop() {
+            match value {
+                JsonValue::Array(values) => pending.extend(values.iter().rev()),
+                JsonValue::Object(_) => {
+                    let types = match value.get("@type") {
+                        Some(JsonValue::String(name)) => vec![name.as_str()],
+                        Some(JsonValue::Array(names)) => names.iter().filter_map(JsonValue::as_str).collect(),
+                        _ => Vec::new(),
+                    };
+                    if types.contains(&type_name) {
+                        found.push(value);
+                    }
+                    if let Some(graph) = value.get("@graph") {
+                        pending.push(graph);
+                    }
+                }
+                _ => {}
+            }
+        }
+        found
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.json_ld.is_empty() && self.microdata.is_empty() && self.rdfa.is_empty() && self.opengraph.is_empty() && self.twitter.is_empty()
+    }
+}
+
+impl MicrodataItem {
+    // the first value of a property
+    pub fn get(&self, name: &str) -> Option<&MicrodataValue> {
+        self.properties.iter().find(|(property, _)| property == name).map(|(_, value)| value)
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&MicrodataValue> {
+        self.properties.iter().filter(|(property, _)| property == name).map(|(_, value)| value).collect()
+    }
+}
+
+impl MicrodataValue {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            MicrodataValue::Text(text) => Some(text),
+            MicrodataValue::Item(_) => None,
+        }
+    }
+
+    pub fn as_item(&self) -> Option<&MicrodataItem> {
+        match self {
+            MicrodataValue::Item(item) => Some(item),
+            MicrodataValue::Text(_) => None,
+        }
+    }
+}
+
+fn first_of<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value.as_str())
+}
+
+fn element_children(node: &Node) -> impl Iterator<Item = &Node> {
+    children_of(node).iter().filter(|child| matches!(child, Node::Element { .. }))
+}
+
+pub fn metadata(document: &Document) -> Metadata {
+    metadata_from(document.root(), document.base_url().as_ref())
+}
+
+// metadata under any node, with URLs resolved against `base`
+pub fn metadata_from(root_node: &Node, base: Option<&Url>) -> Metadata {
+    let mut metadata = Metadata::default();
+    let mut ids: HashMap<&str, &Node> = HashMap::new();
+    let mut order: HashMap<*const Node, usize> = HashMap::new();
+
+    for (node, _) in pre_order(root_node) {
+        order.insert(node as *const Node, order.len());
+        let Node::Element { tag_name, attributes, .. } = node else { continue };
+        if let Some(id) = attributes.get("id") {
+            ids.entry(id.as_str()).or_insert(node);
+        }
+        if tag_name.eq_ignore_ascii_case("script") && attributes.get("type").is_some_and(|kind| is_json_ld(kind)) {
+            let block = metadata.json_ld.len() + metadata.errors.len() + 1;
+            match parse_json_lenient(&node.text_content()) {
+                Ok(value) => metadata.json_ld.push(value),
+                Err(error) => metadata.errors.push(format!("JSON-LD block {}: {}", block, error)),
+            }
+        }
+        if tag_name.eq_ignore_ascii_case("meta") && let Some(content) = attributes.get("content") {
+            for key in attributes.get("property").into_iter().chain(attributes.get("name")).flat_map(|keys| keys.split_whitespace()) {
+                let key = key.to_ascii_lowercase();
+                let content = decode_entities(content).trim().to_string();
+                if key.starts_with("twitter:") {
+                    metadata.twitter.push((key, content));
+                } else if OPENGRAPH_PREFIXES.iter().any(|prefix| key.starts_with(prefix)) {
+                    metadata.opengraph.push((key, content));
+                }
+            }
+        }
+    }
+
+    let microdata = Microdata { ids, order, base };
+    for (node, _) in pre_order(root_node) {
+        if attribute(node, "itemscope").is_some() && attribute(node, "itemprop").is_none() {
+            metadata.microdata.push(microdata.item(node, &mut Vec::new()));
+        }
+    }
+
+    let mut rdfa = Rdfa { base, triples: Vec::new(), blank_nodes: 0 };
+    let context = RdfaContext {
+        vocab: None,
+        prefixes: RDFA_PREFIXES.iter().map(|(prefix, iri)| (prefix.to_string(), iri.to_string())).collect(),
+        subject: base.map(Url::to_string).unwrap_or_default(),
+    };
+    rdfa.walk(root_node, &context);
+    metadata.rdfa = rdfa.triples;
+
+    metadata
+}
+
+fn is_json_ld(kind: &str) -> bool {
+    kind.split(';').next().is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/ld+json"))
+}
+
+struct Microdata<'a> {
+    ids: HashMap<&'a str, &'a Node>,
+    // document order, since itemref can point anywhere in the page
+    order: HashMap<*const Node, usize>,
+    base: Option<&'a Url>,
+}
+
+impl<'a> Microdata<'a> {
+    // `open` holds the items being built further up, to catch cycles
+    fn item(&self, node: &'a Node, open: &mut Vec<*const Node>) -> MicrodataItem {
+        open.push(node as *const Node);
+        let mut item = MicrodataItem {
+            types: attribute(node, "itemtype").map(|types| types.split_whitespace().map(str::to_string).collect()).unwrap_or_default(),
+            id: attribute(node, "itemid").map(|id| resolve_or_raw(self.base, id)),
+            properties: Vec::new(),
+        };
+        for property in self.property_elements(node) {
+            let value = if attribute(property, "itemscope").is_some() {
+                if open.contains(&(property as *const Node)) {
+                    continue;
+                }
+                MicrodataValue::Item(self.item(property, open))
+            } else {
+                MicrodataValue::Text(self.value(property))
+            };
+            for name in attribute(property, "itemprop").unwrap_or_default().split_whitespace() {
+                item.properties.push((name.to_string(), value.clone()));
+            }
+        }
+        open.pop();
+        item
+    }
+
+    // the spec's "crawl the properties": the item's descendants plus the elements
+    // named in itemref, without going into nested items
+    fn property_elements(&self, item: &'a Node) -> Vec<&'a Node> {
+        let mut pending: Vec<&Node> = element_children(item).collect();
+        for id in attribute(item, "itemref").unwrap_or_default().split_whitespace() {
+            if let Some(&referenced) = self.ids.get(id) {
+                pending.push(referenced);
+            }
+        }
+        let mut found: Vec<&Node> = Vec::new();
+        while let Some(node) = pending.pop() {
+            if std::ptr::eq(node, item) || found.iter().any(|seen| std::ptr::eq(*seen, node)) {
+                continue;
+            }
+            if attribute(node, "itemprop").is_some() {
+                found.push(node);
+            }
+            if attribute(node, "itemscope").is_none() {
+                pending.extend(element_children(node));
+            }
+        }
+        found.sort_by_key(|node| self.order.get(&(*node as *const Node)).copied().unwrap_or(usize::MAX));
+        found
+    }
+
+    fn value(&self, node: &Node) -> String {
+        let url_attribute = match tag_of(node).as_str() {
+            "meta" => return decoded_attribute(node, "content").unwrap_or_default().into_owned(),
+            "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => Some("src"),
+            "a" | "area" | "link" => Some("href"),
+            "object" => Some("data"),
+            "data" | "meter" => return decoded_attribute(node, "value").unwrap_or_default().into_owned(),
+            "time" if attribute(node, "datetime").is_some() => return decoded_attribute(node, "datetime").unwrap_or_default().into_owned(),
+            _ => None,
+        };
+        match url_attribute {
+            Some(name) => attribute(node, name).map(|url| resolve_or_raw(self.base, url)).unwrap_or_default(),
+            None => collapsed_text(node),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RdfaContext {
+    vocab: Option<String>,
+    prefixes: HashMap<String, String>,
+    // the subject properties on this element and below are about
+    subject: String,
+}
+
+struct Rdfa<'a> {
+    base: Option<&'a Url>,
+    triples: Vec<Triple>,
+    blank_nodes: usize,
+}
+
+impl Rdfa<'_> {
+    // a term, CURIE or absolute IRI to an IRI; terms need a vocab
+    fn expand(&self, context: &RdfaContext, name: &str) -> Option<String> {
+        if let Some((prefix, local)) = name.split_once(':') {
+            if let Some(iri) = context.prefixes.get(&prefix.to_ascii_lowercase()) {
+                return Some(format!("{}{}", iri, local));
+            }
+            // an undeclared prefix makes it an absolute IRI, if it is one
+            return Url::parse(name).ok().map(|_| name.to_string());
+        }
+        context.vocab.as_ref().map(|vocab| format!("{}{}", vocab, name))
+    }
+
+    fn walk(&mut self, node: &Node, parent: &RdfaContext) {
+        let Node::Element { children, .. } = node else { return };
+        let mut context = parent.clone();
+        if let Some(vocab) = attribute(node, "vocab") {
+            context.vocab = if vocab.trim().is_empty() { None } else { Some(resolve_or_raw(self.base, vocab)) };
+        }
+        if let Some(prefixes) = attribute(node, "prefix") {
+            let tokens: Vec<&str> = prefixes.split_whitespace().collect();
+            for pair in tokens.chunks(2) {
+                if let [prefix, iri] = pair && let Some(prefix) = prefix.strip_suffix(':') {
+                    context.prefixes.insert(prefix.to_ascii_lowercase(), iri.to_string());
+                }
+            }
+        }
+
+        let resource = attribute(node, "resource").map(|resource| resolve_or_raw(self.base, resource));
+        let properties: Vec<String> = attribute(node, "property")
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|name| self.expand(&context, name))
+            .collect();
+
+        if let Some(types) = attribute(node, "typeof") {
+            // a new subject: the resource, or a fresh blank node
+            let subject = resource.unwrap_or_else(|| {
+                self.blank_nodes += 1;
+                format!("_:b{}", self.blank_nodes - 1)
+            });
+            for predicate in properties {
+                self.push(&parent.subject, predicate, RdfObject::Iri(subject.clone()));
+            }
+            for kind in types.split_whitespace().filter_map(|name| self.expand(&context, name)).collect::<Vec<_>>() {
+                self.push(&subject, RDF_TYPE.to_string(), RdfObject::Iri(kind));
+            }
+            context.subject = subject;
+        } else if !properties.is_empty() {
+            let object = match resource.or_else(|| property_link(node).map(|url| resolve_or_raw(self.base, url))) {
+                Some(iri) => RdfObject::Iri(iri),
+                None => RdfObject::Literal(property_literal(node)),
+            };
+            for predicate in properties {
+                self.push(&parent.subject, predicate, object.clone());
+            }
+        } else if let Some(resource) = resource {
+            context.subject = resource;
+        }
+
+        for child in children {
+            self.walk(child, &context);
+        }
+    }
+
+    fn push(&mut self, subject: &str, predicate: String, object: RdfObject) {
+        self.triples.push(Triple { subject: subject.to_string(), predicate, object });
+    }
+}
+
+// href or src make the object an IRI unless there is content
+fn property_link(node: &Node) -> Option<&str> {
+    if attribute(node, "content").is_some() {
+        return None;
+    }
+    attribute(node, "href").or_else(|| attribute(node, "src"))
+}
+
+fn property_literal(node: &Node) -> String {
+    attribute(node, "content")
+        .or_else(|| if is_tag(node, "time") { attribute(node, "datetime") } else { None })
+        .map(|literal| decode_entities(literal).into_owned())
+        .unwrap_or_else(|| collapsed_text(node))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::*;
+    use crate::tokenizer::tokenize;
+    use crate::parser::graph_creator;
+
+    fn parse(html: &str) -> Node {
+        graph_creator(&mut tokenize(html))
+    }
+
+    #[test]
+    fn test_json_ld_opengraph_and_twitter() {
+        let page = parse(r#"<html><head>
+            <meta property="og:title" content=" Widget ">
+            <meta property="og:image" content="https://example.com/1.png">
+            <meta property="og:image" content="https://example.com/2.png">
+            <meta property="product:price:amount" content="9.50">
+            <meta name="twitter:card" content="summary">
+            <meta name="description" content="ignored">
+            <script type="application/ld+json">
+            <!--
+            {"@context": "https://schema.org", "@graph": [
+                {"@type": "Product", "name": "Widget", "offers": {"@type": "Offer", "price": "9.50",},},
+                {"@type": ["Thing", "Organization"], "name": "ACME"},
+            ]}
+            -->
+            </script>
+            <script type="application/ld+json; charset=utf-8">{"@type": "Product" "name": "broken"}</script>
+            <script>var x = "<p>not data</p>";</script>
+        </head><body></body></html>"#);
+        let metadata = metadata_from(&page, None);
+        assert_eq!(metadata.opengraph("og:title"), Some("Widget"));
+        assert_eq!(metadata.opengraph.iter().filter(|(key, _)| key == "og:image").count(), 2);
+        assert_eq!(metadata.opengraph("product:price:amount"), Some("9.50"));
+        assert_eq!(metadata.twitter("twitter:card"), Some("summary"));
+        assert_eq!(metadata.json_ld.len(), 1);
+        assert_eq!(metadata.errors, vec!["JSON-LD block 2: expected ',' or '}' at position 20"]);
+        let products = metadata.json_ld_of_type("Product");
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].get("offers").and_then(|offer| offer.get("price")).and_then(JsonValue::as_str), Some("9.50"));
+        assert_eq!(metadata.json_ld_of_type("Organization")[0].get("name").and_then(JsonValue::as_str), Some("ACME"));
+    }
+
+    #[test]
+    fn test_microdata_items_and_itemref() {
+        let page = parse(r#"<body>
+            <div itemscope itemtype="https://schema.org/Product" itemref="brand extra">
+                <h1 itemprop="name">Widget   Deluxe</h1>
+                <img itemprop="image" src="/w.png">
+                <div itemprop="offers" itemscope itemtype="https://schema.org/Offer">
+                    <meta itemprop="priceCurrency" content="USD">
+                    <data itemprop="price" value="9.5">$9.50</data>
+                </div>
+                <time itemprop="releaseDate" datetime="2024-01-02">Jan 2</time>
+            </div>
+            <p id="brand" itemprop="brand manufacturer">ACME</p>
+            <div id="extra"><span itemprop="color">red</span></div>
+            <div itemscope id="loop" itemref="loop-child"></div>
+            <div id="loop-child" itemprop="self" itemscope itemref="loop-child"></div>
+        </body>"#);
+        let base = Url::parse("https://example.com/shop/").unwrap();
+        let metadata = metadata_from(&page, Some(&base));
+        assert_eq!(metadata.microdata.len(), 2);
+        let product = &metadata.microdata[0];
+        assert_eq!(product.types, vec!["https://schema.org/Product"]);
+        let names: Vec<&str> = product.properties.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["name", "image", "offers", "releaseDate", "brand", "manufacturer", "color"]);
+        assert_eq!(product.get("name").and_then(MicrodataValue::as_text), Some("Widget Deluxe"));
+        assert_eq!(product.get("image").and_then(MicrodataValue::as_text), Some("https://example.com/w.png"));
+        assert_eq!(product.get("releaseDate").and_then(MicrodataValue::as_text), Some("2024-01-02"));
+        assert_eq!(product.get("manufacturer").and_then(MicrodataValue::as_text), Some("ACME"));
+        let offer = product.get("offers").and_then(MicrodataValue::as_item).unwrap();
+        assert_eq!(offer.get("price").and_then(MicrodataValue::as_text), Some("9.5"));
+        assert_eq!(offer.get("priceCurrency").and_then(MicrodataValue::as_text), Some("USD"));
+
+        // the referenced item refers to itself: it's included once, without looping
+        let looped = &metadata.microdata[1];
+        assert_eq!(looped.properties.len(), 1);
+        assert!(looped.get("self").and_then(MicrodataValue::as_item).unwrap().properties.is_empty());
+    }
+
+    #[test]
+    fn test_rdfa_lite_triples() {
+        let page = parse(r#"<body vocab="https://schema.org/" prefix="ex: http://example.org/ns#">
+            <div typeof="Person" resource="/people/ana">
+                <span property="name">Ana</span>
+                <a property="url" href="https://ana.example">site</a>
+                <span property="ex:mood" content="happy">:)</span>
+                <div property="address" typeof="PostalAddress">
+                    <span property="addressLocality">Lisbon</span>
+                </div>
+            </div>
+            <span property="http://purl.org/dc/terms/creator">ana</span>
+        </body>"#);
+        let base = Url::parse("https://example.com/page").unwrap();
+        let triples = metadata_from(&page, Some(&base)).rdfa;
+        let iri = |value: &str| RdfObject::Iri(value.to_string());
+        let literal = |value: &str| RdfObject::Literal(value.to_string());
+        let summary: Vec<(&str, &str, RdfObject)> = triples.iter().map(|t| (t.subject.as_str(), t.predicate.as_str(), t.object.clone())).collect();
+        assert_eq!(summary, vec![
+            ("https://example.com/people/ana", RDF_TYPE, iri("https://schema.org/Person")),
+            ("https://example.com/people/ana", "https://schema.org/name", literal("Ana")),
+            ("https://example.com/people/ana", "https://schema.org/url", iri("https://ana.example/")),
+            ("https://example.com/people/ana", "http://example.org/ns#mood", literal("happy")),
+            ("https://example.com/people/ana", "https://schema.org/address", iri("_:b0")),
+            ("_:b0", RDF_TYPE, iri("https://schema.org/PostalAddress")),
+            ("_:b0", "https://schema.org/addressLocality", literal("Lisbon")),
+            ("https://example.com/page", "http://purl.org/dc/terms/creator", literal("ana")),
+        ]);
+    }
+
+    #[test]
+    fn test_entities_are_decoded() {
+        let page = parse(r#"<html><head>
+            <meta property="og:title" content="Tom &amp; Jerry &#8211; Live">
+            <meta name="twitter:title" content="&quot;Live&quot;">
+        </head><body>
+            <div itemscope><span itemprop="name">Fish &amp; Chips</span><meta itemprop="brand" content="A&amp;B"><a itemprop="url" href="/p?id=1&amp;v=2">x</a></div>
+            <div vocab="https://schema.org/" typeof="Thing"><span property="name" content="R&amp;D">x</span><span property="alternateName">Q&amp;A</span></div>
+        </body></html>"#);
+        let metadata = metadata_from(&page, Some(&Url::parse("https://example.com/").unwrap()));
+        assert_eq!(metadata.opengraph("og:title"), Some("Tom & Jerry – Live"));
+        assert_eq!(metadata.twitter("twitter:title"), Some("\"Live\""));
+        let item = &metadata.microdata[0];
+        assert_eq!(item.get("name").and_then(MicrodataValue::as_text), Some("Fish & Chips"));
+        assert_eq!(item.get("brand").and_then(MicrodataValue::as_text), Some("A&B"));
+        assert_eq!(item.get("url").and_then(MicrodataValue::as_text), Some("https://example.com/p?id=1&v=2"));
+        let literals: Vec<&RdfObject> = metadata.rdfa.iter().map(|triple| &triple.object).filter(|object| matches!(object, RdfObject::Literal(_))).collect();
+        let literal = |value: &str| RdfObject::Literal(value.to_string());
+        assert_eq!(literals, vec![&literal("Tom & Jerry – Live"), &literal("R&D"), &literal("Q&A")]);
+    }
+}
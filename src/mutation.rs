@@ -0,0 +1,292 @@
+use crate::Node;
+
+// Editing the tree in place. The buscador functions hand back shared references
+// into the tree, which can't be held while the tree is borrowed mutably, so every
+// edit here is addressed by a NodePath: the list of child indexes to follow from
+// the root. `path_to`/`paths_to` turn buscador matches into paths.
+
+pub type NodePath = Vec<usize>;
+
+// find where a node returned by one of the buscador functions lives in the tree.
+// nodes are compared by address, so the target has to come from this same root.
+pub fn path_to(root_node: &Node, target: &Node) -> Option<NodePath> {
+    let mut path = Vec::new();
+    if search_path(root_node, target, &mut path) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn search_path(node: &Node, target: &Node, path: &mut NodePath) -> bool {
+    if std::ptr::eq(node, target) {
+        return true;
+    }
+    if let Node::Element { children, .. } = node {
+        for (i, child) in children.iter().enumerate() {
+            path.push(i);
+            if search_path(child, target, path) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+// paths for a whole result set, in document order and without duplicates.
+// targets that aren't part of the tree are skipped.
+pub fn paths_to(root_node: &Node, targets: &[&Node]) -> Vec<NodePath> {
+    let mut paths: Vec<NodePath> = targets.iter().filter_map(|target| path_to(root_node, target)).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+pub fn node_at<'a>(root_node: &'a Node, path: &[usize]) -> Option<&'a Node> {
+    let mut node = root_node;
+    for &i in path {
+        match node {
+            Node::Element { children, .. } => node = children.get(i)?,
+            Node::Text(_) => return None,
+        }
+    }
+    Some(node)
+}
+
+pub fn node_at_mut<'a>(root_node: &'a mut Node, path: &[usize]) -> Option<&'a mut Node> {
+    let mut node = root_node;
+    for &i in path {
+        match node {
+            Node::Element { children, .. } => node = children.get_mut(i)?,
+            Node::Text(_) => return None,
+        }
+    }
+    Some(node)
+}
+
+// the children of the parent of `path` and the index of `path` inside them.
+// the root has no parent, so it can't be removed, replaced or unwrapped.
+fn siblings_mut<'a>(root_node: &'a mut Node, path: &[usize]) -> Option<(&'a mut Vec<Node>, usize)> {
+    let (&index, parent_path) = path.split_last()?;
+    match node_at_mut(root_node, parent_path)? {
+        Node::Element { children, .. } if index < children.len() => Some((children, index)),
+        _ => None,
+    }
+}
+
+// drop paths that sit inside another path of the list, since editing the
+// ancestor already takes care of them.
+fn outermost(paths: &[NodePath]) -> Vec<NodePath> {
+    let mut sorted = paths.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    let mut kept: Vec<NodePath> = Vec::new();
+    for path in sorted {
+        if !kept.iter().any(|ancestor| path.starts_with(ancestor)) {
+            kept.push(path);
+        }
+    }
+    kept
+}
+
+pub fn remove_node(root_node: &mut Node, path: &[usize]) -> Option<Node> {
+    let (siblings, index) = siblings_mut(root_node, path)?;
+    Some(siblings.remove(index))
+}
+
+// remove several nodes at once. the removals run from the last path to the first
+// so that earlier paths stay valid while the tree shrinks.
+pub fn remove_nodes(root_node: &mut Node, paths: &[NodePath]) -> Vec<Node> {
+    let mut removed: Vec<Node> = outermost(paths)
+        .iter()
+        .rev()
+        .filter_map(|path| remove_node(root_node, path))
+        .collect();
+    removed.reverse();
+    removed
+}
+
+// remove everything a buscador query finds, e.g.
+// remove_matches(&mut root, |node| find_by_tag_name("script", node))
+pub fn remove_matches<F>(root_node: &mut Node, finder: F) -> Vec<Node>
+where
+    F: for<'a> Fn(&'a Node) -> Vec<&'a Node>,
+{
+    let paths = paths_to(root_node, &finder(root_node));
+    remove_nodes(root_node, &paths)
+}
+
+// put `replacement` where `path` was and hand back the old node.
+pub fn replace_node(root_node: &mut Node, path: &[usize], replacement: Node) -> Option<Node> {
+    let (siblings, index) = siblings_mut(root_node, path)?;
+    Some(std::mem::replace(&mut siblings[index], replacement))
+}
+
+pub fn replace_with_text(root_node: &mut Node, path: &[usize], text: &str) -> Option<Node> {
+    replace_node(root_node, path, Node::Text(text.to_string()))
+}
+
+// replace an element with its own children, e.g. <span><b>x</b></span> -> <b>x</b>.
+// the element is returned without its children. text nodes can't be unwrapped.
+pub fn unwrap_node(root_node: &mut Node, path: &[usize]) -> Option<Node> {
+    let (siblings, index) = siblings_mut(root_node, path)?;
+    if let Node::Text(_) = siblings[index] {
+        return None;
+    }
+    let mut element = siblings.remove(index);
+    if let Node::Element { children, .. } = &mut element {
+        let moved = std::mem::take(children);
+        siblings.splice(index..index, moved);
+    }
+    Some(element)
+}
+
+// move the nodes at `paths` into `wrapper`, which takes the place of the first of
+// them. all the paths must share the same parent. the wrapped nodes are appended
+// after any children the wrapper already has, in document order. returns the path
+// of the wrapper.
+pub fn wrap_nodes(root_node: &mut Node, paths: &[NodePath], mut wrapper: Node) -> Option<NodePath> {
+    let mut paths = paths.to_vec();
+    paths.sort();
+    paths.dedup();
+    let first = paths.first()?.clone();
+    let parent_path = &first[..first.len().checked_sub(1)?];
+    if paths.iter().any(|path| path.is_empty() || &path[..path.len() - 1] != parent_path) {
+        return None;
+    }
+    let Node::Element { children: wrapper_children, .. } = &mut wrapper else {
+        return None;
+    };
+    let Some(Node::Element { children, .. }) = node_at_mut(root_node, parent_path) else {
+        return None;
+    };
+    let indexes: Vec<usize> = paths.iter().map(|path| path[path.len() - 1]).collect();
+    if indexes.iter().any(|&i| i >= children.len()) {
+        return None;
+    }
+    let mut wrapped = Vec::new();
+    for &i in indexes.iter().rev() {
+        wrapped.push(children.remove(i));
+    }
+    wrapped.reverse();
+    wrapper_children.extend(wrapped);
+    children.insert(indexes[0], wrapper);
+    Some(first)
+}
+
+pub fn wrap_node(root_node: &mut Node, path: &[usize], wrapper: Node) -> Option<NodePath> {
+    wrap_nodes(root_node, &[path.to_vec()], wrapper)
+}
+
+// set an attribute on the element at `path`, returning the previous value.
+pub fn set_attribute(root_node: &mut Node, path: &[usize], name: &str, value: &str) -> Option<String> {
+    match node_at_mut(root_node, path)? {
+        Node::Element { attributes, .. } => attributes.insert(name.to_string(), value.to_string()),
+        Node::Text(_) => None,
+    }
+}
+
+pub fn remove_attribute(root_node: &mut Node, path: &[usize], name: &str) -> Option<String> {
+    match node_at_mut(root_node, path)? {
+        Node::Element { attributes, .. } => attributes.remove(name),
+        Node::Text(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Node;
+    use crate::buscador::*;
+    use crate::mutation::*;
+    use crate::html;
+
+    fn page() -> Node {
+        html! {
+            body {
+                div(class = "ad") { "buy" }
+                p { "one " span { "two" } }
+                script { "x" }
+                p { "three" }
+            }
+        }
+    }
+
+    fn tags(node: &Node) -> Vec<String> {
+        if let Node::Element { children, .. } = node {
+            children.iter().map(|child| match child {
+                Node::Element { tag_name, .. } => tag_name.clone(),
+                Node::Text(text) => format!("#{}", text),
+            }).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn test_paths_from_buscador_matches() {
+        let root = page();
+        let found = find_by_tag_name("p", &root);
+        assert_eq!(paths_to(&root, &found), vec![vec![1], vec![3]]);
+        assert_eq!(path_to(&root, find_by_text("two", &root)[0]), Some(vec![1, 1, 0]));
+
+        let other = page();
+        assert_eq!(path_to(&other, found[0]), None);
+    }
+
+    #[test]
+    fn test_remove_matches() {
+        let mut root = page();
+        let removed = remove_matches(&mut root, |node| find_by_attribute("class", "ad", node));
+        assert_eq!(removed.len(), 1);
+        let removed = remove_matches(&mut root, |node| find_by_tag_name("script", node));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(tags(&root), vec!["p", "p"]);
+    }
+
+    #[test]
+    fn test_remove_nodes_handles_nested_and_unordered_paths() {
+        let mut root = page();
+        let removed = remove_nodes(&mut root, &[vec![3], vec![1, 1], vec![1], vec![0]]);
+        assert_eq!(removed.len(), 3);
+        assert_eq!(tags(&root), vec!["script"]);
+        assert!(remove_node(&mut root, &[]).is_none());
+    }
+
+    #[test]
+    fn test_replace_and_unwrap() {
+        let mut root = page();
+        let old = replace_with_text(&mut root, &[2], "gone");
+        assert!(matches!(old, Some(Node::Element { ref tag_name, .. }) if tag_name == "script"));
+        replace_node(&mut root, &[0], html! { hr });
+        assert_eq!(tags(&root), vec!["hr", "p", "#gone", "p"]);
+
+        let unwrapped = unwrap_node(&mut root, &[1, 1]);
+        assert!(matches!(unwrapped, Some(Node::Element { ref children, .. }) if children.is_empty()));
+        assert_eq!(tags(node_at(&root, &[1]).unwrap()), vec!["#one ", "#two"]);
+        assert!(unwrap_node(&mut root, &[2]).is_none());
+    }
+
+    #[test]
+    fn test_wrap_nodes() {
+        let mut root = page();
+        let wrapper = html! { section(id = "main") };
+        let path = wrap_nodes(&mut root, &[vec![3], vec![1]], wrapper);
+        assert_eq!(path, Some(vec![1]));
+        assert_eq!(tags(&root), vec!["div", "section", "script"]);
+        assert_eq!(tags(node_at(&root, &[1]).unwrap()), vec!["p", "p"]);
+
+        // nodes with different parents can't be wrapped together
+        assert!(wrap_nodes(&mut root, &[vec![0], vec![1, 0]], html! { div }).is_none());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut root = page();
+        assert_eq!(set_attribute(&mut root, &[0], "class", "clean"), Some("ad".to_string()));
+        assert_eq!(set_attribute(&mut root, &[0], "data-x", "1"), None);
+        assert_eq!(find_by_attribute("class", "clean", &root).len(), 1);
+        assert_eq!(remove_attribute(&mut root, &[0], "data-x"), Some("1".to_string()));
+        assert_eq!(remove_attribute(&mut root, &[0, 0], "class"), None);
+    }
+}
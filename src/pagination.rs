@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use reqwest::Url;
+use crate::Node;
+use crate::document::Document;
+use crate::links::{links_from, resolve, LinkKind};
+use crate::traversal::pre_order;
+use crate::util::{attribute, collapsed_text, decoded_attribute, tag_of};
+
+// Finding the next page of a paginated listing.
+//
+// Candidates come from several places, each with its own confidence:
+//
+//   <link rel=next>                                    1.0
+//   <a rel=next>                                       0.9
+//   a numbered pagination widget, the link after the
+//   current page                                       0.85
+//   an anchor reading "Next", "Weiter", "次へ"...       0.8
+//   an anchor that is only an arrow like "›" or "»"    0.6
+//   an anchor with "next" in its class                 0.5
+//
+// A URL found by several of them gets the combined score 1 - Π(1 - s), so
+// agreement ranks it higher; links back to the page itself are dropped.
+//
+// A pagination widget is an element whose numbered items are in increasing
+// order with exactly one current page, which is the item marked with
+// aria-current or a current/active/selected class, or else the only number that
+// isn't a link. The page before or after the current one must be linked.
+//
+// The page-number pattern is a URL template with {page} where the number goes,
+// inferred from the widget's numbered links, or else from the page URL and the
+// best candidate when they differ only in a number that goes up by one. From
+// the widget, the number may also be an offset growing by a fixed step, like
+// ?start=0, ?start=10, ?start=20 for pages 1, 2 and 3.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NextSource {
+    RelLink,
+    RelAnchor,
+    Widget,
+    Label,
+    Arrow,
+    ClassName,
+}
+
+impl NextSource {
+    pub fn confidence(&self) -> f64 {
+        match self {
+            NextSource::RelLink => 1.0,
+            NextSource::RelAnchor => 0.9,
+            NextSource::Widget => 0.85,
+            NextSource::Label => 0.8,
+            NextSource::Arrow => 0.6,
+            NextSource::ClassName => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NextCandidate {
+    pub url: Url,
+    pub score: f64,
+    // every source that pointed at this URL, best first
+    pub sources: Vec<NextSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagePattern {
+    // like "https://example.com/list?page={page}"
+    pub template: String,
+    // the number that goes in page 1's URL, and how much it grows from page to
+    // page: 1 and 1 for page numbers, 0 and 10 for offsets like ?start=20 on page 3
+    pub first: u32,
+    pub step: u32,
+}
+
+impl PagePattern {
+    // a pattern numbering pages 1, 2, 3...
+    pub fn numbered(template: String) -> PagePattern {
+        PagePattern { template, first: 1, step: 1 }
+    }
+
+    pub fn url_for(&self, page: u32) -> Option<Url> {
+        let number = (self.first as u64 + (page as u64).checked_sub(1)? * self.step as u64).to_string();
+        Url::parse(&self.template.replace("{page}", &number)).ok()
+    }
+
+    // the page number of a URL that fits the pattern
+    pub fn page_of(&self, url: &Url) -> Option<u32> {
+        let (prefix, suffix) = self.template.split_once("{page}")?;
+        let number = url.as_str().strip_prefix(prefix)?.strip_suffix(suffix)?;
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let offset = number.parse::<u32>().ok()?.checked_sub(self.first)?;
+        if self.step == 0 || offset % self.step != 0 {
+            return None;
+        }
+        (offset / self.step).checked_add(1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pagination {
+    // best first
+    pub candidates: Vec<NextCandidate>,
+    pub current_page: Option<u32>,
+    pub pattern: Option<PagePattern>,
+}
+
+impl Pagination {
+    pub fn next(&self) -> Option<&Url> {
+        self.candidates.first().map(|candidate| &candidate.url)
+    }
+}
+
+// anchor texts that mean "next page", compared after lowercasing and trimming arrows
+const NEXT_LABELS: [&str; 35] = [
+    "next", "next page", "older posts", "older entries", "weiter", "nächste", "nächste seite", "vorwärts", "suivant", "suivante", "page suivante",
+    "siguiente", "página siguiente", "successiva", "pagina successiva", "avanti", "próxima", "próximo", "próxima página", "seguinte", "volgende", "nästa", "neste",
+    "næste", "seuraava", "następna", "następna strona", "dalej", "далее", "следующая", "вперёд", "次へ", "次のページ", "下一页", "다음",
+];
+const ARROWS: [char; 9] = ['›', '»', '→', '>', '≫', '▶', '▸', '❯', '⟩'];
+const CURRENT_CLASSES: [&str; 5] = ["current", "active", "selected", "is-current", "is-active"];
+
+fn without_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+fn page_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text.is_empty() || text.len() > 6 || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn is_marked_current(node: &Node) -> bool {
+    let aria = attribute(node, "aria-current").is_some_and(|value| !value.trim().eq_ignore_ascii_case("false"));
+    aria || attribute(node, "class").is_some_and(|class| class.split_whitespace().any(|class| CURRENT_CLASSES.contains(&class.to_ascii_lowercase().as_str())))
+}
+
+fn link_url(node: &Node, base: Option<&Url>) -> Option<Url> {
+    (tag_of(node) == "a").then(|| attribute(node, "href").and_then(|href| resolve(base, href))).flatten()
+}
+
+// a numbered entry of a pagination widget
+struct Item {
+    number: u32,
+    url: Option<Url>,
+    marked: bool,
+}
+
+// a widget's current page and its numbered links
+type Widget = (u32, Vec<(u32, Url)>);
+
+// the widget these items make up, if they make one
+fn widget(items: &[Item]) -> Option<Widget> {
+    if items.len() < 2 || !items.windows(2).all(|pair| pair[0].number < pair[1].number) {
+        return None;
+    }
+    let marked: Vec<&Item> = items.iter().filter(|item| item.marked).collect();
+    let current = match marked.as_slice() {
+        [item] => item.number,
+        [] => {
+            let unlinked: Vec<&Item> = items.iter().filter(|item| item.url.is_none()).collect();
+            match unlinked.as_slice() {
+                [item] => item.number,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let linked: Vec<(u32, Url)> = items.iter().filter(|item| item.number != current).filter_map(|item| Some((item.number, item.url.clone()?))).collect();
+    // a widget links at least one neighbour of the current page
+    if !linked.iter().any(|(number, _)| *number + 1 == current || *number == current + 1) {
+        return None;
+    }
+    Some((current, linked))
+}
+
+// the anchors and widgets found in one walk over the tree
+struct Scan<'b> {
+    base: Option<&'b Url>,
+    found: Vec<(Url, NextSource)>,
+    // numbered items in document order; an element's are the ones added while
+    // its children were visited, so no subtree is read twice
+    items: Vec<Item>,
+    // the widget last in document order, which is the innermost one since
+    // ancestors holding it repeat the same numbers
+    widget: Option<(usize, Widget)>,
+    elements: usize,
+}
+
+impl Scan<'_> {
+    fn visit(&mut self, node: &Node) {
+        let Node::Element { children, .. } = node else {
+            if let Node::Text(text) = node && let Some(number) = page_number(text) {
+                self.items.push(Item { number, url: None, marked: false });
+            }
+            return;
+        };
+        let position = self.elements;
+        self.elements += 1;
+        let url = link_url(node, self.base);
+        if let Some(url) = &url && let Some(source) = label_source(node) {
+            self.found.push((url.clone(), source));
+        }
+        let (found_at, items_at) = (self.found.len(), self.items.len());
+        for child in children {
+            self.visit(child);
+        }
+        let marked = is_marked_current(node);
+        if url.is_some() || marked {
+            if let Some(number) = page_number(&node.text_content()) {
+                // a marked item may wrap the link to itself
+                let url = url.or_else(|| pre_order(node).find_map(|(inner, _)| link_url(inner, self.base)));
+                self.items.truncate(items_at);
+                self.items.push(Item { number, url, marked });
+                return;
+            }
+            // a link that isn't a number, like "Next"
+            if url.is_some() {
+                self.items.truncate(items_at);
+                return;
+            }
+        }
+        if let Some((current, linked)) = widget(&self.items[items_at..]) {
+            if let Some((_, url)) = linked.iter().find(|(number, _)| *number == current + 1) {
+                // where the element itself comes in document order
+                self.found.insert(found_at, (url.clone(), NextSource::Widget));
+            }
+            if self.widget.as_ref().is_none_or(|(last, _)| *last < position) {
+                self.widget = Some((position, (current, linked)));
+            }
+        }
+    }
+}
+
+// how an anchor's own text, aria-label, title or class says "next"
+fn label_source(node: &Node) -> Option<NextSource> {
+    let mut best = None;
+    let texts = [Some(collapsed_text(node)), decoded_attribute(node, "aria-label").map(String::from), decoded_attribute(node, "title").map(String::from)];
+    for text in texts.into_iter().flatten() {
+        let text = text.to_lowercase();
+        let trimmed = text.trim_matches(|c: char| c.is_whitespace() || ARROWS.contains(&c));
+        if NEXT_LABELS.contains(&trimmed) {
+            return Some(NextSource::Label);
+        }
+        if trimmed.is_empty() && !text.trim().is_empty() {
+            best = Some(NextSource::Arrow);
+        }
+    }
+    if best.is_none() && attribute(node, "class").is_some_and(|class| {
+        class.split_whitespace().map(str::to_ascii_lowercase).any(|class| class == "next" || class.ends_with("-next") || class.ends_with("_next") || class.starts_with("next-") || class.starts_with("next_"))
+    }) {
+        best = Some(NextSource::ClassName);
+    }
+    best
+}
+
+// the digit runs of a string, as byte ranges
+fn digit_runs(text: &str) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, b) in text.bytes().enumerate() {
+        match (b.is_ascii_digit(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                runs.push((from, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        runs.push((from, text.len()));
+    }
+    runs
+}
+
+// the first number and step putting every (page, number) pair on one line.
+// A single page only fits when its number is the page number itself
+fn fit(pairs: &[(u32, i64)]) -> Option<(u32, u32)> {
+    let &(page, number) = pairs.first()?;
+    let step = match pairs.iter().find(|(other, _)| *other != page) {
+        Some(&(other, other_number)) => {
+            let (pages, numbers) = (other as i64 - page as i64, other_number - number);
+            if numbers % pages != 0 || numbers / pages < 1 {
+                return None;
+            }
+            numbers / pages
+        }
+        None if number == page as i64 => 1,
+        None => return None,
+    };
+    let first = number - (page as i64 - 1) * step;
+    let fits = pairs.iter().all(|&(page, number)| number == first + (page as i64 - 1) * step);
+    if !fits || first < 0 {
+        return None;
+    }
+    Some((u32::try_from(first).ok()?, u32::try_from(step).ok()?))
+}
+
+// the template most of the numbered URLs agree on. The number in it may be the
+// page number or grow by a fixed step, like an offset of 10 results per page
+fn infer_pattern(samples: &[(u32, Url)]) -> Option<PagePattern> {
+    // every URL with one of its numbers taken out, with the pages and the
+    // numbers that were there, and where the number starts
+    let mut templates: HashMap<String, (Vec<(u32, i64)>, usize)> = HashMap::new();
+    for (page, url) in samples {
+        let text = url.as_str();
+        for (start, end) in digit_runs(text) {
+            // numbers too big for a page count are ids
+            let Ok(number) = text[start..end].parse::<u32>() else { continue };
+            let entry = templates.entry(format!("{}{{page}}{}", &text[..start], &text[end..])).or_insert((Vec::new(), start));
+            if !entry.0.contains(&(*page, number as i64)) {
+                entry.0.push((*page, number as i64));
+            }
+        }
+    }
+    let needed = if samples.len() == 1 { 1 } else { 2 };
+    templates
+        .into_iter()
+        .filter(|(_, (pairs, _))| pairs.len() >= needed)
+        .filter_map(|(template, (pairs, position))| {
+            let (first, step) = fit(&pairs)?;
+            Some(((pairs.len(), position), PagePattern { template, first, step }))
+        })
+        // ties go to the number further right, page numbers tend to come last
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.template.cmp(&a.1.template)))
+        .map(|(_, pattern)| pattern)
+}
+
+// the current page number and pattern when `next` is `current` with one number increased by one
+fn numeric_step(current: &Url, next: &Url) -> Option<(u32, PagePattern)> {
+    let (current, next) = (current.as_str(), next.as_str());
+    let (current_runs, next_runs) = (digit_runs(current), digit_runs(next));
+    if current_runs.len() != next_runs.len() {
+        return None;
+    }
+    let mut step = None;
+    let (mut current_at, mut next_at) = (0, 0);
+    for (&(current_start, current_end), &(next_start, next_end)) in current_runs.iter().zip(&next_runs) {
+        if current[current_at..current_start] != next[next_at..next_start] {
+            return None;
+        }
+        let (from, to) = (&current[current_start..current_end], &next[next_start..next_end]);
+        if from != to {
+            let (from_number, to_number) = (from.parse::<u32>().ok()?, to.parse::<u32>().ok()?);
+            if step.is_some() || from_number.checked_add(1) != Some(to_number) {
+                return None;
+            }
+            step = Some((from_number, current_start, current_end));
+        }
+        current_at = current_end;
+        next_at = next_end;
+    }
+    if current[current_at..] != next[next_at..] {
+        return None;
+    }
+    let (number, start, end) = step?;
+    Some((number, PagePattern::numbered(format!("{}{{page}}{}", &current[..start], &current[end..]))))
+}
+
+pub fn pagination(document: &Document) -> Pagination {
+    let base = document.base_url();
+    detect(document.root(), base.as_ref(), document.url().or(base.as_ref()))
+}
+
+// pagination under any node, with `base` used both to resolve links and as the
+// address of the page itself
+pub fn pagination_from(root_node: &Node, base: Option<&Url>) -> Pagination {
+    detect(root_node, base, base)
+}
+
+fn detect(root_node: &Node, base: Option<&Url>, page_url: Option<&Url>) -> Pagination {
+    let page_url = page_url.map(without_fragment);
+    let mut found: Vec<(Url, NextSource)> = Vec::new();
+
+    for link in links_from(root_node, base) {
+        if link.kind == LinkKind::Next {
+            found.push((link.url, if link.element == "link" { NextSource::RelLink } else { NextSource::RelAnchor }));
+        }
+    }
+
+    let mut scan = Scan { base, found, items: Vec::new(), widget: None, elements: 0 };
+    scan.visit(root_node);
+    let found = scan.found;
+    let (mut current_page, mut samples) = match scan.widget {
+        Some((_, (current, linked))) => (Some(current), linked),
+        None => (None, Vec::new()),
+    };
+
+    let mut candidates: Vec<NextCandidate> = Vec::new();
+    for (url, source) in found {
+        let url = without_fragment(&url);
+        if Some(&url) == page_url.as_ref() || !matches!(url.scheme(), "http" | "https" | "file") {
+            continue;
+        }
+        match candidates.iter_mut().find(|candidate| candidate.url == url) {
+            Some(candidate) if candidate.sources.contains(&source) => {}
+            Some(candidate) => candidate.sources.push(source),
+            None => candidates.push(NextCandidate { url, score: 0.0, sources: vec![source] }),
+        }
+    }
+    for candidate in &mut candidates {
+        candidate.sources.sort_by(|a, b| b.confidence().total_cmp(&a.confidence()));
+        candidate.score = 1.0 - candidate.sources.iter().map(|source| 1.0 - source.confidence()).product::<f64>();
+    }
+    // stable, so equal scores keep document order
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut pattern = None;
+    if let Some(current) = current_page {
+        if let Some(page_url) = &page_url {
+            samples.push((current, page_url.clone()));
+        }
+        pattern = infer_pattern(&samples);
+    }
+    if pattern.is_none() && let (Some(page_url), Some(best)) = (&page_url, candidates.first()) {
+        if let Some((number, step)) = numeric_step(page_url, &best.url) {
+            current_page = current_page.or(Some(number));
+            pattern = Some(step);
+        } else if current_page.is_none() {
+            // the first page often has no number in its URL: ?page=2 is then the next one
+            pattern = infer_pattern(&[(2, best.url.clone())]).filter(|pattern| pattern.url_for(1).is_some());
+        }
+    }
+    if current_page.is_none() && let (Some(pattern), Some(page_url)) = (&pattern, &page_url) {
+        current_page = pattern.page_of(page_url);
+    }
+    Pagination { candidates, current_page, pattern }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::pagination::*;
+
+    // the expected output of a fixture: current page, pattern (with its first
+    // number and step unless they are 1), then one candidate per line
+    fn describe(pagination: &Pagination) -> String {
+        let pattern = match &pagination.pattern {
+            Some(pattern) if (pattern.first, pattern.step) == (1, 1) => pattern.template.clone(),
+            Some(pattern) => format!("{} from {} by {}", pattern.template, pattern.first, pattern.step),
+            None => "-".to_string(),
+        };
+        let mut out = format!(
+            "current: {}\npattern: {}\n",
+            pagination.current_page.map(|page| page.to_string()).unwrap_or_else(|| "-".to_string()),
+            pattern
+        );
+        for candidate in &pagination.candidates {
+            out.push_str(&format!("{:.2} {} {:?}\n", candidate.score, candidate.url, candidate.sources));
+        }
+        out
+    }
+
+    // tests/fixtures/pagination holds pages written after the markup of a
+    // WordPress blog, an old forum, a search engine and a Bootstrap shop, each
+    // next to a .expected file; the address each one is read as is listed here
+    #[test]
+    fn test_fixture_pages() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pagination");
+        let pages = [
+            ("blog", "https://blog.example.com/page/2/"),
+            ("forum", "https://forum.example.de/thread-481-seite-4.html"),
+            ("search", "https://search.example.jp/results?q=rust&start=20"),
+            ("shop", "https://example.com/shop/list?page=2"),
+        ];
+        for (name, url) in pages {
+            let html = fs::read_to_string(directory.join(format!("{}.html", name))).unwrap();
+            let document = Document::parse(&html).with_url(Url::parse(url).unwrap());
+            let expected = fs::read_to_string(directory.join(format!("{}.expected", name))).unwrap();
+            assert_eq!(describe(&pagination(&document)), expected, "fixture {}", name);
+        }
+    }
+
+    #[test]
+    fn test_page_pattern() {
+        let pattern = PagePattern::numbered("https://example.com/list?page={page}&sort=new".to_string());
+        assert_eq!(pattern.url_for(7).unwrap().as_str(), "https://example.com/list?page=7&sort=new");
+        assert_eq!(pattern.page_of(&Url::parse("https://example.com/list?page=12&sort=new").unwrap()), Some(12));
+        assert_eq!(pattern.page_of(&Url::parse("https://example.com/list?page=&sort=new").unwrap()), None);
+
+        let offsets = PagePattern { template: "https://example.com/find?q=a&start={page}".to_string(), first: 0, step: 10 };
+        assert_eq!(offsets.url_for(3).unwrap().as_str(), "https://example.com/find?q=a&start=20");
+        assert_eq!(offsets.page_of(&Url::parse("https://example.com/find?q=a&start=0").unwrap()), Some(1));
+        assert_eq!(offsets.page_of(&Url::parse("https://example.com/find?q=a&start=25").unwrap()), None);
+        let samples: Vec<(u32, Url)> = [(2, 11), (3, 21), (5, 41)].into_iter().map(|(page, first)| (page, Url::parse(&format!("https://example.com/find?first={}&n=10", first)).unwrap())).collect();
+        assert_eq!(infer_pattern(&samples), Some(PagePattern { template: "https://example.com/find?first={page}&n=10".to_string(), first: 1, step: 10 }));
+
+        let first = Url::parse("https://example.com/list").unwrap();
+        let next = Url::parse("https://example.com/list?page=2").unwrap();
+        assert!(numeric_step(&first, &next).is_none());
+        let (number, step) = numeric_step(&next, &Url::parse("https://example.com/list?page=3").unwrap()).unwrap();
+        assert_eq!((number, step.template.as_str()), (2, "https://example.com/list?page={page}"));
+    }
+
+    #[test]
+    fn test_no_pagination() {
+        let document = Document::parse("<html><body><p>Only 3 items, <a href=\"/about\">about us</a>.</p></body></html>")
+            .with_url(Url::parse("https://example.com/").unwrap());
+        assert_eq!(pagination(&document), Pagination::default());
+    }
+}
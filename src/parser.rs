@@ -1,12 +1,43 @@
 use std::collections::{HashMap, VecDeque};
-use crate::tokenizer::Tokens;
+use crate::tokenizer::{Position, Tokens};
+use crate::mutation::NodePath;
 use crate::{Node,Attributes};
 
+// where each node of a parsed tree started in the source, keyed by NodePath
+pub type SourcePositions = HashMap<NodePath, Position>;
 
+// keeps the token positions next to the queue. the parser only pops from (and
+// pushes back to) the front, so the token just popped is always number
+// `total - tokens.len() - 1`.
+struct PositionTracker {
+    positions: Vec<Position>,
+    total: usize,
+    found: SourcePositions,
+}
+
+impl PositionTracker {
+    fn record(&mut self, tokens: &VecDeque<Tokens>, path: NodePath) {
+        if let Some(position) = self.positions.get(self.total - tokens.len() - 1) {
+            self.found.insert(path, *position);
+        }
+    }
+}
 
 // go through each token and make nodes
 
 pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
+    build_node(tokens, &mut None, &mut Vec::new())
+}
+
+// same as graph_creator, with the positions from tokenize_with_positions
+pub fn graph_creator_with_positions(tokens:&mut VecDeque<Tokens>, positions: Vec<Position>) -> (Node, SourcePositions) {
+    let total = tokens.len();
+    let mut tracker = Some(PositionTracker { positions, total, found: HashMap::new() });
+    let graph = build_node(tokens, &mut tracker, &mut Vec::new());
+    (graph, tracker.map(|tracker| tracker.found).unwrap_or_default())
+}
+
+fn build_node(tokens:&mut VecDeque<Tokens>, tracker: &mut Option<PositionTracker>, path: &mut NodePath) -> Node {
     // Placeholder implementation.
     let mut tag_name = String::new();
     let mut attributes: Attributes = HashMap::new();
@@ -21,7 +52,7 @@ pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
         Some (Tokens::ClosingTag) => {
           //print!("closing tag found\n");
           // closing tag
-          if let Some(Tokens::Identifier(name)) = tokens.pop_front() {
+          if let Some(Tokens::Identifier(_name)) = tokens.pop_front() {
             // ensure the closing tag matches the opening tag
               // consume greater than
             tokens.pop_front(); // remove '>'
@@ -42,9 +73,12 @@ pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
         },
         Some (Tokens::LessThan) => {
             // opening tag, is it a new opening tag?
-            if tag_name == "" {
+            if tag_name.is_empty() {
               // grab the tag name
               //print!("New node found and we dont have a node yet\n");
+              if let Some(tracker) = tracker {
+                tracker.record(tokens, path.clone());
+              }
               if let Some(Tokens::Identifier(name)) = tokens.pop_front() {
                 tag_name = name;
               }
@@ -54,14 +88,20 @@ pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
               //print!("New node found so we're going to recurse\n");
               // add back less than so that it can be used in the recursive call
               tokens.push_front(Tokens::LessThan);
-              children.push(graph_creator(tokens));
+              path.push(children.len());
+              children.push(build_node(tokens, tracker, path));
+              path.pop();
               continue; // continue to the next iteration
             }
         },
         Some(Tokens::Attribute(attribute_name)) => {
           //print!("attribute found: {}\n", attribute_name);
           // handle attributes
-          // e.g., class="my-class"
+          // e.g., class="my-class", or a bare `itemscope` which gets an empty value
+          if !matches!(tokens.front(), Some(Tokens::Equals)) {
+            attributes.entry(attribute_name).or_default();
+            continue;
+          }
           tokens.pop_front(); // remove '='
           if let Some(Tokens::String(value)) = tokens.pop_front() {
             //print!("attribute value found: {}\n", value);
@@ -87,6 +127,11 @@ pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
           
           // text content inside an element
           //print!("text found: {}\n", text);
+          if let Some(tracker) = tracker {
+            path.push(children.len());
+            tracker.record(tokens, path.clone());
+            path.pop();
+          }
           children.push(Node::Text(text));
           continue;
         },
@@ -97,15 +142,16 @@ pub fn graph_creator(tokens:&mut VecDeque<Tokens>) -> Node {
         } // end of tokens
       }
   }
-  return Node::Element { tag_name, attributes, children };
+  Node::Element { tag_name, attributes, children }
   // If we reach here, it means we didn't find a closing tag
 }
 
 #[cfg(test)]
 mod tests {
-  use std::collections::{HashMap, VecDeque};
+  use std::collections::VecDeque;
   use crate::Node;
   use crate::parser::*;
+  use crate::html;
   use crate::tokenizer::Tokens;
   #[test]
   fn test_parser()
@@ -268,164 +314,29 @@ mod tests {
       Tokens::GreaterThan,
       Tokens::EOF,
     ];
-      let ast = vec![
-        Node::Element {
-            tag_name: "html".to_string(),
-            attributes: {
-                let mut attrs = HashMap::new();
-                attrs.insert("lang".to_string(), "en".to_string());
-                attrs
-            },
-            children: vec![
-                Node::Element {
-                    tag_name: "head".to_string(),
-                    attributes: HashMap::new(),
-                    children: vec![
-                        Node::Element {
-                            tag_name: "meta".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("charset".to_string(), "UTF-8".to_string());
-                                attrs
-                            },
-                            children: vec![],
-                        },
-                        Node::Element {
-                            tag_name: "meta".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("name".to_string(), "viewport".to_string());
-                                attrs.insert("content".to_string(), "width=device-width, initial-scale=1.0".to_string());
-                                attrs
-                            },
-                            children: vec![],
-                        },
-                        Node::Element {
-                            tag_name: "title".to_string(),
-                            attributes: HashMap::new(),
-                            children: vec![
-                                Node::Text("Tokenizer Test Page".to_string()),
-                            ],
-                        },
-                        Node::Element {
-                            tag_name: "style".to_string(),
-                            attributes: HashMap::new(),
-                            children: vec![
-                                Node::Text(r#"body { font-family: sans-serif; margin: 20px; background-color: #f4f4f4; color: #333; } .container { max-width: 800px; margin: 0 auto; background-color: #fff; padding: 20px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1); } h1 { color: #0056b3; } p { line-height: 1.6; } code { background-color: #e0e0e0; padding: 2px 4px; border-radius: 3px; }"#.to_string()),
-                            ],
-                        },
-                    ],
-                },
-                Node::Element {
-                    tag_name: "body".to_string(),
-                    attributes: HashMap::new(),
-                    children: vec![
-                        Node::Element {
-                            tag_name: "div".to_string(),
-                            attributes: {
-                                let mut attrs = HashMap::new();
-                                attrs.insert("class".to_string(), "container".to_string());
-                                attrs
-                            },
-                            children: vec![
-                                Node::Element {
-                                    tag_name: "h1".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("This is some sample text".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("class".to_string(), "intro".to_string());
-                                        attrs
-                                    },
-                                    children: vec![
-                                        Node::Text("Welcome to this ".to_string()),
-                                        Node::Element {
-                                            tag_name: "span".to_string(),
-                                            attributes: {
-                                                let mut attrs = HashMap::new();
-                                                attrs.insert("id".to_string(), "test_span".to_string());
-                                                attrs
-                                            },
-                                            children: vec![
-                                                Node::Text("simple page".to_string()),
-                                            ],
-                                        },
-                                        Node::Text(" for tokenizer testing.".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("Here's a paragraph with a ".to_string()),
-                                        Node::Element {
-                                            tag_name: "a".to_string(),
-                                            attributes: {
-                                                let mut attrs = HashMap::new();
-                                                attrs.insert("target".to_string(), "_blank".to_string());
-                                                attrs.insert("href".to_string(), "https://example.com".to_string());
-                                                attrs.insert("data-info".to_string(), "link".to_string());
-                                                attrs
-                                            },
-                                            children: vec![
-                                                Node::Text("link".to_string()),
-                                            ],
-                                        },
-                                        Node::Text(".".to_string()),
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "img".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("src".to_string(), "https://placehold.co/150x50/cccccc/333333?text=Image".to_string());
-                                        attrs.insert("alt".to_string(), "Placeholder Image".to_string());
-                                        attrs
-                                    },
-                                    children: vec![],
-                                },
-                                Node::Element {
-                                    tag_name: "br".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![],
-                                },
-                                Node::Element {
-                                    tag_name: "div".to_string(),
-                                    attributes: {
-                                        let mut attrs = HashMap::new();
-                                        attrs.insert("id".to_string(), "dynamicContent".to_string());
-                                        attrs.insert("style".to_string(), "background-color: lightblue;".to_string());
-                                        attrs
-                                    },
-                                    children: vec![
-                                        Node::Element {
-                                            tag_name: "p".to_string(),
-                                            attributes: HashMap::new(),
-                                            children: vec![
-                                                Node::Text("Another paragraph inside a div.".to_string()),
-                                            ],
-                                        },
-                                    ],
-                                },
-                                Node::Element {
-                                    tag_name: "p".to_string(),
-                                    attributes: HashMap::new(),
-                                    children: vec![
-                                        Node::Text("This is the final content.".to_string()),
-                                    ],
-                                },
-                            ],
-                        },
-                    ],
-                },
-            ],
-        },
-    ];
+    let ast = html! {
+      html(lang = "en") {
+        head {
+          meta(charset = "UTF-8")
+          meta(name = "viewport", content = "width=device-width, initial-scale=1.0")
+          title { "Tokenizer Test Page" }
+          style { r#"body { font-family: sans-serif; margin: 20px; background-color: #f4f4f4; color: #333; } .container { max-width: 800px; margin: 0 auto; background-color: #fff; padding: 20px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1); } h1 { color: #0056b3; } p { line-height: 1.6; } code { background-color: #e0e0e0; padding: 2px 4px; border-radius: 3px; }"# }
+        }
+        body {
+          div(class = "container") {
+            h1 { "This is some sample text" }
+            p(class = "intro") { "Welcome to this " span(id = "test_span") { "simple page" } " for tokenizer testing." }
+            p { "Here's a paragraph with a " a(href = "https://example.com", target = "_blank", "data-info" = "link") { "link" } "." }
+            img(src = "https://placehold.co/150x50/cccccc/333333?text=Image", alt = "Placeholder Image")
+            br
+            div(id = "dynamicContent", style = "background-color: lightblue;") {
+              p { "Another paragraph inside a div." }
+            }
+            p { "This is the final content." }
+          }
+        }
+      }
+    };
     let mut test_tokens_deque: VecDeque<Tokens> = test_tokens.into_iter().collect();
     let output_graph = graph_creator(&mut test_tokens_deque);
 
@@ -455,5 +366,14 @@ mod tests {
     }
 }
 
-compare_nodes(&ast[0], &output_graph, "");  }
-}
\ No newline at end of file
+compare_nodes(&ast, &output_graph, "");  }
+
+  #[test]
+  fn test_bare_and_unquoted_attributes() {
+    let root = graph_creator(&mut crate::tokenizer::tokenize("<td nowrap colspan=2 class=\"x\">a</td>"));
+    let Node::Element { attributes, .. } = &root else { panic!("expected an element") };
+    assert_eq!(attributes.get("nowrap").map(String::as_str), Some(""));
+    assert_eq!(attributes.get("colspan").map(String::as_str), Some("2"));
+    assert_eq!(attributes.get("class").map(String::as_str), Some("x"));
+  }
+}
@@ -0,0 +1,420 @@
+use reqwest::Url;
+use crate::Node;
+use crate::entities::decode_entities;
+use crate::util::{attribute, children_of, is_block, resolve_or_raw, tag_of};
+use crate::render::{Segment, is_skipped, segments};
+use crate::table::layout;
+
+// HTML to plain text, laid out the way a text browser would show it.
+//
+// Paragraphs, headings and other blocks are separated by blank lines; h1 and h2
+// are underlined. List items get bullets or numbers with their continuation
+// lines indented under the text, nested lists indent further. Tables become
+// aligned columns with a dashed line under the header row. <br> breaks the line,
+// <hr> draws a rule, <pre> is kept as is and blockquotes are prefixed with "> ".
+//
+// Everything else wraps at `width` characters (0 turns wrapping off). With
+// `link_footnotes` every link gets a [n] marker after its text and the URLs are
+// listed under "References:" at the end.
+
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    pub width: usize,
+    pub link_footnotes: bool,
+    // footnote URLs are resolved against this
+    pub base_url: Option<Url>,
+    pub bullet: char,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions { width: 80, link_footnotes: false, base_url: None, bullet: '*' }
+    }
+}
+
+// the narrowest nested content gets, however deep the indentation
+const MIN_WIDTH: usize = 20;
+
+// a block is its lines; blocks are printed with a blank line between them
+type Block = Vec<String>;
+// a table cell's text, the column it starts at and how many columns it spans
+type Cell = (String, usize, usize);
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+// greedy word wrap; a word longer than the width gets a line of its own
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    if width == 0 {
+        return vec![text.to_string()];
+    }
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ').filter(|word| !word.is_empty()) {
+        if !line.is_empty() && char_len(&line) + 1 + char_len(word) > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn narrower(width: usize, by: usize) -> usize {
+    if width == 0 { 0 } else { width.saturating_sub(by).max(MIN_WIDTH) }
+}
+
+struct Renderer<'o> {
+    options: &'o TextOptions,
+    links: Vec<String>,
+}
+
+pub fn to_text(node: &Node, options: &TextOptions) -> String {
+    let mut renderer = Renderer { options, links: Vec::new() };
+    let blocks = renderer.blocks(std::slice::from_ref(node), options.width);
+    let mut out = blocks.iter().map(|block| block.join("\n")).collect::<Vec<_>>().join("\n\n");
+    if !renderer.links.is_empty() {
+        out.push_str("\n\nReferences:\n");
+        let references: Vec<String> = renderer.links.iter().enumerate().map(|(i, url)| format!("[{}] {}", i + 1, url)).collect();
+        out.push_str(&references.join("\n"));
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+impl Renderer<'_> {
+    fn blocks(&mut self, nodes: &[Node], width: usize) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        for segment in segments(nodes) {
+            match segment {
+                Segment::Inline(run) => self.paragraph(&run, width, &mut blocks),
+                Segment::Block(node) => blocks.extend(self.block(node, width)),
+            }
+        }
+        blocks
+    }
+
+    fn paragraph(&mut self, nodes: &[&Node], width: usize, blocks: &mut Vec<Block>) {
+        let lines = self.inline_lines(nodes, width);
+        if !lines.is_empty() {
+            blocks.push(lines);
+        }
+    }
+
+    // inline content wrapped to lines, with <br> starting a new one
+    fn inline_lines(&mut self, nodes: &[&Node], width: usize) -> Vec<String> {
+        let mut text = String::new();
+        for node in nodes {
+            self.inline(node, &mut text);
+        }
+        let text = text.trim_matches([' ', '\n']);
+        if text.is_empty() {
+            return Vec::new();
+        }
+        text.split('\n').flat_map(|line| {
+            let wrapped = wrap(line.trim(), width);
+            // keep empty lines from <br><br>
+            if wrapped.is_empty() { vec![String::new()] } else { wrapped }
+        }).collect()
+    }
+
+    fn block(&mut self, node: &Node, width: usize) -> Vec<Block> {
+        let tag = tag_of(node);
+        let children = children_of(node);
+        match tag.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let mut lines = self.inline_lines(&children.iter().collect::<Vec<_>>(), width);
+                let underline = match tag.as_str() {
+                    "h1" => Some('='),
+                    "h2" => Some('-'),
+                    _ => None,
+                };
+                if let Some(underline) = underline && !lines.is_empty() {
+                    let length = lines.iter().map(|line| char_len(line)).max().unwrap_or(0);
+                    lines.push(underline.to_string().repeat(length));
+                }
+                if lines.is_empty() { vec![] } else { vec![lines] }
+            }
+            "hr" => vec![vec!["-".repeat(if width == 0 { 40 } else { width })]],
+            "pre" => {
+                let text = decode_entities(&node.text_content()).into_owned();
+                let text = text.strip_prefix('\n').unwrap_or(&text);
+                let text = text.strip_suffix('\n').unwrap_or(text);
+                if text.is_empty() { vec![] } else { vec![text.lines().map(|line| line.trim_end().to_string()).collect()] }
+            }
+            "blockquote" => {
+                let inner = self.blocks(children, narrower(width, 2));
+                let lines = join_blocks(&inner).into_iter().map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) }).collect::<Vec<_>>();
+                if lines.is_empty() { vec![] } else { vec![lines] }
+            }
+            "ul" | "ol" => {
+                let lines = self.list(node, tag == "ol", width);
+                if lines.is_empty() { vec![] } else { vec![lines] }
+            }
+            "dl" => {
+                let mut lines = Vec::new();
+                for child in children {
+                    match tag_of(child).as_str() {
+                        "dt" => lines.extend(self.inline_lines(&children_of(child).iter().collect::<Vec<_>>(), width)),
+                        "dd" => {
+                            let inner = self.blocks(children_of(child), narrower(width, 4));
+                            lines.extend(join_blocks(&inner).into_iter().map(|line| if line.is_empty() { line } else { format!("    {}", line) }));
+                        }
+                        _ => {}
+                    }
+                }
+                if lines.is_empty() { vec![] } else { vec![lines] }
+            }
+            "table" => {
+                let lines = self.table(node);
+                if lines.is_empty() { vec![] } else { vec![lines] }
+            }
+            _ => self.blocks(children, width),
+        }
+    }
+
+    fn list(&mut self, list: &Node, ordered: bool, width: usize) -> Vec<String> {
+        let start = attribute(list, "start").and_then(|start| start.trim().parse::<usize>().ok()).unwrap_or(1);
+        let items: Vec<&Node> = children_of(list).iter().filter(|child| tag_of(child) == "li").collect();
+        // numbers are right-aligned so the item texts line up
+        let number_width = char_len(&(start + items.len().saturating_sub(1)).to_string());
+        let mut lines = Vec::new();
+        for (number, item) in (start..).zip(items) {
+            let marker = if ordered { format!("{:>width$}. ", number, width = number_width) } else { format!("{} ", self.options.bullet) };
+            let indent = " ".repeat(char_len(&marker));
+            let inner = self.blocks(children_of(item), narrower(width, char_len(&marker)));
+            // an item's blocks stay together, nested lists right under its text
+            let item_lines: Vec<String> = inner.into_iter().flatten().collect();
+            if item_lines.is_empty() {
+                lines.push(marker.trim_end().to_string());
+                continue;
+            }
+            for (i, line) in item_lines.into_iter().enumerate() {
+                let prefix = if i == 0 { &marker } else { &indent };
+                lines.push(if line.is_empty() { line } else { format!("{}{}", prefix, line) });
+            }
+        }
+        lines
+    }
+
+    // aligned columns two spaces apart, the header row underlined. Columns are
+    // as wide as their single-column cells; a cell spanning several columns
+    // fills their widths and the gaps between them, widening the last one only
+    // when it doesn't fit
+    fn table(&mut self, table: &Node) -> Vec<String> {
+        let layout = layout(table);
+        let texts: Vec<String> = layout.cells.iter().map(|cell| self.inline_lines(&children_of(cell.node).iter().collect::<Vec<_>>(), 0).join(" ")).collect();
+        // every row covers all the columns: the slots under a cell from a row
+        // above, and the ones no cell reaches, are blank cells of their own
+        let mut rows: Vec<(Vec<Cell>, bool)> = Vec::new();
+        for (r, row) in layout.grid.iter().enumerate() {
+            let mut cells = Vec::new();
+            let mut column = 0;
+            while column < row.len() {
+                let cell = match row[column] {
+                    Some(index) if layout.cells[index].row == r => (texts[index].clone(), column, layout.cells[index].colspan),
+                    Some(index) => (String::new(), column, layout.cells[index].colspan),
+                    None => (String::new(), column, 1),
+                };
+                column += cell.2;
+                cells.push(cell);
+            }
+            rows.push((cells, r < layout.header_rows));
+        }
+        let columns = rows.iter().flat_map(|(cells, _)| cells.last()).map(|(_, column, span)| column + span).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for (text, column, _) in rows.iter().flat_map(|(cells, _)| cells).filter(|(_, _, span)| *span == 1) {
+            widths[*column] = widths[*column].max(char_len(text));
+        }
+        let spanned = |widths: &[usize], column: usize, span: usize| widths[column..column + span].iter().sum::<usize>() + 2 * (span - 1);
+        for (text, column, span) in rows.iter().flat_map(|(cells, _)| cells).filter(|(_, _, span)| *span > 1) {
+            let missing = char_len(text).saturating_sub(spanned(&widths, *column, *span));
+            widths[column + span - 1] += missing;
+        }
+        let mut lines = Vec::new();
+        for (i, (cells, header)) in rows.iter().enumerate() {
+            let line: Vec<String> = cells.iter().map(|(text, column, span)| format!("{:<width$}", text, width = spanned(&widths, *column, *span))).collect();
+            lines.push(line.join("  ").trim_end().to_string());
+            // underline the last row of a header
+            let next_is_header = rows.get(i + 1).is_some_and(|(_, header)| *header);
+            if *header && !next_is_header {
+                lines.push(widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>().join("  "));
+            }
+        }
+        lines
+    }
+
+    fn inline(&mut self, node: &Node, out: &mut String) {
+        if let Node::Text(text) = node {
+            let mut last_space = out.is_empty() || out.ends_with([' ', '\n']);
+            for c in decode_entities(text).chars() {
+                if c.is_whitespace() {
+                    if !last_space {
+                        out.push(' ');
+                    }
+                    last_space = true;
+                } else {
+                    out.push(c);
+                    last_space = false;
+                }
+            }
+            return;
+        }
+        if is_skipped(node) {
+            return;
+        }
+        match tag_of(node).as_str() {
+            "br" => {
+                while out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push('\n');
+            }
+            "img" => {
+                if let Some(alt) = attribute(node, "alt").map(|alt| alt.split_whitespace().collect::<Vec<_>>().join(" ")).filter(|alt| !alt.is_empty()) {
+                    out.push_str(&format!("[{}]", decode_entities(&alt)));
+                }
+            }
+            "a" => {
+                for child in children_of(node) {
+                    self.inline(child, out);
+                }
+                if self.options.link_footnotes && let Some(url) = self.footnote_url(node) {
+                    let number = match self.links.iter().position(|known| *known == url) {
+                        Some(known) => known + 1,
+                        None => {
+                            self.links.push(url);
+                            self.links.len()
+                        }
+                    };
+                    let trailing_space = out.ends_with(' ');
+                    if trailing_space {
+                        out.pop();
+                    }
+                    out.push_str(&format!("[{}]", number));
+                    if trailing_space {
+                        out.push(' ');
+                    }
+                }
+            }
+            _ => {
+                // a block met inside inline content is kept apart from its neighbours
+                let spaced = is_block(node);
+                if spaced && !out.is_empty() && !out.ends_with([' ', '\n']) {
+                    out.push(' ');
+                }
+                for child in children_of(node) {
+                    self.inline(child, out);
+                }
+                if spaced && !out.ends_with([' ', '\n']) {
+                    out.push(' ');
+                }
+            }
+        }
+    }
+
+    // the absolute URL a link footnote points at; in-page and script links get none
+    fn footnote_url(&self, link: &Node) -> Option<String> {
+        let href = attribute(link, "href")?.trim();
+        if href.is_empty() || href.starts_with('#') || href.to_ascii_lowercase().starts_with("javascript:") {
+            return None;
+        }
+        Some(resolve_or_raw(self.options.base_url.as_ref(), href))
+    }
+}
+
+// blocks flattened into lines, with a blank line between them
+fn join_blocks(blocks: &[Block]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        lines.extend(block.iter().cloned());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plaintext::*;
+    use crate::tokenizer::tokenize;
+    use crate::parser::graph_creator;
+
+    fn render(html: &str, options: &TextOptions) -> String {
+        to_text(&graph_creator(&mut tokenize(html)), options)
+    }
+
+    #[test]
+    fn test_blocks_lists_and_wrapping() {
+        let html = "<div><h1>Weekly digest</h1><p>The quick brown fox jumps over the lazy dog, again and again, all week long.</p>\
+            <ul><li>First item</li><li>Second item with enough words to wrap onto a second line<ol><li>nested</li></ol></li></ul>\
+            <ol start=\"9\"><li>nine</li><li>ten</li></ol><p>one<br>two</p><hr><blockquote><p>Quoted words here</p></blockquote></div>";
+        let options = TextOptions { width: 30, ..TextOptions::default() };
+        assert_eq!(render(html, &options), "\
+Weekly digest
+=============
+
+The quick brown fox jumps over
+the lazy dog, again and again,
+all week long.
+
+* First item
+* Second item with enough
+  words to wrap onto a second
+  line
+  1. nested
+
+ 9. nine
+10. ten
+
+one
+two
+
+------------------------------
+
+> Quoted words here
+");
+    }
+
+    #[test]
+    fn test_table_columns() {
+        let html = "<table><thead><tr><th>Name</th><th>Qty</th></tr></thead>\
+            <tr><td>Apples</td><td>3</td></tr><tr><td>Kiwi</td><td>12</td></tr><tr><td colspan=\"2\">total: 15</td></tr></table>";
+        assert_eq!(render(html, &TextOptions::default()), "\
+Name    Qty
+------  ---
+Apples  3
+Kiwi    12
+total: 15
+");
+        // a spanned cell too wide for its columns widens the last of them
+        let html = "<table><tr><th colspan=\"2\">Fruit basket</th></tr><tr><td>a</td><td>b</td></tr></table>";
+        assert_eq!(render(html, &TextOptions::default()), "Fruit basket\n-  ---------\na  b\n");
+        // a cell spanning rows leaves its slot blank in the rows below
+        let html = "<table><tr><th>Item</th><th>A</th><th>B</th></tr><tr><td rowspan=2>x</td><td>1</td><td>2</td></tr><tr><td>3</td><td>4</td></tr></table>";
+        assert_eq!(render(html, &TextOptions::default()), "Item  A  B\n----  -  -\nx     1  2\n      3  4\n");
+    }
+
+    #[test]
+    fn test_link_footnotes() {
+        let html = "<p>See <a href=\"/docs\">the docs</a>, the <a href=\"https://example.org/faq\">FAQ</a> and <a href=\"/docs\">docs again</a>. <a href=\"#top\">Top</a></p>";
+        let options = TextOptions { link_footnotes: true, base_url: Some(Url::parse("https://example.com/").unwrap()), ..TextOptions::default() };
+        assert_eq!(render(html, &options), "\
+See the docs[1], the FAQ[2] and docs again[1]. Top
+
+References:
+[1] https://example.com/docs
+[2] https://example.org/faq
+");
+        assert_eq!(render(html, &TextOptions::default()), "See the docs, the FAQ and docs again. Top\n");
+    }
+}
@@ -0,0 +1,185 @@
+use std::ops::Not;
+use crate::Node;
+use crate::buscador::TextMatch;
+use crate::util::attribute;
+
+// Composable conditions on nodes for find_by_predicate:
+//
+//     tag("a").and(has_class("btn")).and(attr_starts_with("href", "/cart"))
+//     tag("li").and(!has_class("ad"))
+//
+// Every helper only matches elements, except text_matches which also looks at
+// text nodes' own text. Only Predicate is re-exported at the crate root, since
+// short names like tag and element would clash there; the helpers are used
+// through the module, e.g. `use scraper::predicate::*`.
+
+pub struct Predicate {
+    test: Box<dyn Fn(&Node) -> bool + Send + Sync>,
+}
+
+impl Predicate {
+    pub fn new<F>(test: F) -> Self
+    where
+        F: Fn(&Node) -> bool + Send + Sync + 'static,
+    {
+        Predicate { test: Box::new(test) }
+    }
+
+    pub fn matches(&self, node: &Node) -> bool {
+        (self.test)(node)
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        Predicate::new(move |node| self.matches(node) && other.matches(node))
+    }
+
+    pub fn or(self, other: Predicate) -> Self {
+        Predicate::new(move |node| self.matches(node) || other.matches(node))
+    }
+}
+
+impl Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        Predicate::new(move |node| !self.matches(node))
+    }
+}
+
+impl std::fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Predicate")
+    }
+}
+
+// the whitespace-separated tokens of an element's class attribute
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassList<'a> {
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> ClassList<'a> {
+    pub fn contains(&self, class: &str) -> bool {
+        self.tokens.contains(&class)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.tokens.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+impl Node {
+    // empty for text nodes and elements without a class
+    pub fn class_list(&self) -> ClassList<'_> {
+        let mut tokens: Vec<&str> = match self {
+            Node::Element { attributes, .. } => attributes.get("class").map(|class| class.split_whitespace().collect()).unwrap_or_default(),
+            Node::Text(_) => Vec::new(),
+        };
+        let mut seen = Vec::new();
+        tokens.retain(|token| {
+            let first = !seen.contains(token);
+            seen.push(*token);
+            first
+        });
+        ClassList { tokens }
+    }
+}
+
+// any element
+pub fn element() -> Predicate {
+    Predicate::new(|node| matches!(node, Node::Element { .. }))
+}
+
+// tag names are compared ignoring ASCII case
+pub fn tag(tag_name: &str) -> Predicate {
+    let tag_name = tag_name.to_string();
+    Predicate::new(move |node| matches!(node, Node::Element { tag_name: name, .. } if name.eq_ignore_ascii_case(&tag_name)))
+}
+
+pub fn has_class(class: &str) -> Predicate {
+    let class = class.to_string();
+    Predicate::new(move |node| node.class_list().contains(&class))
+}
+
+pub fn has_attr(name: &str) -> Predicate {
+    let name = name.to_string();
+    Predicate::new(move |node| attribute(node, &name).is_some())
+}
+
+pub fn attr_equals(name: &str, value: &str) -> Predicate {
+    let (name, value) = (name.to_string(), value.to_string());
+    Predicate::new(move |node| attribute(node, &name) == Some(value.as_str()))
+}
+
+pub fn attr_starts_with(name: &str, prefix: &str) -> Predicate {
+    let (name, prefix) = (name.to_string(), prefix.to_string());
+    Predicate::new(move |node| attribute(node, &name).is_some_and(|value| value.starts_with(&prefix)))
+}
+
+pub fn attr_ends_with(name: &str, suffix: &str) -> Predicate {
+    let (name, suffix) = (name.to_string(), suffix.to_string());
+    Predicate::new(move |node| attribute(node, &name).is_some_and(|value| value.ends_with(&suffix)))
+}
+
+pub fn attr_contains(name: &str, part: &str) -> Predicate {
+    let (name, part) = (name.to_string(), part.to_string());
+    Predicate::new(move |node| attribute(node, &name).is_some_and(|value| value.contains(&part)))
+}
+
+// a text node whose text matches, or an element with such a text node as a child
+pub fn text_matches(text_match: TextMatch) -> Predicate {
+    Predicate::new(move |node| match node {
+        Node::Text(text) => text_match.is_match(text),
+        Node::Element { children, .. } => children.iter().any(|child| matches!(child, Node::Text(text) if text_match.is_match(text))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::predicate::*;
+    use crate::html;
+
+    fn link(class: &str, href: &str) -> Node {
+        html! { a(class = class, href = href) { "link" } }
+    }
+
+    #[test]
+    fn test_class_list() {
+        let node = html! { div(class = "  card wide\tcard  ") };
+        let classes = node.class_list();
+        assert_eq!(classes.iter().collect::<Vec<_>>(), vec!["card", "wide"]);
+        assert!(classes.contains("wide"));
+        assert!(!classes.contains("car"));
+        assert!(html! { div }.class_list().is_empty());
+        assert!(Node::Text("card".to_string()).class_list().is_empty());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let cart_button = tag("a").and(has_class("btn")).and(attr_starts_with("href", "/cart"));
+        assert!(cart_button.matches(&link("btn primary", "/cart/add")));
+        assert!(!cart_button.matches(&link("btn", "/checkout")));
+        assert!(!cart_button.matches(&link("button", "/cart")));
+
+        let not_ad = tag("A").and(!has_class("ad"));
+        assert!(not_ad.matches(&link("btn", "/")));
+        assert!(!not_ad.matches(&link("ad btn", "/")));
+
+        let either = has_class("ad").or(attr_ends_with("href", ".pdf"));
+        assert!(either.matches(&link("x", "/doc.pdf")));
+        assert!(!either.matches(&link("x", "/doc.html")));
+        assert!(attr_contains("href", "doc").matches(&link("x", "/doc.html")));
+        assert!(attr_equals("class", "x").matches(&link("x", "/")));
+        assert!(has_attr("href").matches(&link("x", "/")));
+        assert!(!element().matches(&Node::Text("x".to_string())));
+        assert!(text_matches(TextMatch::Equals("link".to_string())).matches(&link("x", "/")));
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+use std::fmt::Write as _;
+use crate::Node;
+use crate::mutation::NodePath;
+use crate::parser::SourcePositions;
+use crate::tokenizer::VOID_ELEMENTS;
+use crate::entities::entity_at;
+
+// Turning a tree back into something readable:
+// - `{}` (Display) writes compact HTML, like outerHTML
+// - pretty() writes indented HTML, one element per line
+// - dump() writes the html5lib test format, which is easy to diff in snapshots:
+//
+//     | <p>
+//     |   class="intro"
+//     |   "Welcome to this "
+//     |   <span>
+//
+// Attributes are always written in name order so the output is stable.
+
+// script and style content is written as is, everything else gets escaped
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+// the parser keeps entities as they are in the source, so an & that already
+// starts a reference is written back untouched instead of becoming &amp;amp;
+fn escape(text: &str, replacements: &[(char, &str)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        match c {
+            '&' if entity_at(&text[i..]).is_none() => out.push_str("&amp;"),
+            _ => match replacements.iter().find(|(special, _)| *special == c) {
+                Some((_, replacement)) => out.push_str(replacement),
+                None => out.push(c),
+            },
+        }
+    }
+    out
+}
+
+fn escape_text(text: &str) -> String {
+    escape(text, &[('<', "&lt;"), ('>', "&gt;")])
+}
+
+fn escape_attribute(value: &str) -> String {
+    escape(value, &[('"', "&quot;")])
+}
+
+fn open_tag(tag_name: &str, attributes: &crate::Attributes) -> String {
+    let mut names: Vec<&String> = attributes.keys().collect();
+    names.sort();
+    let mut out = format!("<{}", tag_name);
+    for name in names {
+        let _ = write!(out, " {}=\"{}\"", name, escape_attribute(&attributes[name]));
+    }
+    out.push('>');
+    out
+}
+
+fn write_html(node: &Node, raw: bool, out: &mut String) {
+    match node {
+        Node::Text(text) if raw => out.push_str(text),
+        Node::Text(text) => out.push_str(&escape_text(text)),
+        Node::Element { tag_name, attributes, children } => {
+            out.push_str(&open_tag(tag_name, attributes));
+            if VOID_ELEMENTS.contains(&tag_name.as_str()) && children.is_empty() {
+                return;
+            }
+            let raw = RAW_TEXT_ELEMENTS.contains(&tag_name.as_str());
+            for child in children {
+                write_html(child, raw, out);
+            }
+            let _ = write!(out, "</{}>", tag_name);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        write_html(self, false, &mut out);
+        f.write_str(&out)
+    }
+}
+
+fn write_pretty(node: &Node, depth: usize, raw: bool, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match node {
+        Node::Text(text) => {
+            let text = text.trim();
+            if !text.is_empty() {
+                let text = if raw { text.to_string() } else { escape_text(text) };
+                let _ = writeln!(out, "{}{}", indent, text);
+            }
+        }
+        Node::Element { tag_name, attributes, children } => {
+            let open = open_tag(tag_name, attributes);
+            if VOID_ELEMENTS.contains(&tag_name.as_str()) && children.is_empty() {
+                let _ = writeln!(out, "{}{}", indent, open);
+                return;
+            }
+            let raw = RAW_TEXT_ELEMENTS.contains(&tag_name.as_str());
+            // keep <h1>Title</h1> on one line
+            if let [Node::Text(text)] = children.as_slice() && !text.contains('\n') {
+                let text = if raw { text.trim().to_string() } else { escape_text(text.trim()) };
+                let _ = writeln!(out, "{}{}{}</{}>", indent, open, text, tag_name);
+                return;
+            }
+            let _ = writeln!(out, "{}{}", indent, open);
+            for child in children {
+                write_pretty(child, depth + 1, raw, out);
+            }
+            let _ = writeln!(out, "{}</{}>", indent, tag_name);
+        }
+    }
+}
+
+fn write_dump(node: &Node, depth: usize, path: &mut NodePath, positions: Option<&SourcePositions>, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let position = positions
+        .and_then(|positions| positions.get(path))
+        .map(|position| format!(" ({}:{})", position.line, position.column))
+        .unwrap_or_default();
+    match node {
+        Node::Text(text) => {
+            let _ = writeln!(out, "| {}\"{}\"{}", indent, text, position);
+        }
+        Node::Element { tag_name, attributes, children } => {
+            let _ = writeln!(out, "| {}<{}>{}", indent, tag_name, position);
+            let mut names: Vec<&String> = attributes.keys().collect();
+            names.sort();
+            for name in names {
+                let _ = writeln!(out, "| {}  {}=\"{}\"", indent, name, attributes[name]);
+            }
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                write_dump(child, depth + 1, path, positions, out);
+                path.pop();
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        write_pretty(self, 0, false, &mut out);
+        out
+    }
+
+    // html5lib tree format
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        write_dump(self, 0, &mut Vec::new(), None, &mut out);
+        out
+    }
+
+    // html5lib tree format with `(line:column)` after every node found in
+    // `positions`, see graph_creator_with_positions
+    pub fn dump_with_positions(&self, positions: &SourcePositions) -> String {
+        let mut out = String::new();
+        write_dump(self, 0, &mut Vec::new(), Some(positions), &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{graph_creator, graph_creator_with_positions};
+    use crate::tokenizer::{tokenize, tokenize_with_positions};
+    use crate::html;
+
+    #[test]
+    fn test_display() {
+        let node = html! {
+            div(class = "a \"b\"", id = "x") {
+                "1 < 2 & 3"
+                br
+                img(src = "i.png")
+                script { "if (a < b) {}" }
+            }
+        };
+        assert_eq!(
+            node.to_string(),
+            r#"<div class="a &quot;b&quot;" id="x">1 &lt; 2 &amp; 3<br><img src="i.png"><script>if (a < b) {}</script></div>"#
+        );
+    }
+
+    #[test]
+    fn test_pretty() {
+        let node = html! {
+            body {
+                h1 { "Title" }
+                p(class = "intro") { "Welcome to this " span { "page" } }
+                hr
+            }
+        };
+        assert_eq!(node.pretty(), "\
+<body>
+  <h1>Title</h1>
+  <p class=\"intro\">
+    Welcome to this
+    <span>page</span>
+  </p>
+  <hr>
+</body>
+");
+    }
+
+    #[test]
+    fn test_dump() {
+        let node = html! { p(id = "b", class = "a") { "Welcome " span { "page" } } };
+        assert_eq!(node.dump(), "\
+| <p>
+|   class=\"a\"
+|   id=\"b\"
+|   \"Welcome \"
+|   <span>
+|     \"page\"
+");
+    }
+
+    #[test]
+    fn test_dump_with_positions() {
+        let source = "<html>\n  <body>\n    <p class=\"x\">hi <b>there</b></p>\n  </body>\n</html>";
+        let (mut tokens, positions) = tokenize_with_positions(source);
+        let (graph, positions) = graph_creator_with_positions(&mut tokens, positions);
+        assert_eq!(graph.dump_with_positions(&positions), "\
+| <html> (1:1)
+|   <body> (2:3)
+|     <p> (3:5)
+|       class=\"x\"
+|       \"hi \" (3:18)
+|       <b> (3:21)
+|         \"there\" (3:24)
+");
+    }
+
+    #[test]
+    fn test_entities_round_trip() {
+        let source = r#"<p title="Tom &amp; &quot;Jerry&quot;">a &amp; b &lt;c&gt; &copy; &#169; AT&T</p>"#;
+        let printed = graph_creator(&mut tokenize(source)).to_string();
+        assert_eq!(printed, r#"<p title="Tom &amp; &quot;Jerry&quot;">a &amp; b &lt;c&gt; &copy; &#169; AT&amp;T</p>"#);
+        assert_eq!(graph_creator(&mut tokenize(&printed)).to_string(), printed);
+    }
+}
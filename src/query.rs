@@ -0,0 +1,234 @@
+use std::fmt;
+use regex::Regex;
+use crate::Node;
+use std::borrow::Cow;
+use crate::selector::{compile_selector, Matches, SelectorList};
+
+// A CSS selector parsed once and run as often as needed. Queries own all their
+// data, so they can live in a static and be shared between threads:
+//
+//     static PRICE: LazyLock<Query> = LazyLock::new(|| Query::compile("div.product .price").unwrap());
+//     let prices = PRICE.select(&page);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    source: String,
+    selectors: SelectorList,
+}
+
+// where the selector stopped making sense. position counts characters from 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    pub fn compile(selector: &str) -> Result<Query, QueryError> {
+        Ok(Query { source: selector.to_string(), selectors: compile_selector(selector)? })
+    }
+
+    // the selector text the query was compiled from
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    // every matching element under (and including) root_node, in document order.
+    // a trailing ::text or ::attr() makes no difference here, see extract
+    pub fn select<'a>(&self, root_node: &'a Node) -> Vec<&'a Node> {
+        self.selectors.select(root_node)
+    }
+
+    // lazy version of select, stops walking when the caller stops pulling
+    pub fn iter<'q, 'a>(&'q self, root_node: &'a Node) -> Matches<'q, 'a> {
+        self.selectors.matches(root_node)
+    }
+
+    pub fn select_first<'a>(&self, root_node: &'a Node) -> Option<&'a Node> {
+        self.iter(root_node).next()
+    }
+
+    // like iter, for a query that isn't kept around
+    pub(crate) fn into_matches<'a>(self, root_node: &'a Node) -> Matches<'static, 'a> {
+        Matches::new(Cow::Owned(self.selectors), root_node)
+    }
+
+    // the strings the selector's ::text or ::attr(name) points at, e.g.
+    // `h2.title::text` or `a.next::attr(href)`. without one, the outer HTML of
+    // every match
+    pub fn extract(&self, root_node: &Node) -> Extracted {
+        Extracted { values: self.selectors.extract(root_node) }
+    }
+
+    // true when every selector in the list ends in ::text or ::attr(), so extract
+    // gives strings rather than HTML
+    pub fn extracts_values(&self) -> bool {
+        self.selectors.extracts_values()
+    }
+
+    pub(crate) fn selectors(&self) -> &SelectorList {
+        &self.selectors
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+// what Query::extract found, with Scrapy's helpers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extracted {
+    values: Vec<String>,
+}
+
+impl Extracted {
+    // the first value
+    pub fn get(&self) -> Option<&str> {
+        self.values.first().map(String::as_str)
+    }
+
+    pub fn get_all(&self) -> &[String] {
+        &self.values
+    }
+
+    // every regex match in every value. with capture groups, the groups that
+    // took part in the match instead of the whole match
+    pub fn re(&self, pattern: &str) -> Result<Vec<String>, String> {
+        let regex = Regex::new(pattern).map_err(|error| error.to_string())?;
+        let mut found = Vec::new();
+        for value in &self.values {
+            for captures in regex.captures_iter(value) {
+                if captures.len() == 1 {
+                    found.push(captures[0].to_string());
+                } else {
+                    found.extend(captures.iter().skip(1).flatten().map(|group| group.as_str().to_string()));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    pub fn re_first(&self, pattern: &str) -> Result<Option<String>, String> {
+        Ok(self.re(pattern)?.into_iter().next())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl IntoIterator for Extracted {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Extracted {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+    use crate::query::*;
+    use crate::html;
+
+    static LINKS: LazyLock<Query> = LazyLock::new(|| Query::compile("ul > li a[href]").unwrap());
+
+    fn page() -> Node {
+        html! {
+            ul {
+                li { a(href = "/one") { "one" } }
+                li { a { "no link" } }
+                li { a(href = "/three") { "three" } }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_query_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Query>();
+
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| LINKS.select(&page()).len())).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(counts, vec![2, 2, 2, 2]);
+        assert_eq!(LINKS.as_str(), "ul > li a[href]");
+    }
+
+    #[test]
+    fn test_query_error_position() {
+        let error = Query::compile("ul > li a[href").unwrap_err();
+        assert_eq!(error, QueryError { position: 14, message: "unterminated attribute selector".to_string() });
+        assert_eq!(error.to_string(), "unterminated attribute selector at position 14");
+
+        let error = Query::compile("li:hover").unwrap_err();
+        assert_eq!(error.position, 2);
+        assert_eq!(error.message, "unsupported pseudo-class ':hover'");
+    }
+
+    #[test]
+    fn test_extract_pseudo_elements() {
+        let page = html! {
+            div {
+                h2(class = "title") { "First " b { "bold" } " title" }
+                h2(class = "title") { "Second" }
+                a(class = "next", href = "/page/2") { "Next" }
+                span(class = "price") { "Now $12.50, was $15.00" }
+            }
+        };
+        let titles = Query::compile("h2.title::text").unwrap().extract(&page);
+        assert_eq!(titles.get_all(), ["First ", " title", "Second"]);
+        assert_eq!(titles.get(), Some("First "));
+        assert_eq!(Query::compile("a.next::attr(href)").unwrap().extract(&page).get(), Some("/page/2"));
+        assert!(Query::compile("h2::attr(href)").unwrap().extract(&page).is_empty());
+        assert_eq!(Query::compile("h2 ::text").unwrap().extract(&page).get_all(), ["First ", "bold", " title", "Second"]);
+        assert_eq!(Query::compile("div ::attr(class)").unwrap().extract(&page).get_all(), ["title", "title", "next", "price"]);
+        assert_eq!(Query::compile("h2 > ::text").unwrap().extract(&page).get_all(), ["bold"]);
+        let nested = html! { ul { li { "a" ul { li { "b" } } } } };
+        assert_eq!(Query::compile("li ::text").unwrap().extract(&nested).get_all(), ["a", "b"]);
+        assert_eq!(Query::compile("h2 b, a").unwrap().extract(&page).get_all(), ["<b>bold</b>", "<a class=\"next\" href=\"/page/2\">Next</a>"]);
+        assert_eq!(Query::compile("a::text, a::attr(href)").unwrap().extract(&page).get_all(), ["Next", "/page/2"]);
+
+        let prices = Query::compile(".price::text").unwrap().extract(&page);
+        assert_eq!(prices.re(r"\$\d+\.\d\d").unwrap(), vec!["$12.50", "$15.00"]);
+        assert_eq!(prices.re(r"\$(\d+)\.(\d\d)").unwrap(), vec!["12", "50", "15", "00"]);
+        assert_eq!(prices.re_first(r"was \$([\d.]+)").unwrap(), Some("15.00".to_string()));
+        assert!(prices.re("(").is_err());
+
+        // bare ::text is the scope element's own text
+        assert_eq!(Query::compile("::text").unwrap().extract(&html! { p { "hi" } }).get(), Some("hi"));
+    }
+
+    #[test]
+    fn test_pseudo_element_errors() {
+        assert_eq!(Query::compile("a::before").unwrap_err().to_string(), "unsupported pseudo-element '::before' at position 1");
+        assert_eq!(Query::compile(":not(a::text)").unwrap_err().position, 6);
+        assert!(Query::compile("a::text b").is_err());
+        assert!(Query::compile("a::attr()").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::Node;
+use crate::util::{is_block, tag_of};
+
+// What to_markdown and to_text share: which elements are left out and how a run
+// of children splits into blocks and paragraphs. Tables are laid out by table.rs.
+
+// never rendered, content included
+const SKIPPED_TAGS: [&str; 17] = [
+    "head", "title", "meta", "link", "script", "style", "noscript", "template", "iframe", "svg", "canvas", "input", "select", "textarea", "button", "object", "embed",
+];
+
+pub(crate) fn is_skipped(node: &Node) -> bool {
+    SKIPPED_TAGS.contains(&tag_of(node).as_str())
+}
+
+pub(crate) enum Segment<'a> {
+    // inline nodes next to each other, which make up one paragraph
+    Inline(Vec<&'a Node>),
+    Block(&'a Node),
+}
+
+// the nodes as paragraphs and block elements in order, skipped elements left out
+pub(crate) fn segments(nodes: &[Node]) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut inline_run: Vec<&Node> = Vec::new();
+    for node in nodes {
+        if is_skipped(node) {
+            continue;
+        }
+        if is_block(node) {
+            if !inline_run.is_empty() {
+                segments.push(Segment::Inline(std::mem::take(&mut inline_run)));
+            }
+            segments.push(Segment::Block(node));
+        } else {
+            inline_run.push(node);
+        }
+    }
+    if !inline_run.is_empty() {
+        segments.push(Segment::Inline(inline_run));
+    }
+    segments
+}
@@ -0,0 +1,261 @@
+use crate::Node;
+use crate::mutation::{node_at, NodePath};
+use crate::query::{Query, QueryError};
+
+// A set of elements that can be narrowed down step by step, jQuery style:
+//
+//     document.select("table.prices").find("tr").filter(":has(td)").eq(1).text()
+//
+// Elements are kept as paths from the root, so moving up to parents and over to
+// siblings is cheap, and they stay in document order without duplicates.
+//
+// Nothing here returns a Result. A selector that doesn't compile gives an empty
+// selection with the error recorded, every step after it stays empty and keeps
+// that first error, and error() reports it at the end of the chain.
+
+#[derive(Debug, Clone)]
+pub struct Selection<'a> {
+    root: &'a Node,
+    paths: Vec<NodePath>,
+    error: Option<QueryError>,
+}
+
+impl<'a> Selection<'a> {
+    // a selection holding just root_node
+    pub fn new(root_node: &'a Node) -> Self {
+        Selection { root: root_node, paths: vec![Vec::new()], error: None }
+    }
+
+    pub(crate) fn from_paths(root_node: &'a Node, paths: Vec<NodePath>) -> Self {
+        Selection { root: root_node, paths, error: None }
+    }
+
+    pub(crate) fn failed(root_node: &'a Node, error: QueryError) -> Self {
+        Selection { root: root_node, paths: Vec::new(), error: Some(error) }
+    }
+
+    // a new selection from the same tree, carrying the error along
+    fn with_paths(&self, mut paths: Vec<NodePath>) -> Self {
+        paths.sort();
+        paths.dedup();
+        Selection { root: self.root, paths, error: self.error.clone() }
+    }
+
+    fn compile(&self, selector: &str) -> Result<Query, Self> {
+        Query::compile(selector).map_err(|error| Selection {
+            root: self.root,
+            paths: Vec::new(),
+            error: Some(self.error.clone().unwrap_or(error)),
+        })
+    }
+
+    fn is_element(&self, path: &[usize]) -> bool {
+        matches!(node_at(self.root, path), Some(Node::Element { .. }))
+    }
+
+    // matching descendants of the selected elements. :scope is the element
+    // being searched, so `find(":scope > li")` gives direct children only
+    pub fn find(&self, selector: &str) -> Self {
+        let query = match self.compile(selector) {
+            Ok(query) => query,
+            Err(failed) => return failed,
+        };
+        let paths = self.paths.iter().flat_map(|path| query.selectors().select_paths(self.root, path, false)).collect();
+        self.with_paths(paths)
+    }
+
+    // the selected elements that match
+    pub fn filter(&self, selector: &str) -> Self {
+        let query = match self.compile(selector) {
+            Ok(query) => query,
+            Err(failed) => return failed,
+        };
+        let paths = self.paths.iter().filter(|path| query.selectors().matches_path(self.root, path, &[])).cloned().collect();
+        self.with_paths(paths)
+    }
+
+    // for each element, itself or its nearest ancestor that matches
+    pub fn closest(&self, selector: &str) -> Self {
+        let query = match self.compile(selector) {
+            Ok(query) => query,
+            Err(failed) => return failed,
+        };
+        let paths = self
+            .paths
+            .iter()
+            .filter_map(|path| (0..=path.len()).rev().map(|end| &path[..end]).find(|ancestor| query.selectors().matches_path(self.root, ancestor, &[])))
+            .map(|path| path.to_vec())
+            .collect();
+        self.with_paths(paths)
+    }
+
+    pub fn parent(&self) -> Self {
+        let paths = self.paths.iter().filter(|path| !path.is_empty()).map(|path| path[..path.len() - 1].to_vec()).collect();
+        self.with_paths(paths)
+    }
+
+    // child elements, text is left out
+    pub fn children(&self) -> Self {
+        let mut paths = Vec::new();
+        for path in &self.paths {
+            if let Some(Node::Element { children, .. }) = node_at(self.root, path) {
+                for (i, child) in children.iter().enumerate() {
+                    if let Node::Element { .. } = child {
+                        let mut child_path = path.clone();
+                        child_path.push(i);
+                        paths.push(child_path);
+                    }
+                }
+            }
+        }
+        self.with_paths(paths)
+    }
+
+    // the other elements with the same parent
+    pub fn siblings(&self) -> Self {
+        let mut paths = Vec::new();
+        for path in &self.paths {
+            let Some((&own, parent)) = path.split_last() else { continue };
+            if let Some(Node::Element { children, .. }) = node_at(self.root, parent) {
+                for i in (0..children.len()).filter(|&i| i != own) {
+                    let mut sibling = parent.to_vec();
+                    sibling.push(i);
+                    if self.is_element(&sibling) {
+                        paths.push(sibling);
+                    }
+                }
+            }
+        }
+        self.with_paths(paths)
+    }
+
+    // the element at `index`, or an empty selection
+    pub fn eq(&self, index: usize) -> Self {
+        self.with_paths(self.paths.get(index).cloned().into_iter().collect())
+    }
+
+    pub fn first(&self) -> Self {
+        self.eq(0)
+    }
+
+    pub fn last(&self) -> Self {
+        self.with_paths(self.paths.last().cloned().into_iter().collect())
+    }
+
+    // the text of every selected element, joined
+    pub fn text(&self) -> String {
+        self.nodes().iter().map(|node| node.text_content()).collect()
+    }
+
+    // the attribute on the first selected element
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        match self.nodes().first()? {
+            Node::Element { attributes, .. } => attributes.get(name).map(String::as_str),
+            Node::Text(_) => None,
+        }
+    }
+
+    // call f with each element's index and a selection holding just that element
+    pub fn each<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, Selection<'a>),
+    {
+        for (i, path) in self.paths.iter().enumerate() {
+            f(i, self.with_paths(vec![path.clone()]));
+        }
+    }
+
+    pub fn nodes(&self) -> Vec<&'a Node> {
+        self.paths.iter().filter_map(|path| node_at(self.root, path)).collect()
+    }
+
+    pub fn paths(&self) -> &[NodePath] {
+        &self.paths
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    // the first invalid selector met along the chain
+    pub fn error(&self) -> Option<&QueryError> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Document;
+    use crate::html;
+
+    fn document() -> Document {
+        Document::new(html! {
+            body {
+                table(class = "prices") {
+                    tr { th { "Item" } th { "Price" } }
+                    tr { td { "Widget" } td(class = "price") { "4.50" } }
+                    tr(class = "total") { td { "Total" } td(class = "price") { "4.50" } }
+                }
+                ul { li { a(href = "/a") { "a" } } li(class = "current") { "b" } li { "c" } }
+            }
+        })
+    }
+
+    #[test]
+    fn test_chaining() {
+        let document = document();
+        let rows = document.select("table.prices").find("tr").filter(":has(td)");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows.eq(1).find("td").first().text(), "Total");
+        assert_eq!(rows.find(".price").text(), "4.504.50");
+        assert_eq!(rows.first().children().len(), 2);
+        assert_eq!(rows.parent().len(), 1);
+        assert_eq!(rows.last().attr("class"), Some("total"));
+        assert!(rows.eq(5).is_empty());
+        assert_eq!(document.select("table").find(":scope > tr").len(), 3);
+    }
+
+    #[test]
+    fn test_closest_and_siblings() {
+        let document = document();
+        assert_eq!(document.select("a").closest("ul").len(), 1);
+        assert_eq!(document.select("li").closest("li").len(), 3);
+        assert!(document.select("a").closest("table").is_empty());
+        let others = document.select("li.current").siblings();
+        assert_eq!(others.text(), "ac");
+        assert_eq!(document.select("li").siblings().len(), 3);
+        assert_eq!(document.select("li").find("a").attr("href"), Some("/a"));
+    }
+
+    #[test]
+    fn test_each() {
+        let document = document();
+        let mut seen = Vec::new();
+        document.select("li").each(|i, item| seen.push(format!("{}:{}", i, item.text())));
+        assert_eq!(seen, vec!["0:a", "1:b", "2:c"]);
+    }
+
+    #[test]
+    fn test_invalid_selector_is_recorded() {
+        let document = document();
+        let broken = document.select("table").find("tr[").filter("td").parent();
+        assert!(broken.is_empty());
+        assert_eq!(broken.error().map(|error| error.position), Some(3));
+        let second = document.select("li").filter("::bogus").find("a[");
+        assert_eq!(second.error().unwrap().message, "unsupported pseudo-element '::bogus'");
+        assert!(document.select("li").error().is_none());
+    }
+
+    #[test]
+    fn test_select_with_index() {
+        let plain = document();
+        let indexed = document().indexed();
+        for selector in ["td.price", "tr", "li.current, a", "tr:has(td) > td"] {
+            assert_eq!(indexed.select(selector).paths(), plain.select(selector).paths(), "{}", selector);
+        }
+    }
+}
@@ -0,0 +1,1070 @@
+use std::borrow::Cow;
+use crate::Node;
+use crate::mutation::{node_at, NodePath};
+use crate::query::QueryError;
+use crate::traversal::pre_order;
+
+// CSS selectors (Selectors Level 4 subset) over the Node tree.
+//
+// Nodes don't know their parents, so matching works on a context: the chain of
+// (node, index in parent's children) from the root down to the node being tested.
+// Selectors are matched right to left against that chain, the same way browsers
+// do it, and the combinators walk up the chain or over to the siblings.
+
+pub(crate) type Context<'a> = [(&'a Node, usize)];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorList {
+    selectors: Vec<ComplexSelector>,
+}
+
+// compounds[0] combinators[0] compounds[1] ... read left to right
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexSelector {
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+    // only allowed at the very end of a top-level selector
+    pseudo_element: Option<PseudoElement>,
+    // set for `h2 ::text`, with whitespace before the pseudo-element, which takes
+    // it from the matched element and every element under it
+    pseudo_element_descendants: bool,
+}
+
+// Scrapy's extraction pseudo-elements: `h2::text` gives the text nodes directly
+// inside each h2 and `a::attr(href)` the href values. Like parsel, `h2 ::text`
+// gives every text node under each h2, its own included, and `ul ::attr(href)`
+// the hrefs of ul and everything in it
+#[derive(Debug, Clone, PartialEq)]
+enum PseudoElement {
+    Text,
+    Attr(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Compound {
+    simple: Vec<Simple>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,        // a b
+    Child,             // a > b
+    NextSibling,       // a + b
+    SubsequentSibling, // a ~ b
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Simple {
+    Universal,
+    Type(String),
+    Id(String),
+    Class(String),
+    Attribute(AttributeSelector),
+    Not(SelectorList),
+    Is(SelectorList),
+    Where(SelectorList),
+    Has(Vec<ComplexSelector>),
+    // the element the query runs from, also the anchor inside :has()
+    Scope,
+    // :nth-child(An+B of S) and :nth-last-child, also :first-child and friends
+    NthChild { nth: Nth, of: Option<SelectorList>, from_end: bool },
+    // :nth-of-type, :nth-last-of-type, :first-of-type, ...
+    NthOfType { nth: Nth, from_end: bool },
+    Empty,
+    Root,
+}
+
+// what DocumentIndex can look a selector up by
+pub(crate) enum IndexKey<'s> {
+    Id(&'s str),
+    Class(&'s str),
+    Tag(&'s str),
+}
+
+// the An+B of the nth pseudo-classes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Nth {
+    a: i64,
+    b: i64,
+}
+
+impl Nth {
+    const FIRST: Nth = Nth { a: 0, b: 1 };
+
+    // position is 1-based. true when position = a*n + b for some n >= 0
+    fn matches(&self, position: i64) -> bool {
+        if self.a == 0 {
+            return position == self.b;
+        }
+        let steps = position - self.b;
+        steps % self.a == 0 && steps / self.a >= 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AttributeOperator {
+    Equals,     // [a=v]
+    Includes,   // [a~=v] one of the whitespace separated words
+    DashMatch,  // [a|=v] v or v-...
+    Prefix,     // [a^=v]
+    Suffix,     // [a$=v]
+    Substring,  // [a*=v]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeSelector {
+    name: String,
+    // None for a plain [name]
+    operator: Option<(AttributeOperator, String)>,
+    case_insensitive: bool,
+}
+
+impl AttributeSelector {
+    fn matches(&self, attributes: &crate::Attributes) -> bool {
+        let Some(actual) = attributes.iter().find(|(name, _)| name.eq_ignore_ascii_case(&self.name)).map(|(_, value)| value) else {
+            return false;
+        };
+        let Some((operator, expected)) = &self.operator else {
+            return true;
+        };
+        let (actual, expected) = if self.case_insensitive {
+            (actual.to_lowercase(), expected.to_lowercase())
+        } else {
+            (actual.clone(), expected.clone())
+        };
+        match operator {
+            AttributeOperator::Equals => actual == expected,
+            AttributeOperator::Includes => actual.split_whitespace().any(|word| word == expected),
+            AttributeOperator::DashMatch => actual == expected || actual.starts_with(&format!("{}-", expected)),
+            AttributeOperator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+            AttributeOperator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+            AttributeOperator::Substring => !expected.is_empty() && actual.contains(&expected),
+        }
+    }
+}
+
+pub fn parse_selector(selector: &str) -> Result<SelectorList, String> {
+    compile_selector(selector).map_err(|error| error.to_string())
+}
+
+pub(crate) fn compile_selector(selector: &str) -> Result<SelectorList, QueryError> {
+    let mut parser = SelectorParser { chars: selector.chars().collect(), pos: 0, nesting: 0 };
+    parser.skip_whitespace();
+    let list = parser.selector_list()?;
+    parser.skip_whitespace();
+    if let Some(c) = parser.peek() {
+        return Err(parser.error(&format!("unexpected '{}'", c)));
+    }
+    Ok(list)
+}
+
+impl SelectorList {
+    // every element under (and including) root_node that matches, in document order
+    pub fn select<'a>(&self, root_node: &'a Node) -> Vec<&'a Node> {
+        let mut matches = Vec::new();
+        let mut context = vec![(root_node, 0)];
+        self.collect(root_node, &mut context, &mut matches);
+        matches
+    }
+
+    // the same matches, found lazily: the walk only goes as far as the caller pulls
+    pub fn matches<'q, 'a>(&'q self, root_node: &'a Node) -> Matches<'q, 'a> {
+        Matches::new(Cow::Borrowed(self), root_node)
+    }
+
+    fn collect<'a>(&self, root_node: &'a Node, context: &mut Vec<(&'a Node, usize)>, matches: &mut Vec<&'a Node>) {
+        let (node, _) = context[context.len() - 1];
+        if self.matches_context(context, Some(root_node)) {
+            matches.push(node);
+        }
+        if let Node::Element { children, .. } = node {
+            for (i, child) in children.iter().enumerate() {
+                context.push((child, i));
+                self.collect(root_node, context, matches);
+                context.pop();
+            }
+        }
+    }
+
+    // the strings picked out by each selector's ::text or ::attr(), in document
+    // order. selectors without a pseudo-element give the matched element's HTML
+    pub(crate) fn extract(&self, root_node: &Node) -> Vec<String> {
+        let mut values = Vec::new();
+        let mut context = vec![(root_node, 0)];
+        // per selector, whether an ancestor already took the values of the whole
+        // subtree, so nested matches of `li ::text` don't give the same text twice
+        let mut covered = vec![false; self.selectors.len()];
+        self.collect_values(root_node, &mut context, &mut covered, &mut values);
+        values
+    }
+
+    fn collect_values<'a>(&self, root_node: &'a Node, context: &mut Vec<(&'a Node, usize)>, covered: &mut [bool], values: &mut Vec<String>) {
+        let (node, _) = context[context.len() - 1];
+        let Node::Element { attributes, children, .. } = node else {
+            return;
+        };
+        let mut newly_covered = Vec::new();
+        for (i, selector) in self.selectors.iter().enumerate() {
+            if covered[i] || !selector.matches_from(selector.compounds.len() - 1, context, Some(root_node)) {
+                continue;
+            }
+            match (&selector.pseudo_element, selector.pseudo_element_descendants) {
+                (None, _) => values.push(node.to_string()),
+                (Some(PseudoElement::Text), false) => values.extend(children.iter().filter_map(|child| match child {
+                    Node::Text(text) => Some(text.clone()),
+                    Node::Element { .. } => None,
+                })),
+                (Some(PseudoElement::Attr(name)), false) => values.extend(attributes.get(name).cloned()),
+                (Some(PseudoElement::Text), true) => values.extend(pre_order(node).filter_map(|(node, _)| match node {
+                    Node::Text(text) => Some(text.clone()),
+                    Node::Element { .. } => None,
+                })),
+                (Some(PseudoElement::Attr(name)), true) => values.extend(pre_order(node).filter_map(|(node, _)| match node {
+                    Node::Element { attributes, .. } => attributes.get(name).cloned(),
+                    Node::Text(_) => None,
+                })),
+            }
+            if selector.pseudo_element_descendants {
+                covered[i] = true;
+                newly_covered.push(i);
+            }
+        }
+        for (i, child) in children.iter().enumerate() {
+            context.push((child, i));
+            self.collect_values(root_node, context, covered, values);
+            context.pop();
+        }
+        for i in newly_covered {
+            covered[i] = false;
+        }
+    }
+
+    // whether every selector in the list ends in ::text or ::attr()
+    pub(crate) fn extracts_values(&self) -> bool {
+        self.selectors.iter().all(|selector| selector.pseudo_element.is_some())
+    }
+
+    // one index lookup per selector, taken from its rightmost compound (an id if
+    // it has one, then a class, then a tag). None when some selector in the list
+    // could match elements the index can't narrow down, like `*` or `[href]`
+    pub(crate) fn index_keys(&self) -> Option<Vec<IndexKey<'_>>> {
+        self.selectors
+            .iter()
+            .map(|selector| {
+                let simple = &selector.compounds[selector.compounds.len() - 1].simple;
+                simple.iter().find_map(|simple| match simple { Simple::Id(id) => Some(IndexKey::Id(id)), _ => None })
+                    .or_else(|| simple.iter().find_map(|simple| match simple { Simple::Class(class) => Some(IndexKey::Class(class)), _ => None }))
+                    .or_else(|| simple.iter().find_map(|simple| match simple { Simple::Type(name) => Some(IndexKey::Tag(name)), _ => None }))
+            })
+            .collect()
+    }
+
+    // does the node at `path` match, for candidates found without walking the
+    // tree. `scope` is the path of the element :scope refers to
+    pub(crate) fn matches_path(&self, root_node: &Node, path: &[usize], scope: &[usize]) -> bool {
+        let (Some(context), Some(scope)) = (context_at(root_node, path), node_at(root_node, scope)) else {
+            return false;
+        };
+        self.matches_context(&context, Some(scope))
+    }
+
+    // paths of the matching elements inside the element at `start`, in document
+    // order, with :scope being that element. the element itself only counts with
+    // `include_start`
+    pub(crate) fn select_paths(&self, root_node: &Node, start: &[usize], include_start: bool) -> Vec<NodePath> {
+        let mut paths = Vec::new();
+        let Some(mut context) = context_at(root_node, start) else {
+            return paths;
+        };
+        let scope = context[context.len() - 1].0;
+        self.collect_paths(scope, &mut context, &mut start.to_vec(), include_start, &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, scope: &Node, context: &mut Vec<(&Node, usize)>, path: &mut NodePath, include: bool, paths: &mut Vec<NodePath>) {
+        let (node, _) = context[context.len() - 1];
+        if include && self.matches_context(context, Some(scope)) {
+            paths.push(path.clone());
+        }
+        if let Node::Element { children, .. } = node {
+            for (i, child) in children.iter().enumerate() {
+                context.push((child, i));
+                path.push(i);
+                self.collect_paths(scope, context, path, true, paths);
+                path.pop();
+                context.pop();
+            }
+        }
+    }
+
+    // does the last node of the context match. `scope` is what :scope refers to
+    pub(crate) fn matches_context(&self, context: &Context, scope: Option<&Node>) -> bool {
+        self.selectors.iter().any(|selector| selector.matches_from(selector.compounds.len() - 1, context, scope))
+    }
+}
+
+impl ComplexSelector {
+    fn matches_from(&self, index: usize, context: &Context, scope: Option<&Node>) -> bool {
+        if !self.compounds[index].matches(context, scope) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        let depth = context.len();
+        match self.combinators[index - 1] {
+            Combinator::Child => depth > 1 && self.matches_from(index - 1, &context[..depth - 1], scope),
+            Combinator::Descendant => (1..depth).rev().any(|end| self.matches_from(index - 1, &context[..end], scope)),
+            Combinator::NextSibling => previous_element_siblings(context)
+                .next()
+                .is_some_and(|sibling| self.matches_from(index - 1, &sibling, scope)),
+            Combinator::SubsequentSibling => previous_element_siblings(context)
+                .any(|sibling| self.matches_from(index - 1, &sibling, scope)),
+        }
+    }
+}
+
+// lazy pre-order walk that yields matching elements, see SelectorList::matches
+pub struct Matches<'q, 'a> {
+    selectors: Cow<'q, SelectorList>,
+    root_node: &'a Node,
+    context: Vec<(&'a Node, usize)>,
+    // for each node in the context, the next child to visit
+    next_child: Vec<usize>,
+    started: bool,
+}
+
+impl<'q, 'a> Matches<'q, 'a> {
+    pub(crate) fn new(selectors: Cow<'q, SelectorList>, root_node: &'a Node) -> Self {
+        Matches { selectors, root_node, context: Vec::new(), next_child: Vec::new(), started: false }
+    }
+}
+
+impl<'a> Iterator for Matches<'_, 'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        if !self.started {
+            self.started = true;
+            self.context.push((self.root_node, 0));
+            self.next_child.push(0);
+            if self.selectors.matches_context(&self.context, Some(self.root_node)) {
+                return Some(self.root_node);
+            }
+        }
+        loop {
+            let (node, _) = *self.context.last()?;
+            let next = self.next_child.last_mut()?;
+            match node {
+                Node::Element { children, .. } if *next < children.len() => {
+                    let i = *next;
+                    *next += 1;
+                    self.context.push((&children[i], i));
+                    self.next_child.push(0);
+                    if self.selectors.matches_context(&self.context, Some(self.root_node)) {
+                        return Some(&children[i]);
+                    }
+                }
+                _ => {
+                    self.context.pop();
+                    self.next_child.pop();
+                }
+            }
+        }
+    }
+}
+
+// the context for the node at `path`
+fn context_at<'a>(root_node: &'a Node, path: &[usize]) -> Option<Vec<(&'a Node, usize)>> {
+    let mut context = vec![(root_node, 0)];
+    for &i in path {
+        let Node::Element { children, .. } = context[context.len() - 1].0 else { return None };
+        context.push((children.get(i)?, i));
+    }
+    Some(context)
+}
+
+// contexts for the element siblings before the last node, nearest first
+fn previous_element_siblings<'a, 'c>(context: &'c Context<'a>) -> impl Iterator<Item = Vec<(&'a Node, usize)>> + 'c {
+    let depth = context.len();
+    let siblings: &'a [Node] = match context.get(depth.wrapping_sub(2)) {
+        Some((Node::Element { children, .. }, _)) => children,
+        _ => &[],
+    };
+    let index = context[depth - 1].1;
+    siblings[..index.min(siblings.len())]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, sibling)| matches!(sibling, Node::Element { .. }))
+        .map(move |(i, sibling)| {
+            let mut sibling_context = context[..depth - 1].to_vec();
+            sibling_context.push((sibling, i));
+            sibling_context
+        })
+}
+
+impl Compound {
+    fn matches(&self, context: &Context, scope: Option<&Node>) -> bool {
+        let (node, _) = context[context.len() - 1];
+        let Node::Element { tag_name, attributes, .. } = node else {
+            return false;
+        };
+        self.simple.iter().all(|simple| match simple {
+            Simple::Universal => true,
+            Simple::Type(name) => tag_name.eq_ignore_ascii_case(name),
+            Simple::Id(id) => attributes.get("id") == Some(id),
+            Simple::Class(class) => attributes.get("class").is_some_and(|value| value.split_whitespace().any(|word| word == class)),
+            Simple::Attribute(attribute) => attribute.matches(attributes),
+            Simple::Not(list) => !list.matches_context(context, scope),
+            Simple::Is(list) | Simple::Where(list) => list.matches_context(context, scope),
+            Simple::Has(relative) => relative.iter().any(|selector| has_match(selector, context)),
+            Simple::Scope => match scope {
+                Some(scope) => std::ptr::eq(scope, node),
+                None => context.len() == 1,
+            },
+            Simple::NthChild { nth, of, from_end } => {
+                let position = sibling_position(context, *from_end, |sibling_context| match of {
+                    Some(list) => list.matches_context(sibling_context, scope),
+                    None => true,
+                });
+                position.is_some_and(|position| nth.matches(position))
+            }
+            Simple::NthOfType { nth, from_end } => {
+                let position = sibling_position(context, *from_end, |sibling_context| {
+                    matches!(sibling_context[sibling_context.len() - 1].0, Node::Element { tag_name: sibling_tag, .. } if sibling_tag.eq_ignore_ascii_case(tag_name))
+                });
+                position.is_some_and(|position| nth.matches(position))
+            }
+            Simple::Empty => match node {
+                Node::Element { children, .. } => children.iter().all(|child| matches!(child, Node::Text(text) if text.trim().is_empty())),
+                Node::Text(_) => false,
+            },
+            Simple::Root => context.len() == 1,
+        })
+    }
+}
+
+// 1-based position of the last node of the context among the element siblings
+// that pass `counts`, from the front or from the back. None when the node itself
+// doesn't pass. an element without a parent is its own only sibling.
+fn sibling_position<F>(context: &Context, from_end: bool, counts: F) -> Option<i64>
+where
+    F: Fn(&Context) -> bool,
+{
+    if !counts(context) {
+        return None;
+    }
+    let depth = context.len();
+    let Some((Node::Element { children, .. }, _)) = context.get(depth.wrapping_sub(2)) else {
+        return Some(1);
+    };
+    let index = context[depth - 1].1;
+    let mut sibling_context = context.to_vec();
+    let mut position = 1;
+    let others: Box<dyn Iterator<Item = usize>> = if from_end {
+        Box::new(index + 1..children.len())
+    } else {
+        Box::new(0..index)
+    };
+    for i in others {
+        if let Node::Element { .. } = children[i] {
+            sibling_context[depth - 1] = (&children[i], i);
+            if counts(&sibling_context) {
+                position += 1;
+            }
+        }
+    }
+    Some(position)
+}
+
+// :has(). the relative selector starts with a :scope compound, so it only matches
+// elements reached from the anchor through the leading combinator
+fn has_match(selector: &ComplexSelector, context: &Context) -> bool {
+    let anchor = context[context.len() - 1].0;
+    let mut search: Vec<(&Node, usize)> = context.to_vec();
+    match selector.combinators[0] {
+        Combinator::Descendant | Combinator::Child => any_below(selector, anchor, &mut search),
+        Combinator::NextSibling | Combinator::SubsequentSibling => {
+            let depth = context.len();
+            let Some((Node::Element { children, .. }, _)) = context.get(depth.wrapping_sub(2)) else {
+                return false;
+            };
+            search.pop();
+            for (i, sibling) in children.iter().enumerate().skip(context[depth - 1].1 + 1) {
+                search.push((sibling, i));
+                if selector.matches_from(selector.compounds.len() - 1, &search, Some(anchor)) || any_below(selector, anchor, &mut search) {
+                    return true;
+                }
+                search.pop();
+            }
+            false
+        }
+    }
+}
+
+fn any_below(selector: &ComplexSelector, anchor: &Node, context: &mut Vec<(&Node, usize)>) -> bool {
+    let (node, _) = context[context.len() - 1];
+    if let Node::Element { children, .. } = node {
+        for (i, child) in children.iter().enumerate() {
+            context.push((child, i));
+            let found = selector.matches_from(selector.compounds.len() - 1, context, Some(anchor)) || any_below(selector, anchor, context);
+            context.pop();
+            if found {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+struct SelectorParser {
+    chars: Vec<char>,
+    pos: usize,
+    // how many pseudo-class arguments deep we are, pseudo-elements need 0
+    nesting: usize,
+}
+
+impl SelectorParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> QueryError {
+        QueryError { position: self.pos, message: message.to_string() }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), QueryError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' but the selector ended", expected))),
+        }
+    }
+
+    fn selector_list(&mut self) -> Result<SelectorList, QueryError> {
+        let mut selectors = vec![self.complex_selector(None)?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            selectors.push(self.complex_selector(None)?);
+        }
+        Ok(SelectorList { selectors })
+    }
+
+    // the list inside :has(), each one may start with a combinator
+    fn relative_selector_list(&mut self) -> Result<Vec<ComplexSelector>, QueryError> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let leading = self.combinator_symbol().unwrap_or(Combinator::Descendant);
+            self.skip_whitespace();
+            selectors.push(self.complex_selector(Some(leading))?);
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                return Ok(selectors);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn combinator_symbol(&mut self) -> Option<Combinator> {
+        let combinator = match self.peek()? {
+            '>' => Combinator::Child,
+            '+' => Combinator::NextSibling,
+            '~' => Combinator::SubsequentSibling,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(combinator)
+    }
+
+    fn complex_selector(&mut self, leading: Option<Combinator>) -> Result<ComplexSelector, QueryError> {
+        let mut selector = ComplexSelector { compounds: Vec::new(), combinators: Vec::new(), pseudo_element: None, pseudo_element_descendants: false };
+        if let Some(combinator) = leading {
+            selector.compounds.push(Compound { simple: vec![Simple::Scope] });
+            selector.combinators.push(combinator);
+        }
+        if leading.is_none() && self.starts_pseudo_element() {
+            // a bare `::text` is about the scope element itself
+            selector.compounds.push(Compound { simple: vec![Simple::Scope] });
+        } else {
+            selector.compounds.push(self.compound()?);
+        }
+        loop {
+            if self.starts_pseudo_element() {
+                selector.pseudo_element = Some(self.pseudo_element()?);
+                return Ok(selector);
+            }
+            let had_whitespace = self.skip_whitespace();
+            if had_whitespace && self.starts_pseudo_element() {
+                selector.pseudo_element = Some(self.pseudo_element()?);
+                selector.pseudo_element_descendants = true;
+                return Ok(selector);
+            }
+            let combinator = match self.combinator_symbol() {
+                Some(combinator) => combinator,
+                None if had_whitespace && self.starts_compound() => Combinator::Descendant,
+                None => return Ok(selector),
+            };
+            self.skip_whitespace();
+            selector.combinators.push(combinator);
+            selector.compounds.push(self.compound()?);
+        }
+    }
+
+    fn starts_compound(&self) -> bool {
+        matches!(self.peek(), Some(c) if c == '*' || c == '#' || c == '.' || c == '[' || c == ':' || is_ident_char(c) || c == '\\')
+    }
+
+    fn compound(&mut self) -> Result<Compound, QueryError> {
+        let mut simple = Vec::new();
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                simple.push(Simple::Universal);
+            }
+            Some(c) if is_ident_start(c) => simple.push(Simple::Type(self.identifier()?.to_lowercase())),
+            _ => {}
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.pos += 1;
+                    simple.push(Simple::Id(self.identifier()?));
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    simple.push(Simple::Class(self.identifier()?));
+                }
+                Some('[') => simple.push(Simple::Attribute(self.attribute()?)),
+                Some(':') if self.starts_pseudo_element() => break,
+                Some(':') => {
+                    self.nesting += 1;
+                    let pseudo_class = self.pseudo_class();
+                    self.nesting -= 1;
+                    simple.push(pseudo_class?);
+                }
+                _ => break,
+            }
+        }
+        // `li > ::text` is `li > *::text`
+        if simple.is_empty() && self.starts_pseudo_element() {
+            simple.push(Simple::Universal);
+        }
+        if simple.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => self.error(&format!("expected a selector but found '{}'", c)),
+                None => self.error("expected a selector but the selector ended"),
+            });
+        }
+        Ok(Compound { simple })
+    }
+
+    fn starts_pseudo_element(&self) -> bool {
+        self.peek() == Some(':') && self.chars.get(self.pos + 1) == Some(&':')
+    }
+
+    fn pseudo_element(&mut self) -> Result<PseudoElement, QueryError> {
+        let start = self.pos;
+        if self.nesting > 0 {
+            return Err(self.error("pseudo-elements are only allowed at the end of a selector"));
+        }
+        self.pos += 2;
+        let name = self.identifier()?.to_lowercase();
+        match name.as_str() {
+            "text" => Ok(PseudoElement::Text),
+            "attr" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+                let attribute = self.identifier()?;
+                self.skip_whitespace();
+                self.expect(')')?;
+                Ok(PseudoElement::Attr(attribute))
+            }
+            _ => {
+                self.pos = start;
+                Err(self.error(&format!("unsupported pseudo-element '::{}'", name)))
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, QueryError> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.pos += 1;
+                if matches!(self.peek(), None | Some('\n' | '\r' | '\x0c')) {
+                    return Err(self.error("unfinished escape"));
+                }
+                name.extend(self.escape());
+            } else if is_ident_char(c) {
+                name.push(c);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => self.error(&format!("expected a name but found '{}'", c)),
+                None => self.error("expected a name but the selector ended"),
+            });
+        }
+        Ok(name)
+    }
+
+    // what follows a backslash: one to six hex digits and an optional
+    // whitespace character are a code point, so "\\31 23" is "123", and 0,
+    // surrogates and anything past U+10FFFF read as U+FFFD. Any other character
+    // stands for itself
+    fn escape(&mut self) -> Option<char> {
+        let digits: String = self.chars[self.pos..].iter().take(6).take_while(|c| c.is_ascii_hexdigit()).collect();
+        if digits.is_empty() {
+            let escaped = self.peek()?;
+            self.pos += 1;
+            return Some(escaped);
+        }
+        self.pos += digits.len();
+        if self.peek() == Some('\r') && self.chars.get(self.pos + 1) == Some(&'\n') {
+            self.pos += 2;
+        } else if matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r' | '\x0c')) {
+            self.pos += 1;
+        }
+        let code = u32::from_str_radix(&digits, 16).unwrap_or(0);
+        Some(if code == 0 { '\u{FFFD}' } else { char::from_u32(code).unwrap_or('\u{FFFD}') })
+    }
+
+    fn string(&mut self) -> Result<String, QueryError> {
+        let quote = self.peek().unwrap_or('"');
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('\\') => {
+                    self.pos += 1;
+                    // a backslash before a line break continues the string on the next line
+                    if self.peek() == Some('\r') && self.chars.get(self.pos + 1) == Some(&'\n') {
+                        self.pos += 2;
+                    } else if matches!(self.peek(), Some('\n' | '\r' | '\x0c')) {
+                        self.pos += 1;
+                    } else {
+                        value.extend(self.escape());
+                    }
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn attribute(&mut self) -> Result<AttributeSelector, QueryError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let name = self.identifier()?;
+        self.skip_whitespace();
+        let operator = match self.peek() {
+            Some(']') => None,
+            Some('=') => Some(AttributeOperator::Equals),
+            Some(c) => {
+                let operator = match c {
+                    '~' => AttributeOperator::Includes,
+                    '|' => AttributeOperator::DashMatch,
+                    '^' => AttributeOperator::Prefix,
+                    '$' => AttributeOperator::Suffix,
+                    '*' => AttributeOperator::Substring,
+                    _ => return Err(self.error(&format!("unknown attribute operator '{}'", c))),
+                };
+                self.pos += 1;
+                if self.peek() != Some('=') {
+                    return Err(self.error(&format!("expected '=' after '{}'", c)));
+                }
+                Some(operator)
+            }
+            None => return Err(self.error("unterminated attribute selector")),
+        };
+        let mut selector = AttributeSelector { name, operator: None, case_insensitive: false };
+        if let Some(operator) = operator {
+            self.pos += 1; // '='
+            self.skip_whitespace();
+            let value = match self.peek() {
+                Some('"') | Some('\'') => self.string()?,
+                _ => self.identifier()?,
+            };
+            selector.operator = Some((operator, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some('i') | Some('I') => {
+                    selector.case_insensitive = true;
+                    self.pos += 1;
+                }
+                Some('s') | Some('S') => self.pos += 1,
+                _ => {}
+            }
+            self.skip_whitespace();
+        }
+        self.expect(']')?;
+        Ok(selector)
+    }
+
+    fn pseudo_class(&mut self) -> Result<Simple, QueryError> {
+        let start = self.pos;
+        self.expect(':')?;
+        let name = self.identifier()?.to_lowercase();
+        let simple = match name.as_str() {
+            "scope" => Simple::Scope,
+            "not" | "is" | "where" | "matches" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+                let list = self.selector_list()?;
+                self.skip_whitespace();
+                self.expect(')')?;
+                match name.as_str() {
+                    "not" => Simple::Not(list),
+                    "where" => Simple::Where(list),
+                    _ => Simple::Is(list),
+                }
+            }
+            "has" => {
+                self.expect('(')?;
+                let list = self.relative_selector_list()?;
+                self.skip_whitespace();
+                self.expect(')')?;
+                Simple::Has(list)
+            }
+            "root" => Simple::Root,
+            "empty" => Simple::Empty,
+            "first-child" => Simple::NthChild { nth: Nth::FIRST, of: None, from_end: false },
+            "last-child" => Simple::NthChild { nth: Nth::FIRST, of: None, from_end: true },
+            "first-of-type" => Simple::NthOfType { nth: Nth::FIRST, from_end: false },
+            "last-of-type" => Simple::NthOfType { nth: Nth::FIRST, from_end: true },
+            "only-child" => Simple::Is(SelectorList {
+                selectors: vec![ComplexSelector {
+                    compounds: vec![Compound { simple: vec![
+                        Simple::NthChild { nth: Nth::FIRST, of: None, from_end: false },
+                        Simple::NthChild { nth: Nth::FIRST, of: None, from_end: true },
+                    ] }],
+                    combinators: vec![],
+                    pseudo_element: None,
+                    pseudo_element_descendants: false,
+                }],
+            }),
+            "only-of-type" => Simple::Is(SelectorList {
+                selectors: vec![ComplexSelector {
+                    compounds: vec![Compound { simple: vec![
+                        Simple::NthOfType { nth: Nth::FIRST, from_end: false },
+                        Simple::NthOfType { nth: Nth::FIRST, from_end: true },
+                    ] }],
+                    combinators: vec![],
+                    pseudo_element: None,
+                    pseudo_element_descendants: false,
+                }],
+            }),
+            "nth-child" | "nth-last-child" => {
+                self.expect('(')?;
+                let nth = self.nth()?;
+                self.skip_whitespace();
+                let mut of = None;
+                if self.chars[self.pos..].starts_with(&['o', 'f']) && self.chars.get(self.pos + 2).is_some_and(|c| c.is_whitespace()) {
+                    self.pos += 2;
+                    self.skip_whitespace();
+                    of = Some(self.selector_list()?);
+                    self.skip_whitespace();
+                }
+                self.expect(')')?;
+                Simple::NthChild { nth, of, from_end: name == "nth-last-child" }
+            }
+            "nth-of-type" | "nth-last-of-type" => {
+                self.expect('(')?;
+                let nth = self.nth()?;
+                self.skip_whitespace();
+                self.expect(')')?;
+                Simple::NthOfType { nth, from_end: name == "nth-last-of-type" }
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error(&format!("unsupported pseudo-class ':{}'", name)));
+            }
+        };
+        Ok(simple)
+    }
+}
+
+impl SelectorParser {
+    // An+B, odd or even, e.g. "2n+1", "-n + 3", "n", "4"
+    fn nth(&mut self) -> Result<Nth, QueryError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+            word.push(c.to_ascii_lowercase());
+            self.pos += 1;
+        }
+        match word.as_str() {
+            "odd" => return Ok(Nth { a: 2, b: 1 }),
+            "even" => return Ok(Nth { a: 2, b: 0 }),
+            _ => self.pos = start,
+        }
+
+        let sign = self.sign();
+        let digits = self.digits();
+        if self.peek().is_some_and(|c| c == 'n' || c == 'N') {
+            self.pos += 1;
+            let a = sign * digits.unwrap_or(1);
+            let after_n = self.pos;
+            self.skip_whitespace();
+            let b = match self.peek() {
+                Some('+') | Some('-') => {
+                    let sign = self.sign();
+                    self.skip_whitespace();
+                    match self.digits() {
+                        Some(digits) => sign * digits,
+                        None => return Err(self.error("expected a number after the sign")),
+                    }
+                }
+                _ => {
+                    self.pos = after_n;
+                    0
+                }
+            };
+            return Ok(Nth { a, b });
+        }
+        match digits {
+            Some(digits) => Ok(Nth { a: 0, b: sign * digits }),
+            None => {
+                self.pos = start;
+                Err(self.error("expected An+B, odd or even"))
+            }
+        }
+    }
+
+    fn sign(&mut self) -> i64 {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                -1
+            }
+            Some('+') => {
+                self.pos += 1;
+                1
+            }
+            _ => 1,
+        }
+    }
+
+    fn digits(&mut self) -> Option<i64> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '-' || !c.is_ascii()
+}
+
+fn is_ident_char(c: char) -> bool {
+    is_ident_start(c) || c.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selector::*;
+
+    #[test]
+    fn test_parse_structure() {
+        let list = parse_selector("div.product > h2 a[href], #main").unwrap();
+        assert_eq!(list.selectors.len(), 2);
+        let first = &list.selectors[0];
+        assert_eq!(first.combinators, vec![Combinator::Child, Combinator::Descendant]);
+        assert_eq!(first.compounds[0].simple, vec![Simple::Type("div".to_string()), Simple::Class("product".to_string())]);
+        assert_eq!(list.selectors[1].compounds[0].simple, vec![Simple::Id("main".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_attribute_operators() {
+        let list = parse_selector(r#"[a="x y" i][b~=c][d|='en'][e^=f][g$=h][i*=j]"#).unwrap();
+        let simple = &list.selectors[0].compounds[0].simple;
+        let operators: Vec<AttributeOperator> = simple.iter().map(|s| match s {
+            Simple::Attribute(AttributeSelector { operator: Some((operator, _)), .. }) => *operator,
+            _ => panic!("expected an attribute selector"),
+        }).collect();
+        assert_eq!(operators, vec![
+            AttributeOperator::Equals, AttributeOperator::Includes, AttributeOperator::DashMatch,
+            AttributeOperator::Prefix, AttributeOperator::Suffix, AttributeOperator::Substring,
+        ]);
+        assert!(matches!(&simple[0], Simple::Attribute(a) if a.case_insensitive && a.operator.as_ref().unwrap().1 == "x y"));
+    }
+
+    fn nth_of(argument: &str) -> Nth {
+        match &parse_selector(&format!(":nth-child({})", argument)).unwrap().selectors[0].compounds[0].simple[0] {
+            Simple::NthChild { nth, .. } => *nth,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_nth() {
+        assert_eq!(nth_of("odd"), Nth { a: 2, b: 1 });
+        assert_eq!(nth_of("EVEN"), Nth { a: 2, b: 0 });
+        assert_eq!(nth_of("3"), Nth { a: 0, b: 3 });
+        assert_eq!(nth_of("n"), Nth { a: 1, b: 0 });
+        assert_eq!(nth_of("-n+3"), Nth { a: -1, b: 3 });
+        assert_eq!(nth_of(" 2n - 1 "), Nth { a: 2, b: -1 });
+        assert_eq!(nth_of("+3n+2"), Nth { a: 3, b: 2 });
+        assert!(parse_selector(":nth-child(x)").is_err());
+        assert!(parse_selector(":nth-child(2n+)").is_err());
+
+        assert!(Nth { a: -1, b: 3 }.matches(1));
+        assert!(Nth { a: -1, b: 3 }.matches(3));
+        assert!(!Nth { a: -1, b: 3 }.matches(4));
+        assert!(Nth { a: 2, b: 1 }.matches(5));
+        assert!(!Nth { a: 2, b: 1 }.matches(4));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_selector("div >").unwrap_err(), "expected a selector but the selector ended at position 5");
+        assert_eq!(parse_selector("a[href").unwrap_err(), "unterminated attribute selector at position 6");
+        assert_eq!(parse_selector("p:hover").unwrap_err(), "unsupported pseudo-class ':hover' at position 1");
+        assert!(parse_selector("").is_err());
+        assert!(parse_selector("a,,b").is_err());
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let simple = |selector: &str| parse_selector(selector).unwrap().selectors[0].compounds[0].simple.clone();
+        assert_eq!(simple("#\\31 23"), vec![Simple::Id("123".to_string())]);
+        assert_eq!(simple("#\\31\n23"), vec![Simple::Id("123".to_string())]);
+        assert_eq!(simple(".\\000031x"), vec![Simple::Class("1x".to_string())]);
+        assert_eq!(simple(".a\\:b.c\\.d"), vec![Simple::Class("a:b".to_string()), Simple::Class("c.d".to_string())]);
+        assert_eq!(simple(".\\E9t\\E9  p"), vec![Simple::Class("été".to_string())]);
+        assert_eq!(simple(".\\0 x"), vec![Simple::Class("\u{FFFD}x".to_string())]);
+        assert!(matches!(&simple("[title='\\26 \\'\\\n!']")[0], Simple::Attribute(a) if a.operator.as_ref().unwrap().1 == "&'!"));
+        assert!(parse_selector("#a\\").is_err());
+    }
+}
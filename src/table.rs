@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use crate::Node;
+use crate::traversal::pre_order;
+use crate::util::{attribute, children_of, collapsed_text, is_tag, tag_of};
+
+// <table> to a rectangular grid of cell texts.
+//
+// Rows come from the table's own <tr>s, directly or inside <thead>, <tbody> and
+// <tfoot>, never from tables nested in a cell. A cell spanning several columns
+// or rows is copied into every slot it covers, and short rows are padded with
+// empty strings, so every row ends up as wide as the widest one. Like in
+// browsers, a rowspan stops at the end of the cell's row group (its <thead>,
+// <tbody> or <tfoot>, or a run of <tr>s directly in the table).
+//
+// Header rows are the rows in <thead>, or when there is no <thead>, the leading
+// rows made only of <th> cells. Stacked header rows are joined per column:
+// "Price" over "USD" becomes the header "Price / USD".
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub caption: Option<String>,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+// a <tr> and whether it is part of the header
+struct RawRow<'a> {
+    cells: Vec<&'a Node>,
+    in_thead: bool,
+    // rows of the same row group share this
+    group: usize,
+}
+
+// colspan/rowspan, with the limits browsers use
+fn span(node: &Node, name: &str, max: usize) -> usize {
+    match attribute(node, name).and_then(|value| value.trim().parse::<usize>().ok()) {
+        Some(value) => value.min(max),
+        None => 1,
+    }
+}
+
+fn collect_rows<'a>(table: &'a Node) -> (Vec<RawRow<'a>>, Option<&'a Node>) {
+    let mut head = Vec::new();
+    let mut body = Vec::new();
+    let mut foot = Vec::new();
+    let mut caption = None;
+    let row = |tr: &'a Node, in_thead: bool, group: usize| RawRow {
+        cells: children_of(tr).iter().filter(|cell| is_tag(cell, "td") || is_tag(cell, "th")).collect(),
+        in_thead,
+        group,
+    };
+    // the index of the table child that started the current row group
+    let mut group = 0;
+    let mut in_bare_rows = false;
+    for (i, child) in children_of(table).iter().enumerate() {
+        let tag = tag_of(child);
+        if !(in_bare_rows && tag == "tr") {
+            group = i;
+        }
+        in_bare_rows = tag == "tr";
+        let rows = || children_of(child).iter().filter(|tr| is_tag(tr, "tr"));
+        match tag.as_str() {
+            "tr" => body.push(row(child, false, group)),
+            "thead" => head.extend(rows().map(|tr| row(tr, true, group))),
+            "tbody" => body.extend(rows().map(|tr| row(tr, false, group))),
+            "tfoot" => foot.extend(rows().map(|tr| row(tr, false, group))),
+            "caption" if caption.is_none() => caption = Some(child),
+            _ => {}
+        }
+    }
+    head.extend(body);
+    head.extend(foot);
+    (head, caption)
+}
+
+// the first table at or under `node`, empty if there is none
+pub fn extract_table(node: &Node) -> Table {
+    match pre_order(node).map(|(node, _)| node).find(|node| is_tag(node, "table")) {
+        Some(table) => build_table(table),
+        None => Table::default(),
+    }
+}
+
+// every table at or under `node`, nested ones included, in document order
+pub fn extract_tables(node: &Node) -> Vec<Table> {
+    pre_order(node).map(|(node, _)| node).filter(|node| is_tag(node, "table")).map(build_table).collect()
+}
+
+// a cell placed on the grid: the slot it starts at and how many columns it covers
+pub(crate) struct PlacedCell<'a> {
+    pub(crate) node: &'a Node,
+    pub(crate) row: usize,
+    pub(crate) column: usize,
+    pub(crate) colspan: usize,
+}
+
+// what extract_table and the table renderers in markdown.rs and plaintext.rs
+// all lay out the same way
+pub(crate) struct Layout<'a> {
+    pub(crate) caption: Option<&'a Node>,
+    pub(crate) cells: Vec<PlacedCell<'a>>,
+    // grid[row][column] is the index in `cells` of the cell covering that slot,
+    // None where no cell does. Every row is as wide as the widest one
+    pub(crate) grid: Vec<Vec<Option<usize>>>,
+    // how many of the leading rows make up the header
+    pub(crate) header_rows: usize,
+}
+
+pub(crate) fn layout(table: &Node) -> Layout<'_> {
+    let (raw_rows, caption) = collect_rows(table);
+
+    let mut cells = Vec::new();
+    let mut grid: Vec<Vec<Option<usize>>> = vec![Vec::new(); raw_rows.len()];
+    for (r, raw) in raw_rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in &raw.cells {
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+            let colspan = span(cell, "colspan", 1000).max(1);
+            // rowspan=0 means down to the last row of the row group
+            let rows_left = raw_rows[r..].iter().take_while(|row| row.group == raw.group).count();
+            let rowspan = match span(cell, "rowspan", 65534) {
+                0 => rows_left,
+                rowspan => rowspan.min(rows_left),
+            };
+            for row in grid.iter_mut().skip(r).take(rowspan) {
+                if row.len() < column + colspan {
+                    row.resize(column + colspan, None);
+                }
+                for slot in &mut row[column..column + colspan] {
+                    *slot = Some(cells.len());
+                }
+            }
+            cells.push(PlacedCell { node: cell, row: r, column, colspan });
+            column += colspan;
+        }
+    }
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut grid {
+        row.resize(width, None);
+    }
+
+    let has_thead = raw_rows.iter().any(|raw| raw.in_thead);
+    let header_rows = if has_thead {
+        raw_rows.iter().take_while(|raw| raw.in_thead).count()
+    } else {
+        raw_rows.iter().take_while(|raw| !raw.cells.is_empty() && raw.cells.iter().all(|cell| is_tag(cell, "th"))).count()
+    };
+    Layout { caption, cells, grid, header_rows }
+}
+
+fn build_table(table: &Node) -> Table {
+    let layout = layout(table);
+    let texts: Vec<String> = layout.cells.iter().map(|cell| collapsed_text(cell.node)).collect();
+    let width = layout.grid.first().map(Vec::len).unwrap_or(0);
+    // a spanning cell's text goes in every slot it covers
+    let mut rows: Vec<Vec<String>> = layout
+        .grid
+        .iter()
+        .map(|row| row.iter().map(|slot| slot.map(|index| texts[index].clone()).unwrap_or_default()).collect())
+        .collect();
+
+    let header_rows: Vec<Vec<String>> = rows.drain(..layout.header_rows).collect();
+    let headers = (0..if header_rows.is_empty() { 0 } else { width })
+        .map(|column| {
+            let mut parts: Vec<&str> = Vec::new();
+            for row in &header_rows {
+                let part = row[column].as_str();
+                if !part.is_empty() && parts.last() != Some(&part) {
+                    parts.push(part);
+                }
+            }
+            parts.join(" / ")
+        })
+        .collect();
+
+    Table { caption: layout.caption.map(collapsed_text), headers, rows }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Table {
+    pub fn width(&self) -> usize {
+        self.headers.len().max(self.rows.first().map(Vec::len).unwrap_or(0))
+    }
+
+    // the header for each column. blank or repeated headers get made unique
+    // ("column 3", "Price (2)") so no cell gets lost in to_records
+    pub fn column_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for column in 0..self.width() {
+            let base = match self.headers.get(column) {
+                Some(header) if !header.is_empty() => header.clone(),
+                _ => format!("column {}", column + 1),
+            };
+            let mut name = base.clone();
+            let mut copy = 2;
+            while names.contains(&name) {
+                name = format!("{} ({})", base, copy);
+                copy += 1;
+            }
+            names.push(name);
+        }
+        names
+    }
+
+    // one map per body row, keyed by column_names
+    pub fn to_records(&self) -> Vec<HashMap<String, String>> {
+        let names = self.column_names();
+        self.rows.iter().map(|row| names.iter().cloned().zip(row.iter().cloned()).collect()).collect()
+    }
+
+    // RFC 4180 style, with the headers as the first line when there are any
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        let mut lines: Vec<&Vec<String>> = Vec::new();
+        if !self.headers.is_empty() {
+            lines.push(&self.headers);
+        }
+        lines.extend(&self.rows);
+        for line in lines {
+            out.push_str(&line.iter().map(|value| csv_field(value)).collect::<Vec<_>>().join(","));
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::table::*;
+    use crate::html;
+    use crate::tokenizer::tokenize;
+    use crate::parser::graph_creator;
+
+    #[test]
+    fn test_spans_and_headers() {
+        let node = html! {
+            table {
+                caption { "Prices" }
+                thead {
+                    tr { th(rowspan = 2) { "Item" } th(colspan = 2) { "Price" } }
+                    tr { th { "USD" } th { "EUR" } }
+                }
+                tbody {
+                    tr { td(rowspan = 2) { "Widget" } td { "1" } td { "0.9" } }
+                    tr { td(colspan = 2) { "n/a" } }
+                    tr { td { "Gadget,  large" } td { "2" } }
+                }
+            }
+        };
+        let table = extract_table(&node);
+        assert_eq!(table.caption.as_deref(), Some("Prices"));
+        assert_eq!(table.headers, vec!["Item", "Price / USD", "Price / EUR"]);
+        assert_eq!(table.rows, vec![
+            vec!["Widget", "1", "0.9"],
+            vec!["Widget", "n/a", "n/a"],
+            vec!["Gadget, large", "2", ""],
+        ]);
+        assert_eq!(table.to_records()[2]["Price / USD"], "2");
+        assert_eq!(table.to_csv(), "Item,Price / USD,Price / EUR\r\nWidget,1,0.9\r\nWidget,n/a,n/a\r\n\"Gadget, large\",2,\r\n");
+    }
+
+    #[test]
+    fn test_th_header_row_and_nested_tables() {
+        let node = html! {
+            div {
+                table(id = "outer") {
+                    tr { th { "Name" } th { "Name" } th }
+                    tr { td { "a" } td { table { tr { td { "inner" } } } } td { "\"q\"" } }
+                }
+            }
+        };
+        let table = extract_table(&node);
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(table.rows[0], vec!["a", "inner", "\"q\""]);
+        assert_eq!(table.column_names(), vec!["Name", "Name (2)", "column 3"]);
+        assert_eq!(table.to_csv().lines().nth(1), Some("a,inner,\"\"\"q\"\"\""));
+        assert_eq!(extract_tables(&node).len(), 2);
+        assert!(extract_table(&html! { p }).rows.is_empty());
+    }
+
+    #[test]
+    fn test_parsed_table_without_header() {
+        let html = "<table><tr><td>1</td><td rowspan=\"0\">all</td></tr><tr><td>2</td></tr><tr><td>3</td></tr></table>";
+        let node = graph_creator(&mut tokenize(html));
+        let table = extract_table(&node);
+        assert!(table.headers.is_empty());
+        assert_eq!(table.rows, vec![vec!["1", "all"], vec!["2", "all"], vec!["3", "all"]]);
+        assert_eq!(table.to_records()[1]["column 2"], "all");
+    }
+
+    #[test]
+    fn test_rowspan_stays_in_its_row_group() {
+        let node = html! {
+            table {
+                thead { tr { th(rowspan = 3) { "Item" } th { "Qty" } } }
+                tbody {
+                    tr { td(rowspan = 0) { "Widget" } td { "1" } }
+                    tr { td { "2" } }
+                }
+                tfoot { tr { td { "Total" } td { "3" } } }
+            }
+        };
+        let table = extract_table(&node);
+        assert_eq!(table.headers, vec!["Item", "Qty"]);
+        assert_eq!(table.rows, vec![vec!["Widget", "1"], vec!["Widget", "2"], vec!["Total", "3"]]);
+    }
+
+    #[test]
+    fn test_cell_entities_are_decoded() {
+        let node = graph_creator(&mut tokenize("<TABLE><TR><TH>Fish &amp; chips</TH></TR><TR><TD>&pound;4</TD></TR></TABLE>"));
+        let table = extract_table(&node);
+        assert_eq!(table.headers, vec!["Fish & chips"]);
+        assert_eq!(table.rows, vec![vec!["£4"]]);
+    }
+}
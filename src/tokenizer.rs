@@ -17,21 +17,107 @@ pub enum Tokens {
 
 
 
+pub const VOID_ELEMENTS:[&str;14]  = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+
+// where a token starts in the input, both 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+  pub line: usize,
+  pub column: usize,
+}
+
+// a peekable char iterator that knows how many bytes it has consumed and,
+// when asked to, the line and column of the next char
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    offset: usize,
+    position: Option<Position>,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+        if let Some(position) = &mut self.position {
+            if c == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+        }
+        Some(c)
+    }
+}
+
 // loop through string one character at a time and tokenize it based on what we see
 
 pub fn tokenize(input: &str) -> VecDeque<Tokens> {
+    tokenize_from(Cursor { chars: input.chars().peekable(), offset: 0, position: None }, input).0
+}
+
+// where `</name` starts in `text`, ignoring case, without copying the text
+fn find_end_tag(text: &str, name: &str) -> Option<usize> {
+    let pattern = format!("</{}", name);
+    text.as_bytes().windows(pattern.len()).position(|window| window.eq_ignore_ascii_case(pattern.as_bytes()))
+}
+
+// same as tokenize, plus the start position of every token (same length as the tokens)
+pub fn tokenize_with_positions(input: &str) -> (VecDeque<Tokens>, Vec<Position>) {
+    let start = Position { line: 1, column: 1 };
+    tokenize_from(Cursor { chars: input.chars().peekable(), offset: 0, position: Some(start) }, input)
+}
+
+// positions are only collected when the cursor tracks them
+fn tokenize_from<'a>(mut chars: Cursor<'a>, input: &'a str) -> (VecDeque<Tokens>, Vec<Position>) {
     let mut needs_self_closing = false;
+    // between the < of a tag and its >, where words are attribute names
+    let mut in_tag = false;
     let mut tokens:VecDeque<Tokens> = VecDeque::new();
-    let mut chars = input.chars().peekable();
-    const VOID_ELEMENTS:[&str;14]  = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+    let mut positions: Vec<Position> = Vec::new();
+    let mut start_position = chars.position;
+    // set right after the start tag of script/style has been read
+    let mut raw_text_element: Option<&str> = None;
     // println!("Entering main loop");
-    while let Some(c) = chars.next() {
+    loop {
+        // whatever the last round pushed started where that round started
+        if let Some(position) = start_position {
+            positions.resize(tokens.len(), position);
+        }
+        let start = chars.offset;
+        start_position = chars.position;
+        // script and style hold raw text: quotes, < and the like are kept as they
+        // are up to the matching end tag, so inline JSON and JS survive intact
+        if matches!(tokens.back(), Some(Tokens::SelfClosingTagEnd)) {
+            raw_text_element = None;
+        }
+        if matches!(tokens.back(), Some(Tokens::GreaterThan)) && let Some(name) = raw_text_element.take() {
+            let end = start + find_end_tag(&input[start..], name).unwrap_or(input.len() - start);
+            let text = &input[start..end];
+            while chars.offset < end {
+                chars.next();
+            }
+            if !text.trim().is_empty() {
+                tokens.push_back(Tokens::Text(text.to_string()));
+            }
+            continue;
+        }
+        let Some(c) = chars.next() else { break };
         match c {
             // opening tag or closing tag
             '<' => {
                 if chars.peek() == Some(&'/') {
                     chars.next(); // consume '/'
                     tokens.push_back(Tokens::ClosingTag);
+                    in_tag = true;
                 } 
                 else if chars.peek() == Some(&'!') {
                   // Handle comments or DOCTYPE
@@ -54,16 +140,18 @@ pub fn tokenize(input: &str) -> VecDeque<Tokens> {
                       }
                   }
                   // println!("Entering loop for comment or DOCTYPE");
-                  while let Some(next_char) = chars.next() {
+                  for next_char in chars.by_ref() {
                       if next_char == '>' {
                           break;
                       }
                   } 
                 }else {
                     tokens.push_back(Tokens::LessThan);
+                    in_tag = true;
                 }
             },
-            '>' => {
+            '>' if in_tag => {
+              in_tag = false;
               if needs_self_closing {
                 needs_self_closing = false;
                 tokens.push_back(Tokens::SelfClosingTagEnd);
@@ -75,12 +163,13 @@ pub fn tokenize(input: &str) -> VecDeque<Tokens> {
               }
             
             },
-            '=' => tokens.push_back(Tokens::Equals),
-            '"' => {
+            '=' if in_tag => tokens.push_back(Tokens::Equals),
+            '"' | '\'' if in_tag => {
                 // println!("Entering loop in quotes");
+                // a value ends at the quote it started with, so class='it"s' keeps its "
                 let mut string_value = String::new();
                 while let Some(&next_char) = chars.peek() {
-                    if next_char == '"' {
+                    if next_char == c {
                         chars.next(); // consume closing quote
                         break;
                     }
@@ -91,38 +180,67 @@ pub fn tokenize(input: &str) -> VecDeque<Tokens> {
             // self closing tag
             '/' if chars.peek() == Some(&'>') => {
                 chars.next(); // consume '>'
+                in_tag = false;
                 tokens.push_back(Tokens::SelfClosingTagEnd);
                 needs_self_closing = false; // reset the flag
             },
             // need to store all text
             _ => {
               // store identifier
+              // whitespace only counts when it leads into text, like the space in
+              // `<b>bold</b> text`; between tags and inside them it's skipped
               if c.is_whitespace() {
-                  continue; // skip whitespace
+                  let rest = input[start..].trim_start();
+                  if in_tag || rest.is_empty() || rest.starts_with('<') {
+                      // skip the whole run at once
+                      while chars.peek().is_some_and(|next_char| next_char.is_whitespace()) {
+                          chars.next();
+                      }
+                      continue;
+                  }
+              }
+              // an unquoted attribute value, like rowspan=2
+              if in_tag && matches!(tokens.back(), Some(Tokens::Equals)) {
+                  let mut value = c.to_string();
+                  while let Some(&next_char) = chars.peek() && !next_char.is_whitespace() && !matches!(next_char, '>' | '"' | '\'') {
+                      value.push(next_char);
+                      chars.next();
+                  }
+                  tokens.push_back(Tokens::String(value));
+                  continue;
               }
               let mut identifier = String::new();
               
               identifier.push(c);
               while let Some(&next_char) = chars.peek() 
               {
-                  if (next_char.is_whitespace() &&  matches!(tokens.back(), Some(Tokens::LessThan))) || next_char == '/' || next_char == '>' || next_char == '<' || next_char == '=' || next_char == '"' 
+                  // outside tags only < ends text, so quotes, = and > stay in it
+                  if next_char == '<' || (in_tag && (next_char.is_whitespace() || next_char == '/' || next_char == '>' || next_char == '=' || next_char == '"' || next_char == '\''))
                   {
                       break;
                   }
                   identifier.push(chars.next().unwrap());
               }
+              // every other word in a tag is an attribute name, with or without a value
+              if in_tag && !matches!(tokens.back(), Some(Tokens::LessThan) | Some(Tokens::ClosingTag)) {
+                  tokens.push_back(Tokens::Attribute(identifier));
+                  continue;
+              }
               if let Some(next_char) = chars.peek()
               {
                 match next_char
                 {
                   c if c.is_whitespace() && matches!(tokens.back(),Some(Tokens::Identifier(_))) => tokens.push_back(Tokens::Attribute(identifier)),
                   '=' => tokens.push_back(Tokens::Attribute(identifier)),
-                  x if matches!(tokens.back(), Some(Tokens::LessThan)) || matches!(tokens.back(), Some(Tokens::ClosingTag)) => 
+                  _ if matches!(tokens.back(), Some(Tokens::LessThan)) || matches!(tokens.back(), Some(Tokens::ClosingTag)) => 
                   {
                     if VOID_ELEMENTS.contains(&identifier.as_str()) {
                       needs_self_closing = true;
                       // println!("Detected void element: {}", identifier);
                     }
+                    if matches!(tokens.back(), Some(Tokens::LessThan)) {
+                      raw_text_element = ["script", "style"].into_iter().find(|name| identifier.eq_ignore_ascii_case(name));
+                    }
                     tokens.push_back(Tokens::Identifier(identifier));
                   },
                   _ => {
@@ -134,7 +252,8 @@ pub fn tokenize(input: &str) -> VecDeque<Tokens> {
     }
     
     tokens.push_back(Tokens::EOF); // End of File marker
-    tokens    
+    positions.extend(chars.position);
+    (tokens, positions)
 }
 
 #[cfg(test)]
@@ -325,4 +444,84 @@ mod tests {
         }
     }
   }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_raw_text_and_bare_attributes() {
+      let result: Vec<String> = tokenize(r#"<div itemscope id=main><script type="application/ld+json">{"a": "<b>"}</script><input disabled></div>"#)
+        .iter()
+        .map(|token| format!("{:?}", token))
+        .collect();
+      assert_eq!(result, vec![
+        "LessThan", "Identifier(\"div\")", "Attribute(\"itemscope\")", "Attribute(\"id\")", "Equals", "String(\"main\")", "GreaterThan",
+        "LessThan", "Identifier(\"script\")", "Attribute(\"type\")", "Equals", "String(\"application/ld+json\")", "GreaterThan",
+        "Text(\"{\\\"a\\\": \\\"<b>\\\"}\")",
+        "ClosingTag", "Identifier(\"script\")", "GreaterThan",
+        "LessThan", "Identifier(\"input\")", "Attribute(\"disabled\")", "SelfClosingTagEnd",
+        "ClosingTag", "Identifier(\"div\")", "GreaterThan", "EOF",
+      ]);
+    }
+
+    #[test]
+    fn test_single_and_mixed_quotes() {
+      let result: Vec<String> = tokenize(r#"<div class='a b' id=x data-x='1' title="it's" alt='say "hi"'>t</div>"#)
+        .iter()
+        .map(|token| format!("{:?}", token))
+        .collect();
+      assert_eq!(result, vec![
+        "LessThan", "Identifier(\"div\")",
+        "Attribute(\"class\")", "Equals", "String(\"a b\")",
+        "Attribute(\"id\")", "Equals", "String(\"x\")",
+        "Attribute(\"data-x\")", "Equals", "String(\"1\")",
+        "Attribute(\"title\")", "Equals", "String(\"it's\")",
+        "Attribute(\"alt\")", "Equals", "String(\"say \\\"hi\\\"\")",
+        "GreaterThan", "Text(\"t\")", "ClosingTag", "Identifier(\"div\")", "GreaterThan", "EOF",
+      ]);
+      let node = crate::parser::graph_creator(&mut tokenize("<p class='x y' id=z>it's</p>"));
+      match node {
+        crate::Node::Element { attributes, .. } => {
+          assert_eq!(attributes.get("class").map(String::as_str), Some("x y"));
+          assert_eq!(attributes.get("id").map(String::as_str), Some("z"));
+          assert_eq!(attributes.len(), 2);
+        }
+        _ => panic!("expected an element"),
+      }
+    }
+
+    #[test]
+    fn test_raw_text_end_tag_any_case() {
+      let result: Vec<String> = tokenize("<STYLE>p > a { color: red }</Style><p>x</p>")
+        .iter()
+        .map(|token| format!("{:?}", token))
+        .collect();
+      assert_eq!(result, vec![
+        "LessThan", "Identifier(\"STYLE\")", "GreaterThan", "Text(\"p > a { color: red }\")",
+        "ClosingTag", "Identifier(\"Style\")", "GreaterThan",
+        "LessThan", "Identifier(\"p\")", "GreaterThan", "Text(\"x\")", "ClosingTag", "Identifier(\"p\")", "GreaterThan", "EOF",
+      ]);
+      assert_eq!(find_end_tag("let a = 1; </SCRIPT>", "script"), Some(11));
+      assert_eq!(find_end_tag("unterminated", "script"), None);
+    }
+
+    #[test]
+    fn test_text_keeps_quotes_and_leading_space() {
+      let result: Vec<String> = tokenize("<p>He said \"hi\", a = b > c<b>x</b> y\n</p>\n<br>")
+        .iter()
+        .filter_map(|token| match token {
+          Tokens::Text(text) => Some(text.clone()),
+          _ => None,
+        })
+        .collect();
+      assert_eq!(result, vec!["He said \"hi\", a = b > c", "x", " y\n"]);
+    }
+
+    #[test]
+    fn test_positions_count_chars_not_bytes() {
+      let (tokens, positions) = tokenize_with_positions("<p>é ü</p>\n<i>x</i>");
+      assert_eq!(tokens.len(), positions.len());
+      let at = |line, column| Position { line, column };
+      assert_eq!(positions, vec![
+        at(1, 1), at(1, 2), at(1, 3), at(1, 4), at(1, 7), at(1, 9), at(1, 10),
+        at(2, 1), at(2, 2), at(2, 3), at(2, 4), at(2, 5), at(2, 7), at(2, 8), at(2, 9),
+      ]);
+    }
+}
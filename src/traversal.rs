@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use crate::Node;
+
+// Lazy walks over the tree. Every iterator yields the node together with its
+// depth, where the node the walk starts from has depth 0. Nothing is collected
+// up front, so stopping early skips the rest of the tree.
+
+pub struct PreOrder<'a> {
+    stack: Vec<(&'a Node, usize)>,
+}
+
+pub struct PostOrder<'a> {
+    // the bool says whether the children of the node were already pushed
+    stack: Vec<(&'a Node, usize, bool)>,
+}
+
+pub struct BreadthFirst<'a> {
+    queue: VecDeque<(&'a Node, usize)>,
+}
+
+// parents before children, children left to right (document order)
+pub fn pre_order(root_node: &Node) -> PreOrder<'_> {
+    PreOrder { stack: vec![(root_node, 0)] }
+}
+
+// children before parents, so the root comes out last
+pub fn post_order(root_node: &Node) -> PostOrder<'_> {
+    PostOrder { stack: vec![(root_node, 0, false)] }
+}
+
+// level by level, left to right
+pub fn breadth_first(root_node: &Node) -> BreadthFirst<'_> {
+    BreadthFirst { queue: VecDeque::from([(root_node, 0)]) }
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = (&'a Node, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        if let Node::Element { children, .. } = node {
+            // pushed in reverse so the first child is popped first
+            self.stack.extend(children.iter().rev().map(|child| (child, depth + 1)));
+        }
+        Some((node, depth))
+    }
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = (&'a Node, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, expanded) = self.stack.pop()?;
+            match node {
+                Node::Element { children, .. } if !expanded && !children.is_empty() => {
+                    self.stack.push((node, depth, true));
+                    self.stack.extend(children.iter().rev().map(|child| (child, depth + 1, false)));
+                }
+                _ => return Some((node, depth)),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = (&'a Node, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.queue.pop_front()?;
+        if let Node::Element { children, .. } = node {
+            self.queue.extend(children.iter().map(|child| (child, depth + 1)));
+        }
+        Some((node, depth))
+    }
+}
+
+// what a visitor wants to happen after `enter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitControl {
+    // keep going into the children
+    Continue,
+    // don't visit the children of this node, `leave` is still called for it
+    SkipChildren,
+    // end the whole walk right away, no more `leave` calls
+    Stop,
+}
+
+// hooks for walking the tree without writing the recursion again.
+// `enter` runs before the children of a node and `leave` after them.
+pub trait Visitor<'a> {
+    fn enter(&mut self, _node: &'a Node, _depth: usize) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn leave(&mut self, _node: &'a Node, _depth: usize) {}
+}
+
+// same as Visitor but it gets to change the nodes. children are visited after
+// `enter` returns, so changes made there (e.g. dropping children) are seen by the walk.
+pub trait VisitorMut {
+    fn enter(&mut self, _node: &mut Node, _depth: usize) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn leave(&mut self, _node: &mut Node, _depth: usize) {}
+}
+
+// returns false if the visitor stopped the walk
+pub fn walk<'a, V: Visitor<'a>>(root_node: &'a Node, visitor: &mut V) -> bool {
+    walk_node(root_node, 0, visitor)
+}
+
+fn walk_node<'a, V: Visitor<'a>>(node: &'a Node, depth: usize, visitor: &mut V) -> bool {
+    match visitor.enter(node, depth) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => {}
+        VisitControl::Continue => {
+            if let Node::Element { children, .. } = node {
+                for child in children {
+                    if !walk_node(child, depth + 1, visitor) {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+    visitor.leave(node, depth);
+    true
+}
+
+// returns false if the visitor stopped the walk
+pub fn walk_mut<V: VisitorMut>(root_node: &mut Node, visitor: &mut V) -> bool {
+    walk_node_mut(root_node, 0, visitor)
+}
+
+fn walk_node_mut<V: VisitorMut>(node: &mut Node, depth: usize, visitor: &mut V) -> bool {
+    match visitor.enter(node, depth) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => {}
+        VisitControl::Continue => {
+            if let Node::Element { children, .. } = node {
+                for child in children.iter_mut() {
+                    if !walk_node_mut(child, depth + 1, visitor) {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+    visitor.leave(node, depth);
+    true
+}
+
+impl Node {
+    // all the text under the node joined together, without any separators
+    pub fn text_content(&self) -> String {
+        pre_order(self)
+            .filter_map(|(node, _)| match node {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element { .. } => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Node;
+    use crate::traversal::*;
+    use crate::html;
+
+    fn tree() -> Node {
+        html! { a { b { d } c { "e" } } }
+    }
+
+    fn label(node: &Node) -> String {
+        match node {
+            Node::Element { tag_name, .. } => tag_name.clone(),
+            Node::Text(text) => text.clone(),
+        }
+    }
+
+    fn labels<'a>(walk: impl Iterator<Item = (&'a Node, usize)>) -> Vec<(String, usize)> {
+        walk.map(|(node, depth)| (label(node), depth)).collect()
+    }
+
+    fn pairs(expected: &[(&str, usize)]) -> Vec<(String, usize)> {
+        expected.iter().map(|(name, depth)| (name.to_string(), *depth)).collect()
+    }
+
+    #[test]
+    fn test_iterator_orders() {
+        let root = tree();
+        assert_eq!(labels(pre_order(&root)), pairs(&[("a", 0), ("b", 1), ("d", 2), ("c", 1), ("e", 2)]));
+        assert_eq!(labels(post_order(&root)), pairs(&[("d", 2), ("b", 1), ("e", 2), ("c", 1), ("a", 0)]));
+        assert_eq!(labels(breadth_first(&root)), pairs(&[("a", 0), ("b", 1), ("c", 1), ("d", 2), ("e", 2)]));
+    }
+
+    #[test]
+    fn test_iterators_are_lazy() {
+        let root = tree();
+        let mut walk = pre_order(&root);
+        walk.next();
+        walk.next();
+        // only the pending siblings and children are held, not the whole tree
+        assert_eq!(walk.stack.len(), 2);
+    }
+
+    struct Recorder {
+        events: Vec<String>,
+        skip: &'static str,
+        stop: &'static str,
+    }
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn enter(&mut self, node: &'a Node, depth: usize) -> VisitControl {
+            self.events.push(format!("enter {} {}", label(node), depth));
+            match label(node) {
+                name if name == self.skip => VisitControl::SkipChildren,
+                name if name == self.stop => VisitControl::Stop,
+                _ => VisitControl::Continue,
+            }
+        }
+
+        fn leave(&mut self, node: &'a Node, _depth: usize) {
+            self.events.push(format!("leave {}", label(node)));
+        }
+    }
+
+    #[test]
+    fn test_visitor_skip_and_stop() {
+        let root = tree();
+        let mut recorder = Recorder { events: vec![], skip: "b", stop: "e" };
+        assert!(!walk(&root, &mut recorder));
+        assert_eq!(recorder.events, vec![
+            "enter a 0", "enter b 1", "leave b", "enter c 1", "enter e 2",
+        ]);
+    }
+
+    struct Uppercase;
+
+    impl VisitorMut for Uppercase {
+        fn enter(&mut self, node: &mut Node, _depth: usize) -> VisitControl {
+            match node {
+                Node::Text(text) => *text = text.to_uppercase(),
+                Node::Element { tag_name, .. } if tag_name == "b" => return VisitControl::SkipChildren,
+                _ => {}
+            }
+            VisitControl::Continue
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut root = tree();
+        assert!(walk_mut(&mut root, &mut Uppercase));
+        assert_eq!(labels(pre_order(&root)).last().unwrap().0, "E");
+    }
+}
@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use reqwest::Url;
+use crate::Node;
+use crate::entities::decode_entities;
+
+// Small node helpers the extraction modules share. Tag names are compared
+// lowercased, since the parser keeps them as written, and attribute values and
+// text are read with their entities decoded where they become output.
+
+// block-level elements, the ones that start a new line or paragraph
+pub(crate) const BLOCK_TAGS: [&str; 40] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "dd", "details", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2",
+    "h3", "h4", "h5", "h6", "header", "hr", "html", "li", "main", "nav", "ol", "p", "pre", "section", "summary", "table", "tbody", "td", "th", "tr", "ul",
+];
+
+// the lowercased tag name, empty for text
+pub(crate) fn tag_of(node: &Node) -> String {
+    match node {
+        Node::Element { tag_name, .. } => tag_name.to_ascii_lowercase(),
+        Node::Text(_) => String::new(),
+    }
+}
+
+// an element with this tag name, ignoring case
+pub(crate) fn is_tag(node: &Node, name: &str) -> bool {
+    matches!(node, Node::Element { tag_name, .. } if tag_name.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn is_block(node: &Node) -> bool {
+    BLOCK_TAGS.contains(&tag_of(node).as_str())
+}
+
+// the raw attribute value, entities and all
+pub(crate) fn attribute<'a>(node: &'a Node, name: &str) -> Option<&'a str> {
+    match node {
+        Node::Element { attributes, .. } => attributes.get(name).map(String::as_str),
+        Node::Text(_) => None,
+    }
+}
+
+// the attribute value with its entities decoded, like the &amp; in a query string
+pub(crate) fn decoded_attribute<'a>(node: &'a Node, name: &str) -> Option<Cow<'a, str>> {
+    attribute(node, name).map(decode_entities)
+}
+
+pub(crate) fn children_of(node: &Node) -> &[Node] {
+    match node {
+        Node::Element { children, .. } => children,
+        Node::Text(_) => &[],
+    }
+}
+
+// all the text under the node, entities decoded and every run of whitespace
+// turned into one space
+pub(crate) fn collapsed_text(node: &Node) -> String {
+    decode_entities(&node.text_content()).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// a URL value made absolute against `base` when it can be, otherwise the
+// decoded, trimmed value itself, for values that should come out either way
+pub(crate) fn resolve_or_raw(base: Option<&Url>, raw: &str) -> String {
+    let raw = decode_entities(raw);
+    let raw = raw.trim();
+    match base.and_then(|base| base.join(raw).ok()) {
+        Some(url) => url.to_string(),
+        None => raw.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::*;
+    use crate::html;
+
+    #[test]
+    fn test_node_helpers() {
+        let node = html! { DIV(title = "Tom &amp; Jerry") { " a\n " b { "&lt;b&gt;" } } };
+        assert_eq!(tag_of(&node), "div");
+        assert!(is_tag(&node, "div") && is_block(&node));
+        assert_eq!(attribute(&node, "title"), Some("Tom &amp; Jerry"));
+        assert_eq!(attribute(&node, "id"), None);
+        assert_eq!(decoded_attribute(&node, "title").as_deref(), Some("Tom & Jerry"));
+        assert_eq!(collapsed_text(&node), "a <b>");
+        assert_eq!(children_of(&node).len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_or_raw() {
+        let base = Url::parse("https://example.com/a/").unwrap();
+        assert_eq!(resolve_or_raw(Some(&base), " b?x=1&amp;y=2 "), "https://example.com/a/b?x=1&y=2");
+        assert_eq!(resolve_or_raw(Some(&base), "#top"), "https://example.com/a/#top");
+        assert_eq!(resolve_or_raw(None, "/relative"), "/relative");
+    }
+}
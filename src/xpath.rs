@@ -0,0 +1,1198 @@
+use crate::Node;
+
+// XPath 1.0 over the Node tree.
+//
+// Nodes don't know their parents or siblings, so evaluation first flattens the
+// tree into an arena in document order where every entry knows its parent and
+// where its subtree ends. Index 0 is the document root (what `/` selects) and the
+// node passed in becomes its only child, so `/html/body` and `//p` work the way
+// they do in lxml. Attributes are nodes too, ordered right after their element.
+//
+// Element and attribute names are compared ASCII case-insensitively, since HTML
+// doesn't care about case. There are no comments or processing instructions in
+// the tree, so comment() and processing-instruction() never match, and variables
+// ($name) are rejected when the expression is parsed.
+
+// what an expression evaluates to
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPathValue<'a> {
+    Nodes(Vec<XPathNode<'a>>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum XPathNode<'a> {
+    // the document root above the node the query ran on
+    Document(&'a Node),
+    Element(&'a Node),
+    Text(&'a Node),
+    Attribute { name: &'a str, value: &'a str },
+}
+
+// node identity, the way XPath compares nodes. attributes compare by name and value
+impl PartialEq for XPathNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (XPathNode::Document(a), XPathNode::Document(b))
+            | (XPathNode::Element(a), XPathNode::Element(b))
+            | (XPathNode::Text(a), XPathNode::Text(b)) => std::ptr::eq(*a, *b),
+            (XPathNode::Attribute { name, value }, XPathNode::Attribute { name: other_name, value: other_value }) => {
+                name == other_name && value == other_value
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> XPathNode<'a> {
+    // the XPath string-value: all the descendant text for elements and the document
+    pub fn string_value(&self) -> String {
+        match self {
+            XPathNode::Document(node) | XPathNode::Element(node) | XPathNode::Text(node) => node.text_content(),
+            XPathNode::Attribute { value, .. } => value.to_string(),
+        }
+    }
+
+    // the tree node behind it, None for attributes
+    pub fn node(&self) -> Option<&'a Node> {
+        match self {
+            XPathNode::Document(node) | XPathNode::Element(node) | XPathNode::Text(node) => Some(node),
+            XPathNode::Attribute { .. } => None,
+        }
+    }
+}
+
+impl<'a> XPathValue<'a> {
+    // the matched tree nodes, attributes left out. empty for non node-set results
+    pub fn nodes(&self) -> Vec<&'a Node> {
+        match self {
+            XPathValue::Nodes(nodes) => nodes.iter().filter_map(|node| node.node()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // one string per node for node-sets, the string conversion otherwise.
+    // handy for expressions ending in /text() or /@href
+    pub fn strings(&self) -> Vec<String> {
+        match self {
+            XPathValue::Nodes(nodes) => nodes.iter().map(|node| node.string_value()).collect(),
+            XPathValue::String(value) => vec![value.clone()],
+            XPathValue::Number(value) => vec![number_to_string(*value)],
+            XPathValue::Boolean(value) => vec![value.to_string()],
+        }
+    }
+
+    // XPath string() conversion: the first node for node-sets
+    pub fn as_string(&self) -> String {
+        match self {
+            XPathValue::Nodes(nodes) => nodes.first().map(|node| node.string_value()).unwrap_or_default(),
+            XPathValue::String(value) => value.clone(),
+            XPathValue::Number(value) => number_to_string(*value),
+            XPathValue::Boolean(value) => value.to_string(),
+        }
+    }
+
+    // XPath number() conversion
+    pub fn as_number(&self) -> f64 {
+        match self {
+            XPathValue::Number(value) => *value,
+            XPathValue::Boolean(value) => if *value { 1.0 } else { 0.0 },
+            _ => string_to_number(&self.as_string()),
+        }
+    }
+
+    // XPath boolean() conversion
+    pub fn as_boolean(&self) -> bool {
+        match self {
+            XPathValue::Nodes(nodes) => !nodes.is_empty(),
+            XPathValue::String(value) => !value.is_empty(),
+            XPathValue::Number(value) => *value != 0.0 && !value.is_nan(),
+            XPathValue::Boolean(value) => *value,
+        }
+    }
+}
+
+pub(crate) fn number_to_string(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() }
+    } else if value == value.trunc() && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+fn string_to_number(value: &str) -> f64 {
+    let trimmed = value.trim();
+    let valid = !trimmed.is_empty()
+        && trimmed.trim_start_matches('-').chars().all(|c| c.is_ascii_digit() || c == '.')
+        && trimmed.matches('.').count() <= 1
+        && trimmed.matches('-').count() <= 1
+        && trimmed.trim_start_matches('-').chars().any(|c| c.is_ascii_digit());
+    if valid { trimmed.parse().unwrap_or(f64::NAN) } else { f64::NAN }
+}
+
+// ---- compiling ----------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    expression: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DoubleDot,
+    At,
+    Comma,
+    DoubleColon,
+    Pipe,
+    Plus,
+    Minus,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    // a name test, `*` and `prefix:*` included
+    Name(String),
+    FunctionName(String),
+    NodeType(String),
+    AxisName(String),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+}
+
+impl Token {
+    // after these a `*` or a name is a name test, after anything else it's an operator
+    fn starts_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::At | Token::DoubleColon | Token::LeftParen | Token::LeftBracket | Token::Comma
+                | Token::And | Token::Or | Token::Mod | Token::Div | Token::Multiply
+                | Token::Slash | Token::DoubleSlash | Token::Pipe | Token::Plus | Token::Minus
+                | Token::Equals | Token::NotEquals | Token::Less | Token::LessEquals
+                | Token::Greater | Token::GreaterEquals
+        )
+    }
+}
+
+fn lex(expression: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut pos = 0;
+    let is_name_start = |c: char| c.is_alphabetic() || c == '_';
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.';
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let start = pos;
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        let next = chars.get(pos + 1).copied();
+        let operand_expected = tokens.last().is_none_or(|(token, _)| token.starts_operand());
+        let token = match c {
+            '/' if next == Some('/') => { pos += 2; Token::DoubleSlash }
+            '/' => { pos += 1; Token::Slash }
+            '(' => { pos += 1; Token::LeftParen }
+            ')' => { pos += 1; Token::RightParen }
+            '[' => { pos += 1; Token::LeftBracket }
+            ']' => { pos += 1; Token::RightBracket }
+            '@' => { pos += 1; Token::At }
+            ',' => { pos += 1; Token::Comma }
+            '|' => { pos += 1; Token::Pipe }
+            '+' => { pos += 1; Token::Plus }
+            '-' => { pos += 1; Token::Minus }
+            '=' => { pos += 1; Token::Equals }
+            ':' if next == Some(':') => { pos += 2; Token::DoubleColon }
+            '!' if next == Some('=') => { pos += 2; Token::NotEquals }
+            '<' if next == Some('=') => { pos += 2; Token::LessEquals }
+            '<' => { pos += 1; Token::Less }
+            '>' if next == Some('=') => { pos += 2; Token::GreaterEquals }
+            '>' => { pos += 1; Token::Greater }
+            '*' if operand_expected => { pos += 1; Token::Name("*".to_string()) }
+            '*' => { pos += 1; Token::Multiply }
+            '.' if next == Some('.') => { pos += 2; Token::DoubleDot }
+            '.' if !next.is_some_and(|c| c.is_ascii_digit()) => { pos += 1; Token::Dot }
+            '"' | '\'' => {
+                let end = chars[pos + 1..].iter().position(|&q| q == c).ok_or_else(|| format!("unterminated string at position {}", start))?;
+                let literal: String = chars[pos + 1..pos + 1 + end].iter().collect();
+                pos += end + 2;
+                Token::Literal(literal)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                    pos += 1;
+                }
+                let text: String = chars[start..pos].iter().collect();
+                Token::Number(text.parse().map_err(|_| format!("invalid number '{}' at position {}", text, start))?)
+            }
+            '$' => {
+                pos += 1;
+                let name_start = pos;
+                while pos < chars.len() && is_name_char(chars[pos]) {
+                    pos += 1;
+                }
+                Token::Variable(chars[name_start..pos].iter().collect())
+            }
+            c if is_name_start(c) => {
+                while pos < chars.len() && is_name_char(chars[pos]) {
+                    pos += 1;
+                }
+                // prefix:local or prefix:*, but not axis::
+                if chars.get(pos) == Some(&':') && chars.get(pos + 1) != Some(&':') {
+                    if chars.get(pos + 1) == Some(&'*') {
+                        pos += 2;
+                    } else if chars.get(pos + 1).is_some_and(|&c| is_name_start(c)) {
+                        pos += 1;
+                        while pos < chars.len() && is_name_char(chars[pos]) {
+                            pos += 1;
+                        }
+                    }
+                }
+                let name: String = chars[start..pos].iter().collect();
+                let mut lookahead = pos;
+                while lookahead < chars.len() && chars[lookahead].is_whitespace() {
+                    lookahead += 1;
+                }
+                if !operand_expected {
+                    match name.as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "mod" => Token::Mod,
+                        "div" => Token::Div,
+                        _ => return Err(format!("expected an operator but found '{}' at position {}", name, start)),
+                    }
+                } else if chars.get(lookahead) == Some(&'(') {
+                    match name.as_str() {
+                        "node" | "text" | "comment" | "processing-instruction" => Token::NodeType(name),
+                        _ => Token::FunctionName(name),
+                    }
+                } else if chars.get(lookahead) == Some(&':') && chars.get(lookahead + 1) == Some(&':') {
+                    Token::AxisName(name)
+                } else {
+                    Token::Name(name)
+                }
+            }
+            c => return Err(format!("unexpected '{}' at position {}", c, start)),
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    // self::, a keyword in Rust
+    Itself,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Axis> {
+        Some(match name {
+            "ancestor" => Axis::Ancestor,
+            "ancestor-or-self" => Axis::AncestorOrSelf,
+            "attribute" => Axis::Attribute,
+            "child" => Axis::Child,
+            "descendant" => Axis::Descendant,
+            "descendant-or-self" => Axis::DescendantOrSelf,
+            "following" => Axis::Following,
+            "following-sibling" => Axis::FollowingSibling,
+            "namespace" => Axis::Namespace,
+            "parent" => Axis::Parent,
+            "preceding" => Axis::Preceding,
+            "preceding-sibling" => Axis::PrecedingSibling,
+            "self" => Axis::Itself,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum NodeTest {
+    // `*` when None
+    Name(Option<String>),
+    Node,
+    Text,
+    // comment() and processing-instruction(), nothing in the tree matches them
+    Nothing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOperator {
+    Or,
+    And,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    Plus,
+    Minus,
+    Multiply,
+    Div,
+    Mod,
+    Union,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    // a location path, from the document root when absolute
+    Path { absolute: bool, steps: Vec<Step> },
+    // (expr)[predicates]/steps
+    Filter { primary: Box<Expr>, predicates: Vec<Expr>, steps: Vec<Step> },
+    Literal(String),
+    Number(f64),
+    Function(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Last, Position, Count, Id, LocalName, NamespaceUri, Name,
+    String, Concat, StartsWith, Contains, SubstringBefore, SubstringAfter, Substring,
+    StringLength, NormalizeSpace, Translate,
+    Boolean, Not, True, False, Lang,
+    Number, Sum, Floor, Ceiling, Round,
+}
+
+impl Function {
+    // the function and how many arguments it takes (min, max)
+    fn lookup(name: &str) -> Option<(Function, usize, usize)> {
+        Some(match name {
+            "last" => (Function::Last, 0, 0),
+            "position" => (Function::Position, 0, 0),
+            "count" => (Function::Count, 1, 1),
+            "id" => (Function::Id, 1, 1),
+            "local-name" => (Function::LocalName, 0, 1),
+            "namespace-uri" => (Function::NamespaceUri, 0, 1),
+            "name" => (Function::Name, 0, 1),
+            "string" => (Function::String, 0, 1),
+            "concat" => (Function::Concat, 2, usize::MAX),
+            "starts-with" => (Function::StartsWith, 2, 2),
+            "contains" => (Function::Contains, 2, 2),
+            "substring-before" => (Function::SubstringBefore, 2, 2),
+            "substring-after" => (Function::SubstringAfter, 2, 2),
+            "substring" => (Function::Substring, 2, 3),
+            "string-length" => (Function::StringLength, 0, 1),
+            "normalize-space" => (Function::NormalizeSpace, 0, 1),
+            "translate" => (Function::Translate, 3, 3),
+            "boolean" => (Function::Boolean, 1, 1),
+            "not" => (Function::Not, 1, 1),
+            "true" => (Function::True, 0, 0),
+            "false" => (Function::False, 0, 0),
+            "lang" => (Function::Lang, 1, 1),
+            "number" => (Function::Number, 0, 1),
+            "sum" => (Function::Sum, 1, 1),
+            "floor" => (Function::Floor, 1, 1),
+            "ceiling" => (Function::Ceiling, 1, 1),
+            "round" => (Function::Round, 1, 1),
+            _ => return None,
+        })
+    }
+}
+
+struct XPathParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    length: usize,
+}
+
+impl XPathParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, position)| *position).unwrap_or(self.length)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position())
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(token) => self.error(&format!("unexpected {:?}", token)),
+            None => self.error("unexpected end of expression"),
+        }
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), String> {
+        if self.eat(expected) { Ok(()) } else { Err(self.unexpected()) }
+    }
+
+    fn binary<F>(&mut self, operators: &[(Token, BinaryOperator)], next: F) -> Result<Expr, String>
+    where
+        F: Fn(&mut Self) -> Result<Expr, String>,
+    {
+        let mut left = next(self)?;
+        'outer: loop {
+            for (token, operator) in operators {
+                if self.eat(token) {
+                    let right = next(self)?;
+                    left = Expr::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[(Token::Or, BinaryOperator::Or)], Self::and_expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[(Token::And, BinaryOperator::And)], Self::equality_expr)
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[(Token::Equals, BinaryOperator::Equals), (Token::NotEquals, BinaryOperator::NotEquals)], Self::relational_expr)
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                (Token::Less, BinaryOperator::Less),
+                (Token::LessEquals, BinaryOperator::LessEquals),
+                (Token::Greater, BinaryOperator::Greater),
+                (Token::GreaterEquals, BinaryOperator::GreaterEquals),
+            ],
+            Self::additive_expr,
+        )
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[(Token::Plus, BinaryOperator::Plus), (Token::Minus, BinaryOperator::Minus)], Self::multiplicative_expr)
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[(Token::Multiply, BinaryOperator::Multiply), (Token::Div, BinaryOperator::Div), (Token::Mod, BinaryOperator::Mod)],
+            Self::unary_expr,
+        )
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.unary_expr()?)));
+        }
+        self.binary(&[(Token::Pipe, BinaryOperator::Union)], Self::path_expr)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Variable(name)) => Err(self.error(&format!("variables are not supported (${})", name))),
+            Some(Token::LeftParen) | Some(Token::Literal(_)) | Some(Token::Number(_)) | Some(Token::FunctionName(_)) => {
+                let primary = self.primary_expr()?;
+                let mut predicates = Vec::new();
+                while self.peek() == Some(&Token::LeftBracket) {
+                    predicates.push(self.predicate()?);
+                }
+                let mut steps = Vec::new();
+                if matches!(self.peek(), Some(Token::Slash) | Some(Token::DoubleSlash)) {
+                    steps = self.relative_path(true)?;
+                }
+                if predicates.is_empty() && steps.is_empty() {
+                    Ok(primary)
+                } else {
+                    Ok(Expr::Filter { primary: Box::new(primary), predicates, steps })
+                }
+            }
+            Some(Token::Slash) => {
+                self.pos += 1;
+                let steps = if self.starts_step() { self.relative_path(false)? } else { Vec::new() };
+                Ok(Expr::Path { absolute: true, steps })
+            }
+            Some(Token::DoubleSlash) => Ok(Expr::Path { absolute: true, steps: self.relative_path(true)? }),
+            _ if self.starts_step() => Ok(Expr::Path { absolute: false, steps: self.relative_path(false)? }),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Name(_)) | Some(Token::NodeType(_)) | Some(Token::AxisName(_)) | Some(Token::At) | Some(Token::Dot) | Some(Token::DoubleDot)
+        )
+    }
+
+    // steps separated by / and //. with `leading` the path starts with one of those
+    fn relative_path(&mut self, leading: bool) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        let mut separator = leading;
+        loop {
+            if separator {
+                if self.eat(&Token::DoubleSlash) {
+                    steps.push(Step { axis: Axis::DescendantOrSelf, test: NodeTest::Node, predicates: Vec::new() });
+                } else if !self.eat(&Token::Slash) {
+                    return Ok(steps);
+                }
+            }
+            steps.push(self.step()?);
+            separator = true;
+        }
+    }
+
+    fn step(&mut self) -> Result<Step, String> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step { axis: Axis::Itself, test: NodeTest::Node, predicates: Vec::new() });
+        }
+        if self.eat(&Token::DoubleDot) {
+            return Ok(Step { axis: Axis::Parent, test: NodeTest::Node, predicates: Vec::new() });
+        }
+        let axis = match self.peek().cloned() {
+            Some(Token::At) => {
+                self.pos += 1;
+                Axis::Attribute
+            }
+            Some(Token::AxisName(name)) => {
+                let axis = Axis::from_name(&name).ok_or_else(|| self.error(&format!("unknown axis '{}'", name)))?;
+                self.pos += 1;
+                self.expect(&Token::DoubleColon)?;
+                axis
+            }
+            _ => Axis::Child,
+        };
+        let test = match self.peek().cloned() {
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                if name == "*" || name.ends_with(":*") { NodeTest::Name(None) } else { NodeTest::Name(Some(name)) }
+            }
+            Some(Token::NodeType(kind)) => {
+                self.pos += 1;
+                self.expect(&Token::LeftParen)?;
+                if kind == "processing-instruction" && let Some(Token::Literal(_)) = self.peek() {
+                    self.pos += 1;
+                }
+                self.expect(&Token::RightParen)?;
+                match kind.as_str() {
+                    "node" => NodeTest::Node,
+                    "text" => NodeTest::Text,
+                    _ => NodeTest::Nothing,
+                }
+            }
+            _ => return Err(self.unexpected()),
+        };
+        let mut predicates = Vec::new();
+        while self.peek() == Some(&Token::LeftBracket) {
+            predicates.push(self.predicate()?);
+        }
+        Ok(Step { axis, test, predicates })
+    }
+
+    fn predicate(&mut self) -> Result<Expr, String> {
+        self.expect(&Token::LeftBracket)?;
+        let expr = self.expr()?;
+        self.expect(&Token::RightBracket)?;
+        Ok(expr)
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr, String> {
+        let start = self.position();
+        match self.peek().cloned() {
+            Some(Token::LeftParen) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::Literal(value)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(value))
+            }
+            Some(Token::Number(value)) => {
+                self.pos += 1;
+                Ok(Expr::Number(value))
+            }
+            Some(Token::FunctionName(name)) => {
+                let (function, min, max) = Function::lookup(&name).ok_or_else(|| self.error(&format!("unknown function '{}'", name)))?;
+                self.pos += 1;
+                self.expect(&Token::LeftParen)?;
+                let mut arguments = Vec::new();
+                if !self.eat(&Token::RightParen) {
+                    loop {
+                        arguments.push(self.expr()?);
+                        if self.eat(&Token::RightParen) {
+                            break;
+                        }
+                        self.expect(&Token::Comma)?;
+                    }
+                }
+                if arguments.len() < min || arguments.len() > max {
+                    return Err(format!("wrong number of arguments for {}() at position {}", name, start));
+                }
+                Ok(Expr::Function(function, arguments))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+impl XPath {
+    pub fn compile(expression: &str) -> Result<XPath, String> {
+        let tokens = lex(expression)?;
+        let mut parser = XPathParser { tokens, pos: 0, length: expression.chars().count() };
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(parser.unexpected());
+        }
+        Ok(XPath { expression: expr })
+    }
+
+    // evaluate with the document root above root_node as the context node
+    pub fn evaluate<'a>(&self, root_node: &'a Node) -> Result<XPathValue<'a>, String> {
+        let document = Document::new(root_node);
+        let context = Context { node: XNode { index: 0, attribute: None }, position: 1, size: 1 };
+        let value = document.eval(&self.expression, &context)?;
+        Ok(match value {
+            Value::Nodes(nodes) => XPathValue::Nodes(nodes.into_iter().map(|node| document.public(node)).collect()),
+            Value::String(value) => XPathValue::String(value),
+            Value::Number(value) => XPathValue::Number(value),
+            Value::Boolean(value) => XPathValue::Boolean(value),
+        })
+    }
+}
+
+// ---- evaluating ---------------------------------------------------------------
+
+// a node of the arena. the derived order is document order: an element, then its
+// attributes (sorted by name), then its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct XNode {
+    index: usize,
+    attribute: Option<usize>,
+}
+
+struct Entry<'a> {
+    node: &'a Node,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // end of the subtree, exclusive
+    end: usize,
+    attributes: Vec<(&'a str, &'a str)>,
+}
+
+struct Document<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Nodes(Vec<XNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+struct Context {
+    node: XNode,
+    position: usize,
+    size: usize,
+}
+
+impl<'a> Document<'a> {
+    fn new(root_node: &'a Node) -> Self {
+        let mut document = Document { entries: Vec::new() };
+        // the document root shares the Node with its only child, entry 1
+        document.entries.push(Entry { node: root_node, parent: None, children: vec![1], end: 0, attributes: Vec::new() });
+        document.add(root_node, 0);
+        document.entries[0].end = document.entries.len();
+        document
+    }
+
+    fn add(&mut self, node: &'a Node, parent: usize) -> usize {
+        let index = self.entries.len();
+        let mut attributes = Vec::new();
+        if let Node::Element { attributes: node_attributes, .. } = node {
+            attributes = node_attributes.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+            attributes.sort();
+        }
+        self.entries.push(Entry { node, parent: Some(parent), children: Vec::new(), end: index + 1, attributes });
+        if let Node::Element { children, .. } = node {
+            for child in children {
+                let child_index = self.add(child, index);
+                self.entries[index].children.push(child_index);
+            }
+        }
+        self.entries[index].end = self.entries.len();
+        index
+    }
+
+    fn public(&self, node: XNode) -> XPathNode<'a> {
+        let entry = &self.entries[node.index];
+        match node.attribute {
+            Some(attribute) => {
+                let (name, value) = entry.attributes[attribute];
+                XPathNode::Attribute { name, value }
+            }
+            None if node.index == 0 => XPathNode::Document(entry.node),
+            None => match entry.node {
+                Node::Element { .. } => XPathNode::Element(entry.node),
+                Node::Text(_) => XPathNode::Text(entry.node),
+            },
+        }
+    }
+
+    fn string_value(&self, node: XNode) -> String {
+        self.public(node).string_value()
+    }
+
+    fn tree(index: usize) -> XNode {
+        XNode { index, attribute: None }
+    }
+
+    fn is_element(&self, index: usize) -> bool {
+        index != 0 && matches!(self.entries[index].node, Node::Element { .. })
+    }
+
+    fn element_name(&self, node: XNode) -> Option<&'a str> {
+        let entry = &self.entries[node.index];
+        match (node.attribute, entry.node) {
+            (Some(attribute), _) => Some(entry.attributes[attribute].0),
+            (None, Node::Element { tag_name, .. }) if node.index != 0 => Some(tag_name),
+            _ => None,
+        }
+    }
+
+    // nodes along the axis, in axis order (reverse document order for reverse axes)
+    fn axis(&self, node: XNode, axis: Axis) -> Vec<XNode> {
+        let entry = &self.entries[node.index];
+        let ancestors = |start: Option<usize>| {
+            let mut found = Vec::new();
+            let mut current = start;
+            while let Some(index) = current {
+                found.push(Self::tree(index));
+                current = self.entries[index].parent;
+            }
+            found
+        };
+        if node.attribute.is_some() {
+            // an attribute has its element as parent, and no children or siblings
+            return match axis {
+                Axis::Itself => vec![node],
+                Axis::Parent => vec![Self::tree(node.index)],
+                Axis::Ancestor => ancestors(Some(node.index)),
+                Axis::AncestorOrSelf => std::iter::once(node).chain(ancestors(Some(node.index))).collect(),
+                Axis::DescendantOrSelf => vec![node],
+                Axis::Following => (node.index + 1..self.entries.len()).map(Self::tree).collect(),
+                Axis::Preceding => self.axis(Self::tree(node.index), Axis::Preceding),
+                _ => Vec::new(),
+            };
+        }
+        match axis {
+            Axis::Itself => vec![node],
+            Axis::Child => entry.children.iter().map(|&index| Self::tree(index)).collect(),
+            Axis::Descendant => (node.index + 1..entry.end).map(Self::tree).collect(),
+            Axis::DescendantOrSelf => (node.index..entry.end).map(Self::tree).collect(),
+            Axis::Parent => entry.parent.map(Self::tree).into_iter().collect(),
+            Axis::Ancestor => ancestors(entry.parent),
+            Axis::AncestorOrSelf => ancestors(Some(node.index)),
+            Axis::Attribute => (0..entry.attributes.len()).map(|attribute| XNode { index: node.index, attribute: Some(attribute) }).collect(),
+            Axis::Namespace => Vec::new(),
+            Axis::FollowingSibling | Axis::PrecedingSibling => {
+                let Some(parent) = entry.parent else { return Vec::new() };
+                let siblings = &self.entries[parent].children;
+                let at = siblings.iter().position(|&index| index == node.index).unwrap_or(0);
+                if axis == Axis::FollowingSibling {
+                    siblings[at + 1..].iter().map(|&index| Self::tree(index)).collect()
+                } else {
+                    siblings[..at].iter().rev().map(|&index| Self::tree(index)).collect()
+                }
+            }
+            Axis::Following => (entry.end..self.entries.len()).map(Self::tree).collect(),
+            Axis::Preceding => {
+                let ancestors: Vec<usize> = ancestors(entry.parent).iter().map(|node| node.index).collect();
+                (0..node.index).rev().filter(|index| !ancestors.contains(index)).map(Self::tree).collect()
+            }
+        }
+    }
+
+    fn passes(&self, node: XNode, axis: Axis, test: &NodeTest) -> bool {
+        match test {
+            NodeTest::Node => true,
+            NodeTest::Nothing => false,
+            NodeTest::Text => node.attribute.is_none() && node.index != 0 && matches!(self.entries[node.index].node, Node::Text(_)),
+            NodeTest::Name(name) => {
+                // the principal node type is attribute on the attribute axis, element elsewhere
+                let right_kind = if axis == Axis::Attribute { node.attribute.is_some() } else { node.attribute.is_none() && self.is_element(node.index) };
+                right_kind
+                    && match name {
+                        None => true,
+                        Some(name) => {
+                            let local = name.rsplit(':').next().unwrap_or(name);
+                            self.element_name(node).is_some_and(|actual| actual.eq_ignore_ascii_case(local))
+                        }
+                    }
+            }
+        }
+    }
+
+    fn filter(&self, nodes: Vec<XNode>, predicates: &[Expr]) -> Result<Vec<XNode>, String> {
+        let mut nodes = nodes;
+        for predicate in predicates {
+            let size = nodes.len();
+            let mut kept = Vec::new();
+            for (i, node) in nodes.into_iter().enumerate() {
+                let context = Context { node, position: i + 1, size };
+                let keep = match self.eval(predicate, &context)? {
+                    Value::Number(number) => number == (i + 1) as f64,
+                    value => self.boolean(&value),
+                };
+                if keep {
+                    kept.push(node);
+                }
+            }
+            nodes = kept;
+        }
+        Ok(nodes)
+    }
+
+    fn steps(&self, start: Vec<XNode>, steps: &[Step]) -> Result<Vec<XNode>, String> {
+        let mut current = start;
+        for step in steps {
+            let mut next = Vec::new();
+            for &node in &current {
+                let candidates: Vec<XNode> = self.axis(node, step.axis).into_iter().filter(|&candidate| self.passes(candidate, step.axis, &step.test)).collect();
+                next.extend(self.filter(candidates, &step.predicates)?);
+            }
+            next.sort();
+            next.dedup();
+            current = next;
+        }
+        Ok(current)
+    }
+
+    fn string(&self, value: &Value) -> String {
+        match value {
+            Value::Nodes(nodes) => nodes.first().map(|&node| self.string_value(node)).unwrap_or_default(),
+            Value::String(value) => value.clone(),
+            Value::Number(value) => number_to_string(*value),
+            Value::Boolean(value) => value.to_string(),
+        }
+    }
+
+    fn number(&self, value: &Value) -> f64 {
+        match value {
+            Value::Number(value) => *value,
+            Value::Boolean(value) => if *value { 1.0 } else { 0.0 },
+            _ => string_to_number(&self.string(value)),
+        }
+    }
+
+    fn boolean(&self, value: &Value) -> bool {
+        match value {
+            Value::Nodes(nodes) => !nodes.is_empty(),
+            Value::String(value) => !value.is_empty(),
+            Value::Number(value) => *value != 0.0 && !value.is_nan(),
+            Value::Boolean(value) => *value,
+        }
+    }
+
+    fn node_set(&self, value: Value, what: &str) -> Result<Vec<XNode>, String> {
+        match value {
+            Value::Nodes(nodes) => Ok(nodes),
+            _ => Err(format!("{} needs a node-set", what)),
+        }
+    }
+
+    // = != < <= > >= with the XPath 1.0 rules for node-sets
+    fn compare(&self, operator: BinaryOperator, left: &Value, right: &Value) -> bool {
+        let atoms = |value: &Value| -> Vec<Value> {
+            match value {
+                Value::Nodes(nodes) => nodes.iter().map(|&node| Value::String(self.string_value(node))).collect(),
+                other => vec![other.clone()],
+            }
+        };
+        match (left, right) {
+            (Value::Nodes(_), Value::Boolean(_)) | (Value::Boolean(_), Value::Nodes(_)) => {
+                self.compare_atoms(operator, &Value::Boolean(self.boolean(left)), &Value::Boolean(self.boolean(right)))
+            }
+            _ => atoms(left).iter().any(|l| atoms(right).iter().any(|r| self.compare_atoms(operator, l, r))),
+        }
+    }
+
+    fn compare_atoms(&self, operator: BinaryOperator, left: &Value, right: &Value) -> bool {
+        match operator {
+            BinaryOperator::Equals | BinaryOperator::NotEquals => {
+                let equal = match (left, right) {
+                    (Value::Boolean(_), _) | (_, Value::Boolean(_)) => self.boolean(left) == self.boolean(right),
+                    (Value::Number(_), _) | (_, Value::Number(_)) => self.number(left) == self.number(right),
+                    _ => self.string(left) == self.string(right),
+                };
+                equal == (operator == BinaryOperator::Equals)
+            }
+            _ => {
+                let (l, r) = (self.number(left), self.number(right));
+                match operator {
+                    BinaryOperator::Less => l < r,
+                    BinaryOperator::LessEquals => l <= r,
+                    BinaryOperator::Greater => l > r,
+                    _ => l >= r,
+                }
+            }
+        }
+    }
+
+    fn eval(&self, expr: &Expr, context: &Context) -> Result<Value, String> {
+        Ok(match expr {
+            Expr::Literal(value) => Value::String(value.clone()),
+            Expr::Number(value) => Value::Number(*value),
+            Expr::Negate(inner) => Value::Number(-self.number(&self.eval(inner, context)?)),
+            Expr::Path { absolute, steps } => {
+                let start = if *absolute { Self::tree(0) } else { context.node };
+                Value::Nodes(self.steps(vec![start], steps)?)
+            }
+            Expr::Filter { primary, predicates, steps } => {
+                let nodes = self.node_set(self.eval(primary, context)?, "a predicate or path")?;
+                let nodes = self.filter(nodes, predicates)?;
+                Value::Nodes(self.steps(nodes, steps)?)
+            }
+            Expr::Binary(operator, left, right) => match operator {
+                BinaryOperator::Or => Value::Boolean(self.boolean(&self.eval(left, context)?) || self.boolean(&self.eval(right, context)?)),
+                BinaryOperator::And => Value::Boolean(self.boolean(&self.eval(left, context)?) && self.boolean(&self.eval(right, context)?)),
+                BinaryOperator::Union => {
+                    let mut nodes = self.node_set(self.eval(left, context)?, "|")?;
+                    nodes.extend(self.node_set(self.eval(right, context)?, "|")?);
+                    nodes.sort();
+                    nodes.dedup();
+                    Value::Nodes(nodes)
+                }
+                BinaryOperator::Equals | BinaryOperator::NotEquals | BinaryOperator::Less | BinaryOperator::LessEquals
+                | BinaryOperator::Greater | BinaryOperator::GreaterEquals => {
+                    Value::Boolean(self.compare(*operator, &self.eval(left, context)?, &self.eval(right, context)?))
+                }
+                BinaryOperator::Plus | BinaryOperator::Minus | BinaryOperator::Multiply | BinaryOperator::Div | BinaryOperator::Mod => {
+                    let l = self.number(&self.eval(left, context)?);
+                    let r = self.number(&self.eval(right, context)?);
+                    Value::Number(match operator {
+                        BinaryOperator::Plus => l + r,
+                        BinaryOperator::Minus => l - r,
+                        BinaryOperator::Multiply => l * r,
+                        BinaryOperator::Div => l / r,
+                        _ => l % r,
+                    })
+                }
+            },
+            Expr::Function(function, arguments) => self.call(*function, arguments, context)?,
+        })
+    }
+
+    fn call(&self, function: Function, arguments: &[Expr], context: &Context) -> Result<Value, String> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.eval(argument, context)?);
+        }
+        // the argument as a string, or the context node when it was left out
+        let string_arg = |i: usize| -> String {
+            match values.get(i) {
+                Some(value) => self.string(value),
+                None => self.string_value(context.node),
+            }
+        };
+        let first_node = |name: &str| -> Result<Option<XNode>, String> {
+            match values.first() {
+                Some(value) => Ok(self.node_set(value.clone(), name)?.first().copied()),
+                None => Ok(Some(context.node)),
+            }
+        };
+        Ok(match function {
+            Function::Last => Value::Number(context.size as f64),
+            Function::Position => Value::Number(context.position as f64),
+            Function::Count => Value::Number(self.node_set(values[0].clone(), "count()")?.len() as f64),
+            Function::Id => {
+                let wanted: Vec<String> = match &values[0] {
+                    Value::Nodes(nodes) => nodes.iter().flat_map(|&node| self.string_value(node).split_whitespace().map(str::to_string).collect::<Vec<_>>()).collect(),
+                    other => self.string(other).split_whitespace().map(str::to_string).collect(),
+                };
+                let found = (1..self.entries.len())
+                    .filter(|&index| self.entries[index].attributes.iter().any(|(name, value)| *name == "id" && wanted.iter().any(|w| w == value)))
+                    .map(Self::tree)
+                    .collect();
+                Value::Nodes(found)
+            }
+            Function::LocalName | Function::Name => {
+                let name = first_node("name()")?.and_then(|node| self.element_name(node)).unwrap_or("");
+                let name = if function == Function::LocalName { name.rsplit(':').next().unwrap_or(name) } else { name };
+                Value::String(name.to_string())
+            }
+            Function::NamespaceUri => {
+                first_node("namespace-uri()")?;
+                Value::String(String::new())
+            }
+            Function::String => Value::String(string_arg(0)),
+            Function::Concat => Value::String(values.iter().map(|value| self.string(value)).collect()),
+            Function::StartsWith => Value::Boolean(string_arg(0).starts_with(&string_arg(1))),
+            Function::Contains => Value::Boolean(string_arg(0).contains(&string_arg(1))),
+            Function::SubstringBefore => {
+                let (haystack, needle) = (string_arg(0), string_arg(1));
+                Value::String(haystack.find(&needle).map(|at| haystack[..at].to_string()).unwrap_or_default())
+            }
+            Function::SubstringAfter => {
+                let (haystack, needle) = (string_arg(0), string_arg(1));
+                Value::String(haystack.find(&needle).map(|at| haystack[at + needle.len()..].to_string()).unwrap_or_default())
+            }
+            Function::Substring => {
+                let value = string_arg(0);
+                let start = xpath_round(self.number(&values[1]));
+                let end = match values.get(2) {
+                    Some(length) => start + xpath_round(self.number(length)),
+                    None => f64::INFINITY,
+                };
+                Value::String(
+                    value
+                        .chars()
+                        .enumerate()
+                        .filter(|(i, _)| {
+                            let position = (*i + 1) as f64;
+                            position >= start && position < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                )
+            }
+            Function::StringLength => Value::Number(string_arg(0).chars().count() as f64),
+            Function::NormalizeSpace => Value::String(string_arg(0).split_whitespace().collect::<Vec<_>>().join(" ")),
+            Function::Translate => {
+                let from: Vec<char> = string_arg(1).chars().collect();
+                let to: Vec<char> = string_arg(2).chars().collect();
+                Value::String(
+                    string_arg(0)
+                        .chars()
+                        .filter_map(|c| match from.iter().position(|&f| f == c) {
+                            Some(at) => to.get(at).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+            Function::Boolean => Value::Boolean(self.boolean(&values[0])),
+            Function::Not => Value::Boolean(!self.boolean(&values[0])),
+            Function::True => Value::Boolean(true),
+            Function::False => Value::Boolean(false),
+            Function::Lang => {
+                let wanted = string_arg(0).to_lowercase();
+                let start = if context.node.attribute.is_some() { Self::tree(context.node.index) } else { context.node };
+                let lang = self
+                    .axis(start, Axis::AncestorOrSelf)
+                    .into_iter()
+                    .find_map(|node| self.entries[node.index].attributes.iter().find(|(name, _)| name.eq_ignore_ascii_case("lang") || name.eq_ignore_ascii_case("xml:lang")).map(|(_, value)| value.to_lowercase()));
+                Value::Boolean(lang.is_some_and(|lang| lang == wanted || lang.starts_with(&format!("{}-", wanted))))
+            }
+            Function::Number => match values.first() {
+                Some(value) => Value::Number(self.number(value)),
+                None => Value::Number(string_to_number(&self.string_value(context.node))),
+            },
+            Function::Sum => {
+                let nodes = self.node_set(values[0].clone(), "sum()")?;
+                Value::Number(nodes.iter().map(|&node| string_to_number(&self.string_value(node))).sum())
+            }
+            Function::Floor => Value::Number(self.number(&values[0]).floor()),
+            Function::Ceiling => Value::Number(self.number(&values[0]).ceil()),
+            Function::Round => Value::Number(xpath_round(self.number(&values[0]))),
+        })
+    }
+}
+
+// XPath rounds halves towards positive infinity
+fn xpath_round(value: f64) -> f64 {
+    if value.is_nan() || value.is_infinite() {
+        value
+    } else {
+        (value + 0.5).floor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpath::*;
+
+    #[test]
+    fn test_lexer_disambiguation() {
+        let tokens: Vec<Token> = lex("div * 2 and @*").unwrap().into_iter().map(|(token, _)| token).collect();
+        assert_eq!(tokens, vec![
+            Token::Name("div".to_string()), Token::Multiply, Token::Number(2.0), Token::And, Token::At, Token::Name("*".to_string()),
+        ]);
+        let tokens: Vec<Token> = lex("child::text() | count(a)").unwrap().into_iter().map(|(token, _)| token).collect();
+        assert_eq!(tokens, vec![
+            Token::AxisName("child".to_string()), Token::DoubleColon, Token::NodeType("text".to_string()), Token::LeftParen, Token::RightParen,
+            Token::Pipe, Token::FunctionName("count".to_string()), Token::LeftParen, Token::Name("a".to_string()), Token::RightParen,
+        ]);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(XPath::compile("//div[").unwrap_err(), "unexpected end of expression at position 6");
+        assert_eq!(XPath::compile("foo(1)").unwrap_err(), "unknown function 'foo' at position 0");
+        assert_eq!(XPath::compile("bogus::a").unwrap_err(), "unknown axis 'bogus' at position 0");
+        assert_eq!(XPath::compile("count()").unwrap_err(), "wrong number of arguments for count() at position 0");
+        assert!(XPath::compile("$x").is_err());
+        assert!(XPath::compile("'open").is_err());
+        assert!(XPath::compile("a b").is_err());
+    }
+
+    #[test]
+    fn test_number_conversions() {
+        assert_eq!(number_to_string(3.0), "3");
+        assert_eq!(number_to_string(-0.0), "0");
+        assert_eq!(number_to_string(2.5), "2.5");
+        assert_eq!(number_to_string(f64::NAN), "NaN");
+        assert_eq!(number_to_string(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(string_to_number(" 12.5 "), 12.5);
+        assert_eq!(string_to_number("-.5"), -0.5);
+        assert!(string_to_number("1e3").is_nan());
+        assert!(string_to_number("$10").is_nan());
+        assert_eq!(xpath_round(2.5), 3.0);
+        assert_eq!(xpath_round(-2.5), -2.0);
+    }
+}